@@ -1,4 +1,12 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use dsp_perf::envelope::{AdsrSettings, Curve};
+use dsp_perf::filter::{FilterMode, FilterModel, FilterSettings, Oversampling};
+use dsp_perf::pitch::{Hz, MidiNote, Octaves};
+use dsp_perf::polyphony::PolySynth;
+use dsp_perf::sample::Sample;
+use dsp_perf::simd::{self, SimdLevel};
+use dsp_perf::{array_of_structs, fixed_batch_size, voice_per_lane, SynthEngine};
 
 #[derive(Copy, Clone)]
 struct AOSIncrementerData {
@@ -208,7 +216,7 @@ fn parabolic_sine(x: f64) -> f64 {
 }
 
 fn wrap01(x: f64) -> f64 {
-    if x >= 0.0 && x <= 1.0 {
+    if (0.0..=1.0).contains(&x) {
         x
     } else {
         x.rem_euclid(1.0)
@@ -269,69 +277,334 @@ fn dsp_bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("Rendering (full implementation)");
 
     for size in &[64usize, 256, 1024, 4096] {
-        group.bench_with_input(
-            BenchmarkId::new("One frame per call", *size),
-            size,
-            |b, size| {
+        for engine in dsp_perf::ENGINES {
+            group.bench_with_input(BenchmarkId::new(engine.name, *size), size, |b, size| {
                 b.iter_with_setup(
-                    || {
-                        (
-                            vec![0.0f64; *size],
-                            dsp_perf::one_frame_per_call::Synth::new(44100.0),
-                        )
-                    },
-                    |(mut data, mut incrementer)| {
-                        incrementer.render(&mut data);
+                    || (vec![0.0f64; *size], (engine.create)(44100.0)),
+                    |(mut data, mut synth)| {
+                        synth.render(&mut data);
                         data
                     },
                 );
-            },
-        );
+            });
+        }
+    }
 
-        group.bench_with_input(
-            BenchmarkId::new("Fixed batch size (struct-of-arrays)", *size),
-            size,
-            |b, size| {
+    group.finish();
+}
+
+fn dsp_envelope_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Amp envelope (full implementation)");
+
+    // The attack is longer than any of the buffers, so the envelope is moving
+    // for the whole render instead of sitting at the sustain level.
+    let settings = AdsrSettings {
+        attack: 1.0,
+        curve: Curve::Exponential,
+        ..AdsrSettings::default()
+    };
+
+    for size in &[64usize, 256, 1024, 4096] {
+        for engine in dsp_perf::ENGINES {
+            group.bench_with_input(BenchmarkId::new(engine.name, *size), size, |b, size| {
                 b.iter_with_setup(
                     || {
-                        (
-                            vec![0.0f64; *size],
-                            dsp_perf::fixed_batch_size::Synth::new(44100.0),
-                        )
+                        let mut synth = (engine.create)(44100.0);
+                        synth.set_amp_envelope(Some(settings));
+                        synth.note_on();
+                        (vec![0.0f64; *size], synth)
                     },
-                    |(mut data, mut incrementer)| {
-                        // synth.render(&mut data)
-                        incrementer.render(&mut data);
+                    |(mut data, mut synth)| {
+                        synth.render(&mut data);
                         data
                     },
                 );
-            },
-        );
+            });
+        }
+    }
 
-        group.bench_with_input(
-            BenchmarkId::new("Fixed batch size (array-of-structs)", *size),
-            size,
-            |b, size| {
+    group.finish();
+}
+
+fn dsp_filter_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Filter (full implementation)");
+
+    // LFO modulation recomputes the coefficients on every frame.
+    let settings = FilterSettings {
+        mode: FilterMode::LowPass,
+        cutoff: Hz(1000.0),
+        resonance: 0.5,
+        cutoff_lfo_amount: Octaves(1.0),
+        ..FilterSettings::default()
+    };
+
+    for size in &[64usize, 256, 1024, 4096] {
+        for engine in dsp_perf::ENGINES {
+            group.bench_with_input(BenchmarkId::new(engine.name, *size), size, |b, size| {
                 b.iter_with_setup(
                     || {
-                        (
-                            vec![0.0f64; *size],
-                            dsp_perf::array_of_structs::Synth::new(44100.0),
-                        )
+                        let mut synth = (engine.create)(44100.0);
+                        synth.set_filter(Some(settings));
+                        (vec![0.0f64; *size], synth)
                     },
-                    |(mut data, mut incrementer)| {
-                        // synth.render(&mut data)
-                        incrementer.render(&mut data);
+                    |(mut data, mut synth)| {
+                        synth.render(&mut data);
                         data
                     },
                 );
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn dsp_ladder_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Ladder filter (full implementation)");
+
+    for &oversampling in &[Oversampling::Off, Oversampling::X2, Oversampling::X4] {
+        let settings = FilterSettings {
+            model: FilterModel::Ladder(oversampling),
+            cutoff: Hz(1000.0),
+            resonance: 0.5,
+            cutoff_lfo_amount: Octaves(1.0),
+            ..FilterSettings::default()
+        };
+
+        for engine in dsp_perf::ENGINES {
+            group.bench_with_input(
+                BenchmarkId::new(engine.name, oversampling.factor()),
+                &1024,
+                |b, size| {
+                    b.iter_with_setup(
+                        || {
+                            let mut synth = (engine.create)(44100.0);
+                            synth.set_filter(Some(settings));
+                            (vec![0.0f64; *size], synth)
+                        },
+                        |(mut data, mut synth)| {
+                            synth.render(&mut data);
+                            data
+                        },
+                    );
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_batch_size<E: SynthEngine>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    batch_size: usize,
+) {
+    group.bench_with_input(BenchmarkId::new(name, batch_size), &4096, |b, size| {
+        b.iter_with_setup(
+            || (vec![0.0f64; *size], E::new(44100.0)),
+            |(mut data, mut synth)| {
+                synth.render(&mut data);
+                data
             },
         );
+    });
+}
+
+fn dsp_batch_size_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Batch size (4096 frames)");
+
+    let soa = "Fixed batch size (struct-of-arrays)";
+    bench_batch_size::<fixed_batch_size::Synth<16>>(&mut group, soa, 16);
+    bench_batch_size::<fixed_batch_size::Synth<32>>(&mut group, soa, 32);
+    bench_batch_size::<fixed_batch_size::Synth<64>>(&mut group, soa, 64);
+    bench_batch_size::<fixed_batch_size::Synth<128>>(&mut group, soa, 128);
+    bench_batch_size::<fixed_batch_size::Synth<256>>(&mut group, soa, 256);
+
+    let aos = "Fixed batch size (array-of-structs)";
+    bench_batch_size::<array_of_structs::Synth<16>>(&mut group, aos, 16);
+    bench_batch_size::<array_of_structs::Synth<32>>(&mut group, aos, 32);
+    bench_batch_size::<array_of_structs::Synth<64>>(&mut group, aos, 64);
+    bench_batch_size::<array_of_structs::Synth<128>>(&mut group, aos, 128);
+    bench_batch_size::<array_of_structs::Synth<256>>(&mut group, aos, 256);
+
+    group.finish();
+}
+
+fn bench_poly_synth<S: Sample>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    create: fn(f64) -> Box<dyn SynthEngine<S>>,
+    voice_count: usize,
+    sample_type: &str,
+) {
+    group.bench_with_input(
+        BenchmarkId::new(name, format!("{}, {}", voice_count, sample_type)),
+        &4096,
+        |b, size| {
+            b.iter_with_setup(
+                || {
+                    let mut poly = PolySynth::new(create, 44100.0, voice_count);
+                    poly.for_each_voice(|synth| {
+                        synth.set_amp_envelope(Some(AdsrSettings::default()))
+                    });
+                    for voice in 0..voice_count {
+                        poly.note_on(MidiNote(48.0 + voice as f64));
+                    }
+                    (vec![S::ZERO; *size], poly)
+                },
+                |(mut data, mut poly)| {
+                    poly.render(&mut data);
+                    data
+                },
+            );
+        },
+    );
+}
+
+fn bench_voice_per_lane<const LANES: usize, S: Sample>(
+    group: &mut BenchmarkGroup<WallTime>,
+    sample_type: &str,
+) {
+    group.bench_with_input(
+        BenchmarkId::new("Voice per lane", format!("{}, {}", LANES, sample_type)),
+        &4096,
+        |b, size| {
+            b.iter_with_setup(
+                || {
+                    let mut synth = voice_per_lane::Synth::<LANES, S>::new(44100.0);
+                    synth.set_amp_envelope(Some(AdsrSettings::default()));
+                    for voice in 0..LANES {
+                        synth.note_on(MidiNote(48.0 + voice as f64));
+                    }
+                    (vec![S::ZERO; *size], synth)
+                },
+                |(mut data, mut synth)| {
+                    synth.render(&mut data);
+                    data
+                },
+            );
+        },
+    );
+}
+
+fn dsp_voices_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Voices (4096 frames)");
+
+    // Every voice is held, so the time-parallel engines render all of them
+    // one after the other, while the voice-parallel one steps them together.
+    for &voice_count in &[4usize, 8] {
+        for engine in dsp_perf::ENGINES {
+            bench_poly_synth(&mut group, engine.name, engine.create, voice_count, "f64");
+            bench_poly_synth(
+                &mut group,
+                engine.name,
+                engine.create_f32,
+                voice_count,
+                "f32",
+            );
+        }
     }
 
+    bench_voice_per_lane::<4, f64>(&mut group, "f64");
+    bench_voice_per_lane::<8, f64>(&mut group, "f64");
+    bench_voice_per_lane::<4, f32>(&mut group, "f32");
+    bench_voice_per_lane::<8, f32>(&mut group, "f32");
+
+    group.finish();
+}
+
+fn dsp_sample_type_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Sample type (4096 frames)");
+
+    for engine in dsp_perf::ENGINES {
+        group.bench_with_input(BenchmarkId::new(engine.name, "f64"), &4096, |b, size| {
+            b.iter_with_setup(
+                || (vec![0.0f64; *size], (engine.create)(44100.0)),
+                |(mut data, mut synth)| {
+                    synth.render(&mut data);
+                    data
+                },
+            );
+        });
+        group.bench_with_input(BenchmarkId::new(engine.name, "f32"), &4096, |b, size| {
+            b.iter_with_setup(
+                || (vec![0.0f32; *size], (engine.create_f32)(44100.0)),
+                |(mut data, mut synth)| {
+                    synth.render(&mut data);
+                    data
+                },
+            );
+        });
+    }
+
+    group.finish();
+}
+
+type Kernel<S> = fn(SimdLevel, &mut [S]);
+
+fn bench_kernels<S: Sample>(
+    group: &mut BenchmarkGroup<WallTime>,
+    kernels: [(&str, Kernel<S>); 3],
+    sample_type: &str,
+) {
+    let values: Vec<S> = (0..4096)
+        .map(|i| S::from_f64((i as f64 - 2048.0) / 1000.0))
+        .collect();
+
+    for (name, kernel) in kernels.iter() {
+        for level in SimdLevel::available() {
+            group.bench_with_input(
+                BenchmarkId::new(*name, format!("{:?}, {}", level, sample_type)),
+                &level,
+                |b, level| {
+                    b.iter_with_setup(
+                        || values.clone(),
+                        |mut data| {
+                            kernel(*level, &mut data);
+                            data
+                        },
+                    );
+                },
+            );
+        }
+    }
+}
+
+fn dsp_simd_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Fastmath kernels (4096 values)");
+    let kernels: [(&str, Kernel<f64>); 3] = [
+        ("exp2", simd::exp2_with),
+        ("wrap01", simd::wrap01_with),
+        ("parabolic_sine", simd::parabolic_sine_with),
+    ];
+    bench_kernels(&mut group, kernels, "f64");
+
+    let kernels: [(&str, Kernel<f32>); 3] = [
+        ("exp2", simd::exp2_f32_with),
+        ("wrap01", simd::wrap01_f32_with),
+        ("parabolic_sine", simd::parabolic_sine_f32_with),
+    ];
+    bench_kernels(&mut group, kernels, "f32");
+
     group.finish();
 }
 
 criterion_group!(benches, dsp_bench);
+criterion_group!(envelope_benches, dsp_envelope_bench);
+criterion_group!(filter_benches, dsp_filter_bench, dsp_ladder_bench);
+criterion_group!(batch_size_benches, dsp_batch_size_bench);
+criterion_group!(voices_benches, dsp_voices_bench);
+criterion_group!(simd_benches, dsp_simd_bench);
+criterion_group!(sample_type_benches, dsp_sample_type_bench);
 criterion_group!(mini_benches, dsp_mini_bench);
-criterion_main!(benches, mini_benches);
+criterion_main!(
+    benches,
+    envelope_benches,
+    filter_benches,
+    batch_size_benches,
+    voices_benches,
+    simd_benches,
+    sample_type_benches,
+    mini_benches
+);
@@ -1,55 +1,99 @@
-use crate::fastmath::{parabolic_sine, wrap01, exp2};
+use std::iter;
+use std::sync::Arc;
+
+use crate::engine::{Oscillator, SynthEngine};
+use crate::envelope::{
+    AdsrSegments, AdsrSettings, AdsrState, BreakpointSettings, BreakpointStages, BreakpointState,
+};
+use crate::fastmath::wrap01;
+use crate::filter::{
+    FilterModel, FilterSettings, LadderCoefficients, LadderState, SvfCoefficients, SvfState,
+};
+use crate::modulation::{ModDestination, ModSource};
+use crate::params::{Choice, ParamId};
+use crate::pitch::{Cents, Hz, Octaves, Semitones};
+use crate::sample::Sample;
+use crate::simd;
+use crate::smoothing::{SmoothedParam, Smoothing};
+use crate::tempo::{Division, Transport};
+use crate::waveform::{self, LfoFade, LfoMode, LfoWaveform, RandomSteps, Waveform};
+use crate::wavetable::Wavetable;
 
 const OSC_MAX_FREQ: f64 = 20480.0;
 
-const BATCH_SIZE: usize = 64;
+pub const DEFAULT_BATCH_SIZE: usize = 64;
 
 #[derive(Copy, Clone)]
-struct OscillatorAudioRate {
-    pub input_frequency_mod_ratio: f64,
-    pub phase_mod: f64,
-    pub frequency_mod: f64,
-    pub modulo: f64,
+struct OscillatorAudioRate<S> {
+    pub input_frequency_mod_ratio: S,
+    pub phase_mod: S,
+    /// Frequency modulation in octaves, added to the static pitch offsets.
+    pub frequency_mod: S,
+    pub modulo: S,
+    /// `modulo` plus `phase_mod`, wrapped into `[0, 1]`: the phase the shape
+    /// is rendered at. Set by `wrap_phase`.
+    pub phase: S,
 
     pub wrap_modulo: bool,
-    pub amplitude_mod: f64,
+    pub phase_increment: S,
+    /// Smoothed amplitude.
+    pub amplitude: S,
+    pub amplitude_mod: S,
+
+    pub sync_wrap: bool,
+    pub sync_modulo: S,
+    pub sync_phase_increment: S,
+    pub sync_from: S,
 }
 
-struct OscillatorHelper {
+struct OscillatorHelper<const BATCH_SIZE: usize, S> {
     pub sample_rate: f64,
-    pub input_frequency: f64,
-    pub octave_offset: f64,
-    pub semitone_offset: f64,
-    pub cent_offset: f64,
-    pub amplitude: f64,
+    pub input_frequency: Hz,
+    /// Static pitch offsets, in octaves, semitones and cents.
+    pub octave_offset: SmoothedParam,
+    pub semitone_offset: SmoothedParam,
+    pub cent_offset: SmoothedParam,
+    pub amplitude: SmoothedParam,
+
+    /// Hard sync: reset the phase whenever the master oscillator, described by
+    /// the `sync_*` audio rate inputs, wraps.
+    pub sync: bool,
 
-    pub audio_rate: [OscillatorAudioRate; BATCH_SIZE],
+    pub audio_rate: [OscillatorAudioRate<S>; BATCH_SIZE],
 
-    last_modulo: f64,
+    last_modulo: S,
 }
 
-impl OscillatorHelper {
+impl<const BATCH_SIZE: usize, S: Sample> OscillatorHelper<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         OscillatorHelper {
             sample_rate,
-            input_frequency: 0.0,
-            octave_offset: 0.0,
-            semitone_offset: 0.0,
-            cent_offset: 0.0,
-            amplitude: 1.0,
+            input_frequency: Hz(0.0),
+            octave_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            semitone_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            cent_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            amplitude: SmoothedParam::new(1.0, Smoothing::default(), sample_rate),
+            sync: false,
             audio_rate: [OscillatorAudioRate {
-                input_frequency_mod_ratio: 1.0,
-                frequency_mod: 0.0,
-                phase_mod: 0.0,
-                amplitude_mod: 1.0,
-                modulo: 0.0,
+                input_frequency_mod_ratio: S::ONE,
+                frequency_mod: S::ZERO,
+                phase_mod: S::ZERO,
+                amplitude: S::ONE,
+                amplitude_mod: S::ONE,
+                modulo: S::ZERO,
+                phase: S::ZERO,
                 wrap_modulo: false,
+                phase_increment: S::ZERO,
+                sync_wrap: false,
+                sync_modulo: S::ZERO,
+                sync_phase_increment: S::ZERO,
+                sync_from: S::ZERO,
             }; BATCH_SIZE],
-            last_modulo: 0.0,
+            last_modulo: S::ZERO,
         }
     }
 
-    fn set_frequency_mod<I: Iterator<Item = f64>>(&mut self, iter: I) {
+    fn set_frequency_mod<I: Iterator<Item = S>>(&mut self, iter: I) {
         self.audio_rate
             .iter_mut()
             .map(|d| &mut d.frequency_mod)
@@ -57,135 +101,1010 @@ impl OscillatorHelper {
             .for_each(|(dest, src)| *dest = src);
     }
 
-    #[inline(never)]
-    fn update(&mut self) {
-        let const_offset =
-            self.octave_offset * 12.0 + self.semitone_offset + self.cent_offset / 100.0;
+    fn set_amplitude_mod<I: Iterator<Item = S>>(&mut self, iter: I) {
+        self.audio_rate
+            .iter_mut()
+            .map(|d| &mut d.amplitude_mod)
+            .zip(iter)
+            .for_each(|(dest, src)| *dest = src);
+    }
 
+    /// Apply a modulation envelope on top of this batch's other modulation.
+    /// `amp_envelope` says whether `amplitude_mod` holds this batch's amp
+    /// envelope levels, rather than the previous batch's modulation.
+    fn modulate(&mut self, destination: ModDestination, levels: &[S], amp_envelope: bool) {
+        for (audio_rate, level) in self.audio_rate.iter_mut().zip(levels) {
+            match destination {
+                ModDestination::Frequency => audio_rate.frequency_mod += *level,
+                ModDestination::Phase => audio_rate.phase_mod = *level,
+                ModDestination::Amplitude if amp_envelope => audio_rate.amplitude_mod *= *level,
+                ModDestination::Amplitude => audio_rate.amplitude_mod = *level,
+                ModDestination::WavetablePosition => {}
+            }
+        }
+    }
+
+    /// Undo the phase and amplitude modulation left by `modulate`. Frequency
+    /// modulation is set from scratch every batch.
+    fn clear_modulation(&mut self) {
         for audio_rate in self.audio_rate.iter_mut() {
-            let frequency = (self.input_frequency
-                * audio_rate.input_frequency_mod_ratio
-                * exp2(audio_rate.frequency_mod + const_offset))
-            .max(-OSC_MAX_FREQ)
-            .min(OSC_MAX_FREQ);
+            audio_rate.phase_mod = S::ZERO;
+            audio_rate.amplitude_mod = S::ONE;
+        }
+    }
+
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        let sample_rate = self.sample_rate;
+        self.octave_offset.set_smoothing(smoothing, sample_rate);
+        self.semitone_offset.set_smoothing(smoothing, sample_rate);
+        self.cent_offset.set_smoothing(smoothing, sample_rate);
+        self.amplitude.set_smoothing(smoothing, sample_rate);
+    }
 
-            let phase_incr = frequency / self.sample_rate;
+    /// Restart rendering. Parameters that are still gliding jump to their
+    /// targets.
+    fn reset(&mut self) {
+        self.octave_offset.finish();
+        self.semitone_offset.finish();
+        self.cent_offset.finish();
+        self.amplitude.finish();
 
-            let wrap = if phase_incr > 0.0 && self.last_modulo >= 1.0 {
-                self.last_modulo -= 1.0;
+        self.last_modulo = S::ZERO;
+    }
+
+    fn sync_to(&mut self, master: &OscillatorHelper<BATCH_SIZE, S>) {
+        for (audio_rate, master) in self.audio_rate.iter_mut().zip(master.audio_rate.iter()) {
+            audio_rate.sync_wrap = master.wrap_modulo;
+            audio_rate.sync_modulo = master.modulo;
+            audio_rate.sync_phase_increment = master.phase_increment;
+        }
+    }
+
+    #[inline(never)]
+    fn update(&mut self, frames: usize) {
+        // The `simd` kernels need their inputs next to each other.
+        let mut frequency_ratios = [S::ZERO; BATCH_SIZE];
+
+        for (audio_rate, frequency_ratio) in self.audio_rate[..frames]
+            .iter_mut()
+            .zip(frequency_ratios.iter_mut())
+        {
+            let const_offset = Octaves(self.octave_offset.tick())
+                + Octaves::from(Semitones(self.semitone_offset.tick()))
+                + Octaves::from(Cents(self.cent_offset.tick()));
+            audio_rate.amplitude = S::from_f64(self.amplitude.tick());
+
+            *frequency_ratio = audio_rate.frequency_mod + S::from_f64(const_offset.0);
+        }
+        simd::exp2(&mut frequency_ratios[..frames]);
+
+        let input_frequency = S::from_f64(self.input_frequency.0);
+        let max_frequency = S::from_f64(OSC_MAX_FREQ);
+        let sample_rate = S::from_f64(self.sample_rate);
+
+        for (audio_rate, frequency_ratio) in self.audio_rate[..frames]
+            .iter_mut()
+            .zip(frequency_ratios.iter())
+        {
+            let frequency =
+                (input_frequency * audio_rate.input_frequency_mod_ratio * *frequency_ratio)
+                    .clamp(-max_frequency, max_frequency);
+
+            let phase_incr = frequency / sample_rate;
+
+            let mut wrap = if phase_incr > S::ZERO && self.last_modulo >= S::ONE {
+                self.last_modulo -= S::ONE;
                 true
-            } else if phase_incr < 0.0 && self.last_modulo <= 0.0 {
-                self.last_modulo += 1.0;
+            } else if phase_incr < S::ZERO && self.last_modulo <= S::ZERO {
+                self.last_modulo += S::ONE;
                 true
             } else {
                 false
             };
 
+            if self.sync && audio_rate.sync_wrap {
+                let advance = waveform::wrap_fraction(
+                    audio_rate.sync_modulo,
+                    audio_rate.sync_phase_increment,
+                ) * phase_incr;
+                audio_rate.sync_from = wrap01(self.last_modulo - advance);
+                self.last_modulo = wrap01(advance);
+                wrap = true;
+            }
+
             audio_rate.modulo = self.last_modulo;
             audio_rate.wrap_modulo = wrap;
+            audio_rate.phase_increment = phase_incr;
 
             self.last_modulo += phase_incr;
         }
     }
+
+    fn wrap_phase(&mut self, frames: usize) {
+        let mut phases = [S::ZERO; BATCH_SIZE];
+
+        for (phase, audio_rate) in phases[..frames].iter_mut().zip(self.audio_rate.iter()) {
+            *phase = audio_rate.modulo + audio_rate.phase_mod;
+        }
+        simd::wrap01(&mut phases[..frames]);
+
+        for (audio_rate, phase) in self.audio_rate[..frames].iter_mut().zip(phases.iter()) {
+            audio_rate.phase = *phase;
+        }
+    }
 }
 
-struct BandLimitedOscillator {
-    helper: OscillatorHelper,
-    output: [f64; BATCH_SIZE],
+struct BandLimitedOscillator<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    waveform: Waveform,
+    pulse_width: f64,
+    output: [S; BATCH_SIZE],
 }
 
-impl BandLimitedOscillator {
+impl<const BATCH_SIZE: usize, S: Sample> BandLimitedOscillator<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         BandLimitedOscillator {
             helper: OscillatorHelper::new(sample_rate),
-            output: [0.0; BATCH_SIZE],
+            waveform: Waveform::Sine,
+            pulse_width: 0.5,
+            output: [S::ZERO; BATCH_SIZE],
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        self.helper.update(frames);
+        self.helper.wrap_phase(frames);
+
+        let pulse_width = S::from_f64(self.pulse_width);
+
+        match self.waveform {
+            Waveform::Sine if !self.helper.sync => self.render_sine(frames),
+            Waveform::Sine => self.render_shape(frames, |modulo, _| waveform::sine(modulo)),
+            Waveform::Saw => self.render_shape(frames, waveform::saw),
+            Waveform::Square => self.render_shape(frames, |modulo, phase_increment| {
+                waveform::square(modulo, phase_increment, pulse_width)
+            }),
+            Waveform::Triangle => self.render_shape(frames, waveform::triangle),
         }
     }
 
-    fn render(&mut self) {
-        self.helper.update();
+    #[inline(always)]
+    fn render_shape<F: Fn(S, S) -> S>(&mut self, frames: usize, shape: F) {
+        for (output, audio_rate) in self.output[..frames]
+            .iter_mut()
+            .zip(self.helper.audio_rate.iter())
+        {
+            let mut out = shape(audio_rate.phase, audio_rate.phase_increment);
 
-        for (output, audio_rate) in self.output.iter_mut().zip(self.helper.audio_rate.iter()) {
-            let modulo = wrap01(audio_rate.modulo + audio_rate.phase_mod);
-            let angle = modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            *output = parabolic_sine(-angle) * self.helper.amplitude * audio_rate.amplitude_mod;
+            if self.helper.sync {
+                out += waveform::sync_correction(
+                    |modulo| shape(modulo, S::ZERO),
+                    audio_rate.sync_wrap,
+                    audio_rate.sync_modulo,
+                    audio_rate.sync_phase_increment,
+                    audio_rate.sync_from,
+                    audio_rate.phase,
+                    audio_rate.phase_increment,
+                );
+            }
+
+            *output = out * audio_rate.amplitude * audio_rate.amplitude_mod;
+        }
+    }
+
+    /// `render_shape` for the sine, with the shape computed for the whole
+    /// batch at once.
+    fn render_sine(&mut self, frames: usize) {
+        let output = &mut self.output[..frames];
+
+        for (output, audio_rate) in output.iter_mut().zip(self.helper.audio_rate.iter()) {
+            *output = waveform::sine_argument(audio_rate.phase);
+        }
+        simd::parabolic_sine(output);
+
+        for (output, audio_rate) in output.iter_mut().zip(self.helper.audio_rate.iter()) {
+            *output = *output * audio_rate.amplitude * audio_rate.amplitude_mod;
         }
     }
 }
 
-struct LFO {
-    helper: OscillatorHelper,
-    output: [(f64, f64); BATCH_SIZE],
+#[derive(Copy, Clone)]
+struct WavetableAudioRate<S> {
+    pub position_mod: S,
+    pub output: S,
 }
 
-impl LFO {
+struct WavetableOscillator<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    wavetable: Option<Arc<Wavetable>>,
+    position: f64,
+    audio_rate: [WavetableAudioRate<S>; BATCH_SIZE],
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> WavetableOscillator<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        WavetableOscillator {
+            helper: OscillatorHelper::new(sample_rate),
+            wavetable: None,
+            position: 0.0,
+            audio_rate: [WavetableAudioRate {
+                position_mod: S::ZERO,
+                output: S::ZERO,
+            }; BATCH_SIZE],
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.wavetable.is_some()
+    }
+
+    /// Apply a modulation envelope, which can also move the position.
+    fn modulate(&mut self, destination: ModDestination, levels: &[S], amp_envelope: bool) {
+        match destination {
+            ModDestination::WavetablePosition => {
+                for (audio_rate, level) in self.audio_rate.iter_mut().zip(levels) {
+                    audio_rate.position_mod = *level;
+                }
+            }
+            _ => self.helper.modulate(destination, levels, amp_envelope),
+        }
+    }
+
+    fn clear_modulation(&mut self) {
+        self.helper.clear_modulation();
+        for audio_rate in self.audio_rate.iter_mut() {
+            audio_rate.position_mod = S::ZERO;
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        let wavetable = match self.wavetable {
+            Some(ref wavetable) => wavetable,
+            None => return,
+        };
+
+        self.helper.update(frames);
+        self.helper.wrap_phase(frames);
+
+        let position = S::from_f64(self.position);
+
+        for (audio_rate, helper_audio_rate) in self.audio_rate[..frames]
+            .iter_mut()
+            .zip(self.helper.audio_rate.iter())
+        {
+            audio_rate.output = wavetable.sample(
+                helper_audio_rate.phase,
+                helper_audio_rate.phase_increment,
+                position + audio_rate.position_mod,
+            ) * helper_audio_rate.amplitude
+                * helper_audio_rate.amplitude_mod;
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+struct LFO<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    waveform: LfoWaveform,
+    pulse_width: f64,
+    random: RandomSteps,
+    frequency: Hz,
+    tempo_sync: Option<Division>,
+    mode: LfoMode,
+    fade: LfoFade,
+    /// Set once a one-shot LFO has run its cycle.
+    finished: bool,
+    output: [(S, S); BATCH_SIZE],
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> LFO<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         LFO {
             helper: OscillatorHelper::new(sample_rate),
-            output: [(0.0, 0.0); BATCH_SIZE],
+            waveform: LfoWaveform::Sine,
+            pulse_width: 0.5,
+            random: RandomSteps::new(waveform::LFO_RANDOM_SEED),
+            frequency: Hz(0.0),
+            tempo_sync: None,
+            mode: LfoMode::FreeRunning,
+            fade: LfoFade::default(),
+            finished: false,
+            output: [(S::ZERO, S::ZERO); BATCH_SIZE],
         }
     }
 
-    fn render(&mut self) {
-        self.helper.update();
+    fn set_frequency(&mut self, frequency: Hz) {
+        self.frequency = frequency;
+        if self.tempo_sync.is_none() {
+            self.helper.input_frequency = frequency;
+        }
+    }
+
+    fn set_tempo_sync(&mut self, division: Option<Division>) {
+        self.tempo_sync = division;
+        if division.is_none() {
+            self.helper.input_frequency = self.frequency;
+        }
+    }
+
+    /// Lock the rate and phase of a tempo-synced LFO to the transport.
+    fn follow_transport(&mut self, transport: &Transport) {
+        if let Some(division) = self.tempo_sync {
+            self.helper.input_frequency = division.frequency(transport.bpm);
+            self.helper.last_modulo = S::from_f64(division.phase(transport.position));
+        }
+    }
+
+    fn set_mode(&mut self, mode: LfoMode) {
+        self.mode = mode;
+        self.finished = false;
+    }
+
+    fn set_delay(&mut self, delay: f64, fade_in: f64) {
+        self.fade.set_times(delay, fade_in, self.helper.sample_rate);
+    }
+
+    /// Start a note: restart the delay and fade-in and, unless the LFO is
+    /// free running, the cycle.
+    fn trigger(&mut self) {
+        self.fade.restart();
+
+        if self.mode != LfoMode::FreeRunning {
+            self.helper.last_modulo = S::ZERO;
+            self.random.advance();
+            self.finished = false;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.helper.reset();
+        self.random = RandomSteps::new(waveform::LFO_RANDOM_SEED);
+        self.fade.restart();
+        self.finished = false;
+    }
+
+    fn render(&mut self, frames: usize) {
+        self.helper.update(frames);
+
+        for audio_rate in self.helper.audio_rate[..frames].iter_mut() {
+            audio_rate.amplitude_mod = S::from_f64(self.fade.next_gain());
+        }
 
-        for (output, audio_rate) in self.output.iter_mut().zip(self.helper.audio_rate.iter()) {
-            let angle = audio_rate.modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            output.0 = parabolic_sine(-angle) * self.helper.amplitude * audio_rate.amplitude_mod;
+        let pulse_width = S::from_f64(self.pulse_width);
+        let quarter = S::from_f64(0.25);
 
-            let quad_angle =
-                (audio_rate.modulo + 0.25) * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
+        match self.waveform {
+            LfoWaveform::Sine => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::sine(modulo))
+            }
+            LfoWaveform::Triangle => self.render_shape(frames, quarter, |modulo, _| {
+                waveform::triangle(modulo, S::ZERO)
+            }),
+            LfoWaveform::RampUp => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::ramp_up(modulo))
+            }
+            LfoWaveform::RampDown => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::ramp_down(modulo))
+            }
+            LfoWaveform::Square => self.render_shape(frames, quarter, |modulo, _| {
+                waveform::pulse(modulo, pulse_width)
+            }),
+            // The random shapes have no phase to shift, so their quadrature
+            // output is the same as the main output.
+            LfoWaveform::SampleAndHold => {
+                self.render_shape(frames, S::ZERO, |_, random| S::from_f64(random.held()))
+            }
+            LfoWaveform::SmoothRandom => self.render_shape(frames, S::ZERO, |modulo, random| {
+                S::from_f64(random.smoothed(modulo.to_f64()))
+            }),
+        }
+    }
+
+    #[inline(always)]
+    fn render_shape<F: Fn(S, &RandomSteps) -> S>(
+        &mut self,
+        frames: usize,
+        quad_offset: S,
+        shape: F,
+    ) {
+        for (output, audio_rate) in self.output[..frames]
+            .iter_mut()
+            .zip(self.helper.audio_rate.iter())
+        {
+            if audio_rate.wrap_modulo {
+                if self.mode == LfoMode::OneShot {
+                    self.finished = true;
+                } else {
+                    self.random.advance();
+                }
+            }
+
+            // A finished one-shot holds the end of its cycle.
+            let modulo = if self.finished {
+                S::ONE
+            } else {
+                audio_rate.modulo
+            };
+
+            output.0 =
+                shape(modulo, &self.random) * audio_rate.amplitude * audio_rate.amplitude_mod;
+
+            let quad_modulo = {
+                let quad_modulo = modulo + quad_offset;
+                if quad_modulo >= S::ONE {
+                    quad_modulo - S::ONE
+                } else {
+                    quad_modulo
+                }
+            };
             output.1 =
-                parabolic_sine(-quad_angle) * self.helper.amplitude * audio_rate.amplitude_mod;
+                shape(quad_modulo, &self.random) * audio_rate.amplitude * audio_rate.amplitude_mod;
+        }
+    }
+}
+
+struct Envelope<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    segments: AdsrSegments,
+    state: AdsrState,
+    output: [S; BATCH_SIZE],
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> Envelope<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        Envelope {
+            enabled: false,
+            segments: AdsrSegments::new(&AdsrSettings::default(), sample_rate),
+            state: AdsrState::default(),
+            output: [S::ONE; BATCH_SIZE],
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        // Note events only happen between batches, so an idle or sustaining
+        // envelope stays at the same level for the whole batch.
+        if self.state.is_steady() {
+            let level = S::from_f64(self.state.next(&self.segments));
+            for output in self.output[..frames].iter_mut() {
+                *output = level;
+            }
+            return;
+        }
+
+        for output in self.output[..frames].iter_mut() {
+            *output = S::from_f64(self.state.next(&self.segments));
         }
     }
 }
 
-pub struct Synth {
-    osc1: BandLimitedOscillator,
-    osc2: BandLimitedOscillator,
-    lfo: LFO,
+struct ModEnvelope<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    destination: ModDestination,
+    stages: BreakpointStages,
+    state: BreakpointState,
+    output: [S; BATCH_SIZE],
 }
 
-impl Synth {
-    pub fn new(sample_rate: f64) -> Self {
+impl<const BATCH_SIZE: usize, S: Sample> ModEnvelope<BATCH_SIZE, S> {
+    fn new() -> Self {
+        ModEnvelope {
+            enabled: false,
+            destination: ModDestination::default(),
+            stages: BreakpointStages::default(),
+            state: BreakpointState::default(),
+            output: [S::ZERO; BATCH_SIZE],
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        if self.state.is_steady() {
+            let level = S::from_f64(self.state.next(&self.stages));
+            for output in self.output[..frames].iter_mut() {
+                *output = level;
+            }
+            return;
+        }
+
+        for output in self.output[..frames].iter_mut() {
+            *output = S::from_f64(self.state.next(&self.stages));
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct FilterAudioRate<S> {
+    /// Cutoff modulation in octaves, added to the static cutoff.
+    pub cutoff_mod: S,
+    pub resonance: S,
+
+    pub cutoff: S,
+    pub svf_coefficients: SvfCoefficients<S>,
+    pub ladder_coefficients: LadderCoefficients<S>,
+}
+
+struct Filter<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    settings: FilterSettings,
+    sample_rate: f64,
+    svf: SvfState<S>,
+    ladder: LadderState<S>,
+    audio_rate: [FilterAudioRate<S>; BATCH_SIZE],
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> Filter<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        Filter {
+            enabled: false,
+            settings: FilterSettings::default(),
+            sample_rate,
+            svf: SvfState::default(),
+            ladder: LadderState::default(),
+            audio_rate: [FilterAudioRate::default(); BATCH_SIZE],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.svf = SvfState::default();
+        self.ladder = LadderState::default();
+    }
+
+    /// Set the cutoff modulation and resonance from the LFO and, where
+    /// they're enabled, the amp and mod envelopes.
+    fn modulate(
+        &mut self,
+        frames: usize,
+        lfo: &[(S, S); BATCH_SIZE],
+        envelope: Option<&[S; BATCH_SIZE]>,
+        mod_envelope: Option<&[S; BATCH_SIZE]>,
+    ) {
+        for (i, audio_rate) in self.audio_rate[..frames].iter_mut().enumerate() {
+            let envelope = envelope.map_or(S::ZERO, |levels| levels[i]);
+            let mod_envelope = mod_envelope.map_or(S::ZERO, |levels| levels[i]);
+            let (cutoff_mod, resonance) =
+                self.settings.modulation(lfo[i].0, envelope, mod_envelope);
+            audio_rate.cutoff_mod = cutoff_mod;
+            audio_rate.resonance = resonance;
+        }
+    }
+
+    /// Filter a batch in place.
+    fn render(&mut self, output: &mut [S]) {
+        let frames = output.len();
+
+        let mut cutoff_ratios = [S::ZERO; BATCH_SIZE];
+        for (cutoff_ratio, audio_rate) in cutoff_ratios[..frames]
+            .iter_mut()
+            .zip(self.audio_rate.iter())
+        {
+            *cutoff_ratio = audio_rate.cutoff_mod;
+        }
+        simd::exp2(&mut cutoff_ratios[..frames]);
+
+        let static_cutoff = S::from_f64(self.settings.cutoff.0);
+        for (audio_rate, cutoff_ratio) in self.audio_rate[..frames]
+            .iter_mut()
+            .zip(cutoff_ratios.iter())
+        {
+            audio_rate.cutoff = static_cutoff * *cutoff_ratio;
+        }
+
+        match self.settings.model {
+            FilterModel::StateVariable => {
+                for audio_rate in self.audio_rate[..frames].iter_mut() {
+                    audio_rate.svf_coefficients = SvfCoefficients::new(
+                        audio_rate.cutoff,
+                        audio_rate.resonance,
+                        self.sample_rate,
+                    );
+                }
+
+                let mode = self.settings.mode;
+                for (output, audio_rate) in output.iter_mut().zip(self.audio_rate.iter()) {
+                    *output = self
+                        .svf
+                        .process(*output, &audio_rate.svf_coefficients, mode);
+                }
+            }
+            FilterModel::Ladder(oversampling) => {
+                for audio_rate in self.audio_rate[..frames].iter_mut() {
+                    audio_rate.ladder_coefficients = LadderCoefficients::new(
+                        audio_rate.cutoff,
+                        audio_rate.resonance,
+                        self.sample_rate,
+                        oversampling,
+                    );
+                }
+
+                for (output, audio_rate) in output.iter_mut().zip(self.audio_rate.iter()) {
+                    *output = self
+                        .ladder
+                        .process(*output, &audio_rate.ladder_coefficients);
+                }
+            }
+        }
+    }
+}
+
+pub struct Synth<const BATCH_SIZE: usize = DEFAULT_BATCH_SIZE, S = f64> {
+    osc1: BandLimitedOscillator<BATCH_SIZE, S>,
+    osc2: BandLimitedOscillator<BATCH_SIZE, S>,
+    wavetable: WavetableOscillator<BATCH_SIZE, S>,
+    lfo: LFO<BATCH_SIZE, S>,
+    osc2_frequency_mod_source: ModSource,
+    envelope: Envelope<BATCH_SIZE, S>,
+    mod_envelope: ModEnvelope<BATCH_SIZE, S>,
+    filter: Filter<BATCH_SIZE, S>,
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> Synth<BATCH_SIZE, S> {
+    fn helper(&self, oscillator: Oscillator) -> &OscillatorHelper<BATCH_SIZE, S> {
+        match oscillator {
+            Oscillator::Osc1 => &self.osc1.helper,
+            Oscillator::Osc2 => &self.osc2.helper,
+            Oscillator::Wavetable => &self.wavetable.helper,
+            Oscillator::Lfo => &self.lfo.helper,
+        }
+    }
+
+    fn helper_mut(&mut self, oscillator: Oscillator) -> &mut OscillatorHelper<BATCH_SIZE, S> {
+        match oscillator {
+            Oscillator::Osc1 => &mut self.osc1.helper,
+            Oscillator::Osc2 => &mut self.osc2.helper,
+            Oscillator::Wavetable => &mut self.wavetable.helper,
+            Oscillator::Lfo => &mut self.lfo.helper,
+        }
+    }
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> SynthEngine<S> for Synth<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
         let mut synth = Synth {
             osc1: BandLimitedOscillator::new(sample_rate),
             osc2: BandLimitedOscillator::new(sample_rate),
+            wavetable: WavetableOscillator::new(sample_rate),
             lfo: LFO::new(sample_rate),
+            osc2_frequency_mod_source: ModSource::default(),
+            envelope: Envelope::new(sample_rate),
+            mod_envelope: ModEnvelope::new(),
+            filter: Filter::new(sample_rate),
         };
 
-        synth.osc1.helper.input_frequency = 440.0;
-        synth.osc2.helper.input_frequency = 440.0;
+        synth.osc1.helper.input_frequency = Hz(440.0);
+        synth.osc2.helper.input_frequency = Hz(440.0);
+        synth.wavetable.helper.input_frequency = Hz(440.0);
 
-        synth.osc2.helper.cent_offset = 2.5;
+        synth.osc2.helper.cent_offset.set_immediate(2.5);
 
-        synth.lfo.helper.input_frequency = 0.5;
+        synth.lfo.set_frequency(Hz(0.5));
 
         synth
     }
 
-    pub fn render(&mut self, buffer: &mut [f64]) {
-        for output_batch in buffer.chunks_exact_mut(BATCH_SIZE) {
-            self.lfo.render();
+    fn render(&mut self, buffer: &mut [S]) {
+        let half = S::from_f64(0.5);
+
+        for output_batch in buffer.chunks_mut(BATCH_SIZE) {
+            let frames = output_batch.len();
+
+            if self.envelope.enabled {
+                self.envelope.render(frames);
+                let levels = self.envelope.output.iter().copied();
+                self.osc1.helper.set_amplitude_mod(levels.clone());
+                self.osc2.helper.set_amplitude_mod(levels.clone());
+                self.wavetable.helper.set_amplitude_mod(levels);
+            }
+
+            self.lfo.render(frames);
 
             self.osc1
                 .helper
                 .set_frequency_mod(self.lfo.output.iter().map(|(out, _)| *out));
-            self.osc2
-                .helper
-                .set_frequency_mod(self.lfo.output.iter().map(|(out, _)| *out));
+            match self.osc2_frequency_mod_source {
+                ModSource::Lfo => self
+                    .osc2
+                    .helper
+                    .set_frequency_mod(self.lfo.output.iter().map(|(out, _)| *out)),
+                ModSource::LfoQuadrature => self
+                    .osc2
+                    .helper
+                    .set_frequency_mod(self.lfo.output.iter().map(|(_, quad_out)| *quad_out)),
+            }
+
+            if self.mod_envelope.enabled {
+                self.mod_envelope.render(frames);
+                let levels = &self.mod_envelope.output[..frames];
+                let destination = self.mod_envelope.destination;
+                let amp_envelope = self.envelope.enabled;
 
-            self.osc1.render();
-            self.osc2.render();
+                self.osc1.helper.modulate(destination, levels, amp_envelope);
+                self.osc2.helper.modulate(destination, levels, amp_envelope);
+            }
+
+            self.osc1.render(frames);
+
+            if self.osc2.helper.sync {
+                self.osc2.helper.sync_to(&self.osc1.helper);
+            }
+
+            self.osc2.render(frames);
 
             for ((output, osc1_out), osc2_out) in output_batch
                 .iter_mut()
                 .zip(self.osc1.output.iter())
                 .zip(self.osc2.output.iter())
             {
-                *output = 0.5 * osc1_out + 0.5 * osc2_out;
+                *output = half * *osc1_out + half * *osc2_out;
+            }
+
+            if self.wavetable.enabled() {
+                self.wavetable
+                    .helper
+                    .set_frequency_mod(self.lfo.output.iter().map(|(out, _)| *out));
+
+                if self.mod_envelope.enabled {
+                    self.wavetable.modulate(
+                        self.mod_envelope.destination,
+                        &self.mod_envelope.output[..frames],
+                        self.envelope.enabled,
+                    );
+                }
+
+                self.wavetable.render(frames);
+
+                for (output, wavetable) in output_batch
+                    .iter_mut()
+                    .zip(self.wavetable.audio_rate.iter())
+                {
+                    *output += half * wavetable.output;
+                }
+            }
+
+            if self.filter.enabled {
+                let envelope = if self.envelope.enabled {
+                    Some(&self.envelope.output)
+                } else {
+                    None
+                };
+                let mod_envelope = if self.mod_envelope.enabled {
+                    Some(&self.mod_envelope.output)
+                } else {
+                    None
+                };
+
+                self.filter
+                    .modulate(frames, &self.lfo.output, envelope, mod_envelope);
+                self.filter.render(output_batch);
+            }
+        }
+    }
+
+    fn render_with_transport(&mut self, buffer: &mut [S], transport: &Transport) {
+        self.lfo.follow_transport(transport);
+        self.render(buffer);
+    }
+
+    fn reset(&mut self) {
+        self.osc1.helper.reset();
+        self.osc2.helper.reset();
+        self.wavetable.helper.reset();
+        self.lfo.reset();
+        self.envelope.state = AdsrState::default();
+        self.mod_envelope.state = BreakpointState::default();
+        self.filter.reset();
+    }
+
+    fn sample_rate(&self) -> f64 {
+        self.osc1.helper.sample_rate
+    }
+
+    fn note_on(&mut self) {
+        self.lfo.trigger();
+        self.envelope.state.note_on(&self.envelope.segments);
+        self.mod_envelope.state.note_on(&self.mod_envelope.stages);
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.state.note_off();
+        self.mod_envelope.state.note_off(&self.mod_envelope.stages);
+    }
+
+    fn set_wavetable(&mut self, wavetable: Option<Arc<Wavetable>>) {
+        self.wavetable.wavetable = wavetable;
+    }
+
+    fn set_wavetable_position(&mut self, position: f64) {
+        self.wavetable.position = position;
+    }
+
+    fn set_hard_sync(&mut self, enabled: bool) {
+        self.osc2.helper.sync = enabled;
+    }
+
+    fn set_osc2_frequency_mod_source(&mut self, source: ModSource) {
+        self.osc2_frequency_mod_source = source;
+    }
+
+    fn set_osc_waveform(&mut self, oscillator: Oscillator, waveform: Waveform) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.waveform = waveform,
+            Oscillator::Osc2 => self.osc2.waveform = waveform,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_osc_pulse_width(&mut self, oscillator: Oscillator, pulse_width: f64) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.pulse_width = pulse_width,
+            Oscillator::Osc2 => self.osc2.pulse_width = pulse_width,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_lfo_frequency(&mut self, frequency: Hz) {
+        self.lfo.set_frequency(frequency);
+    }
+
+    fn set_lfo_tempo_sync(&mut self, division: Option<Division>) {
+        self.lfo.set_tempo_sync(division);
+    }
+
+    fn set_lfo_waveform(&mut self, waveform: LfoWaveform) {
+        self.lfo.waveform = waveform;
+    }
+
+    fn set_lfo_pulse_width(&mut self, pulse_width: f64) {
+        self.lfo.pulse_width = pulse_width;
+    }
+
+    fn set_lfo_mode(&mut self, mode: LfoMode) {
+        self.lfo.set_mode(mode);
+    }
+
+    fn set_lfo_delay(&mut self, delay: f64, fade_in: f64) {
+        self.lfo.set_delay(delay, fade_in);
+    }
+
+    fn set_amp_envelope(&mut self, settings: Option<AdsrSettings>) {
+        match settings {
+            Some(settings) => {
+                self.envelope.segments = AdsrSegments::new(&settings, self.sample_rate());
+                self.envelope.enabled = true;
+            }
+            None => {
+                self.envelope.enabled = false;
+                self.osc1.helper.set_amplitude_mod(iter::repeat(S::ONE));
+                self.osc2.helper.set_amplitude_mod(iter::repeat(S::ONE));
+                self.wavetable
+                    .helper
+                    .set_amplitude_mod(iter::repeat(S::ONE));
+            }
+        }
+    }
+
+    fn amp_envelope(&self) -> Option<AdsrState> {
+        if self.envelope.enabled {
+            Some(self.envelope.state)
+        } else {
+            None
+        }
+    }
+
+    fn set_mod_envelope(
+        &mut self,
+        settings: Option<&BreakpointSettings>,
+        destination: ModDestination,
+    ) {
+        self.osc1.helper.clear_modulation();
+        self.osc2.helper.clear_modulation();
+        self.wavetable.clear_modulation();
+
+        self.mod_envelope.enabled = settings.is_some();
+        self.mod_envelope.destination = destination;
+        if let Some(settings) = settings {
+            self.mod_envelope.stages = BreakpointStages::new(settings, self.sample_rate());
+        }
+    }
+
+    fn set_filter(&mut self, settings: Option<FilterSettings>) {
+        self.filter.enabled = settings.is_some();
+        if let Some(settings) = settings {
+            self.filter.settings = settings;
+        }
+    }
+
+    fn set_parameter_smoothing(&mut self, smoothing: Smoothing) {
+        self.osc1.helper.set_smoothing(smoothing);
+        self.osc2.helper.set_smoothing(smoothing);
+        self.wavetable.helper.set_smoothing(smoothing);
+        self.lfo.helper.set_smoothing(smoothing);
+    }
+
+    fn set_pitch_offset(
+        &mut self,
+        oscillator: Oscillator,
+        octaves: Octaves,
+        semitones: Semitones,
+        cents: Cents,
+    ) {
+        let helper = self.helper_mut(oscillator);
+        helper.octave_offset.set_target(octaves.0);
+        helper.semitone_offset.set_target(semitones.0);
+        helper.cent_offset.set_target(cents.0);
+    }
+
+    fn set_amplitude(&mut self, oscillator: Oscillator, amplitude: f64) {
+        self.helper_mut(oscillator).amplitude.set_target(amplitude);
+    }
+
+    fn set_parameter(&mut self, id: ParamId, value: f64) {
+        let value = id.info().clamp(value);
+
+        match id {
+            ParamId::Frequency => {
+                self.osc1.helper.input_frequency = Hz(value);
+                self.osc2.helper.input_frequency = Hz(value);
+                self.wavetable.helper.input_frequency = Hz(value);
+            }
+            ParamId::Octaves(oscillator) => {
+                self.helper_mut(oscillator).octave_offset.set_target(value);
+            }
+            ParamId::Semitones(oscillator) => {
+                self.helper_mut(oscillator)
+                    .semitone_offset
+                    .set_target(value);
+            }
+            ParamId::Cents(oscillator) => {
+                self.helper_mut(oscillator).cent_offset.set_target(value);
             }
+            ParamId::Level(oscillator) => {
+                self.helper_mut(oscillator).amplitude.set_target(value);
+            }
+            ParamId::Osc1Waveform => {
+                self.set_osc_waveform(Oscillator::Osc1, Waveform::from_value(value))
+            }
+            ParamId::Osc1PulseWidth => self.set_osc_pulse_width(Oscillator::Osc1, value),
+            ParamId::Osc2Waveform => {
+                self.set_osc_waveform(Oscillator::Osc2, Waveform::from_value(value))
+            }
+            ParamId::Osc2PulseWidth => self.set_osc_pulse_width(Oscillator::Osc2, value),
+            ParamId::HardSync => self.osc2.helper.sync = bool::from_value(value),
+            ParamId::WavetablePosition => self.set_wavetable_position(value),
+            ParamId::LfoFrequency => self.lfo.set_frequency(Hz(value)),
+            ParamId::LfoTempoSync => self.lfo.set_tempo_sync(Choice::from_value(value)),
+            ParamId::LfoWaveform => self.lfo.waveform = LfoWaveform::from_value(value),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width = value,
+            ParamId::LfoMode => self.lfo.set_mode(LfoMode::from_value(value)),
+            ParamId::LfoDelay => {
+                let (_, fade_in) = self.lfo.fade.times();
+                self.lfo.set_delay(value, fade_in);
+            }
+            ParamId::LfoFadeIn => {
+                let (delay, _) = self.lfo.fade.times();
+                self.lfo.set_delay(delay, value);
+            }
+        }
+    }
+
+    fn parameter(&self, id: ParamId) -> f64 {
+        match id {
+            ParamId::Frequency => self.osc1.helper.input_frequency.0,
+            ParamId::Octaves(oscillator) => self.helper(oscillator).octave_offset.target(),
+            ParamId::Semitones(oscillator) => self.helper(oscillator).semitone_offset.target(),
+            ParamId::Cents(oscillator) => self.helper(oscillator).cent_offset.target(),
+            ParamId::Level(oscillator) => self.helper(oscillator).amplitude.target(),
+            ParamId::Osc1Waveform => self.osc1.waveform.value(),
+            ParamId::Osc1PulseWidth => self.osc1.pulse_width,
+            ParamId::Osc2Waveform => self.osc2.waveform.value(),
+            ParamId::Osc2PulseWidth => self.osc2.pulse_width,
+            ParamId::HardSync => self.osc2.helper.sync.value(),
+            ParamId::WavetablePosition => self.wavetable.position,
+            ParamId::LfoFrequency => self.lfo.frequency.0,
+            ParamId::LfoTempoSync => self.lfo.tempo_sync.value(),
+            ParamId::LfoWaveform => self.lfo.waveform.value(),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width,
+            ParamId::LfoMode => self.lfo.mode.value(),
+            ParamId::LfoDelay => self.lfo.fade.times().0,
+            ParamId::LfoFadeIn => self.lfo.fade.times().1,
         }
     }
 }
@@ -0,0 +1,295 @@
+//! Biquad filters with the designs from Robert Bristow-Johnson's "Audio EQ
+//! Cookbook", for EQ-style filtering of a rendered buffer.
+//!
+//! A `BiquadBank` runs a chain of sections in series. Changing a section's
+//! design ramps its coefficients linearly over the next `RAMP_FRAMES` frames
+//! instead of switching them at once, which would click. The ramp carries on
+//! across buffers, so the output doesn't depend on how it's split into them.
+
+use std::f64::consts::PI;
+
+use crate::pitch::Hz;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BiquadKind {
+    #[default]
+    LowPass,
+    HighPass,
+    /// Band pass with a gain of one at the center frequency.
+    BandPass,
+    AllPass,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiquadDesign {
+    pub kind: BiquadKind,
+    /// Cutoff, center or shelf midpoint frequency.
+    pub frequency: Hz,
+    /// Resonance for the pass filters, bandwidth for the peaking filter and
+    /// slope for the shelves, where 1/√2 is the steepest without overshoot.
+    pub q: f64,
+    /// Gain in dB of the peaking and shelf filters. The other kinds ignore it.
+    pub gain_db: f64,
+}
+
+impl Default for BiquadDesign {
+    fn default() -> Self {
+        BiquadDesign {
+            kind: BiquadKind::LowPass,
+            frequency: Hz(1000.0),
+            q: std::f64::consts::FRAC_1_SQRT_2,
+            gain_db: 0.0,
+        }
+    }
+}
+
+/// Lowest and highest design frequency, the latter as a fraction of the
+/// sample rate.
+const MIN_FREQUENCY: f64 = 10.0;
+const MAX_FREQUENCY_RATIO: f64 = 0.49;
+const MIN_Q: f64 = 1e-3;
+
+/// Frames a section's coefficients take to ramp to a new design.
+pub const RAMP_FRAMES: usize = 512;
+
+/// Coefficients normalised so that `a0` is one.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BiquadCoefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl BiquadCoefficients {
+    pub fn new(design: &BiquadDesign, sample_rate: f64) -> BiquadCoefficients {
+        let frequency = design
+            .frequency
+            .0
+            .clamp(MIN_FREQUENCY, MAX_FREQUENCY_RATIO * sample_rate);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * design.q.max(MIN_Q));
+        let a = 10f64.powf(design.gain_db / 40.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match design.kind {
+            BiquadKind::LowPass => {
+                let b = (1.0 - cos) / 2.0;
+                (b, 2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadKind::HighPass => {
+                let b = (1.0 + cos) / 2.0;
+                (b, -2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadKind::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::AllPass => (
+                1.0 - alpha,
+                -2.0 * cos,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BiquadKind::LowShelf => {
+                let shelf = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                    (a + 1.0) + (a - 1.0) * cos + shelf,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - shelf,
+                )
+            }
+            BiquadKind::HighShelf => {
+                let shelf = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                    (a + 1.0) - (a - 1.0) * cos + shelf,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - shelf,
+                )
+            }
+        };
+
+        BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Magnitude of the frequency response at `frequency`.
+    pub fn magnitude(&self, frequency: Hz, sample_rate: f64) -> f64 {
+        let w = 2.0 * PI * frequency.0 / sample_rate;
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+
+        // Evaluate numerator and denominator at z = e^(jw), as polynomials in
+        // z^-1.
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -self.b1 * sin1 - self.b2 * sin2;
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -self.a1 * sin1 - self.a2 * sin2;
+
+        num_re.hypot(num_im) / den_re.hypot(den_im)
+    }
+
+    /// Move `fraction` of the way towards `target`.
+    #[inline(always)]
+    fn lerp(&self, target: &BiquadCoefficients, fraction: f64) -> BiquadCoefficients {
+        BiquadCoefficients {
+            b0: self.b0 + fraction * (target.b0 - self.b0),
+            b1: self.b1 + fraction * (target.b1 - self.b1),
+            b2: self.b2 + fraction * (target.b2 - self.b2),
+            a1: self.a1 + fraction * (target.a1 - self.a1),
+            a2: self.a2 + fraction * (target.a2 - self.a2),
+        }
+    }
+}
+
+/// One biquad in transposed direct form II.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BiquadState {
+    s1: f64,
+    s2: f64,
+}
+
+impl BiquadState {
+    #[inline(always)]
+    pub fn process(&mut self, input: f64, coefficients: &BiquadCoefficients) -> f64 {
+        let BiquadCoefficients { b0, b1, b2, a1, a2 } = *coefficients;
+
+        let output = b0 * input + self.s1;
+        self.s1 = b1 * input - a1 * output + self.s2;
+        self.s2 = b2 * input - a2 * output;
+        output
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Section {
+    design: BiquadDesign,
+    /// The coefficients in use, or where the ramp in progress started.
+    coefficients: BiquadCoefficients,
+    /// The coefficients of `design`, which the ramp in progress heads to.
+    target: BiquadCoefficients,
+    /// Frames left of the ramp, zero once it has reached `target`.
+    remaining: usize,
+    state: BiquadState,
+}
+
+impl Section {
+    /// The coefficients `remaining` frames before the end of the ramp.
+    #[inline(always)]
+    fn ramped(&self, remaining: usize) -> BiquadCoefficients {
+        let fraction = (RAMP_FRAMES - remaining) as f64 * (1.0 / RAMP_FRAMES as f64);
+        self.coefficients.lerp(&self.target, fraction)
+    }
+}
+
+/// A chain of biquad sections, processed in series.
+pub struct BiquadBank {
+    sample_rate: f64,
+    sections: Vec<Section>,
+}
+
+impl BiquadBank {
+    pub fn new(sample_rate: f64) -> BiquadBank {
+        BiquadBank {
+            sample_rate,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Append a section and return its index. It starts with its design's
+    /// coefficients, without a ramp.
+    pub fn push(&mut self, design: BiquadDesign) -> usize {
+        let coefficients = BiquadCoefficients::new(&design, self.sample_rate);
+        self.sections.push(Section {
+            design,
+            coefficients,
+            target: coefficients,
+            remaining: 0,
+            state: BiquadState::default(),
+        });
+        self.sections.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    pub fn design(&self, index: usize) -> &BiquadDesign {
+        &self.sections[index].design
+    }
+
+    /// Change a section's design. Its coefficients ramp to the new design
+    /// over the next `RAMP_FRAMES` rendered frames, starting from where a ramp
+    /// in progress has got to.
+    pub fn set_design(&mut self, index: usize, design: BiquadDesign) {
+        let section = &mut self.sections[index];
+        if section.design != design {
+            section.coefficients = section.ramped(section.remaining);
+            section.design = design;
+            section.target = BiquadCoefficients::new(&design, self.sample_rate);
+            section.remaining = RAMP_FRAMES;
+        }
+    }
+
+    /// Clear the filter memory, and finish any pending ramps.
+    pub fn reset(&mut self) {
+        for section in self.sections.iter_mut() {
+            section.coefficients = section.target;
+            section.remaining = 0;
+            section.state = BiquadState::default();
+        }
+    }
+
+    /// Filter `buffer` in place through every section.
+    pub fn render(&mut self, buffer: &mut [f64]) {
+        if buffer.is_empty() {
+            return;
+        }
+
+        for section in self.sections.iter_mut() {
+            let mut samples = buffer.iter_mut();
+
+            if section.remaining > 0 {
+                for sample in samples.by_ref().take(section.remaining) {
+                    section.remaining -= 1;
+                    let coefficients = section.ramped(section.remaining);
+                    *sample = section.state.process(*sample, &coefficients);
+                }
+
+                if section.remaining == 0 {
+                    section.coefficients = section.target;
+                }
+            }
+
+            for sample in samples {
+                *sample = section.state.process(*sample, &section.coefficients);
+            }
+        }
+    }
+}
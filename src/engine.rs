@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::envelope::{AdsrSettings, AdsrState, BreakpointSettings};
+use crate::filter::FilterSettings;
+use crate::modulation::{ModDestination, ModSource};
+use crate::params::ParamId;
+use crate::pitch::{Cents, Hz, Octaves, Semitones};
+use crate::sample::Sample;
+use crate::smoothing::Smoothing;
+use crate::tempo::{Division, Transport};
+use crate::waveform::{LfoMode, LfoWaveform, Waveform};
+use crate::wavetable::Wavetable;
+use crate::{array_of_structs, fixed_batch_size, one_frame_per_call};
+
+/// The oscillators of a synth, for the settings they all share.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Oscillator {
+    Osc1,
+    Osc2,
+    Wavetable,
+    Lfo,
+}
+
+/// Common interface for every rendering strategy in this crate.
+///
+/// All engines render the same patch; they only differ in how the work is
+/// laid out in memory and split over calls. They render in `S`, either `f64`
+/// or `f32`, while settings are always `f64`.
+pub trait SynthEngine<S: Sample = f64> {
+    fn new(sample_rate: f64) -> Self
+    where
+        Self: Sized;
+
+    fn render(&mut self, buffer: &mut [S]);
+
+    /// Render like `render`, given the host's transport at the first frame of
+    /// `buffer`. A tempo-synced LFO takes its rate from the tempo and its phase
+    /// from the song position, so it lines up wherever playback starts.
+    fn render_with_transport(&mut self, buffer: &mut [S], transport: &Transport);
+
+    /// Restore all oscillator phases to their initial state without touching
+    /// any of the patch settings.
+    fn reset(&mut self);
+
+    fn sample_rate(&self) -> f64;
+
+    /// Start a note. Opens the amp envelope's gate and restarts the LFO's
+    /// delay and fade-in, and its cycle unless it's free running.
+    fn note_on(&mut self);
+
+    /// Release the current note.
+    fn note_off(&mut self);
+
+    /// Load the table played by the wavetable oscillator. The oscillator is
+    /// silent, and not rendered at all, while no table is loaded.
+    fn set_wavetable(&mut self, wavetable: Option<Arc<Wavetable>>);
+
+    /// Morph the wavetable oscillator between the frames of its table, from
+    /// the first at `0` to the last at `1`. The mod envelope can move it
+    /// further at audio rate.
+    fn set_wavetable_position(&mut self, position: f64);
+
+    /// Hard sync osc2 to osc1, resetting its phase every time osc1 wraps.
+    fn set_hard_sync(&mut self, enabled: bool);
+
+    /// Select the signal driving osc2's frequency modulation. osc1 and the
+    /// wavetable oscillator always follow the LFO's main output.
+    fn set_osc2_frequency_mod_source(&mut self, source: ModSource);
+
+    /// Select the waveform of osc1 or osc2. The wavetable oscillator plays
+    /// its table and the LFO has `set_lfo_waveform`, so they ignore it.
+    fn set_osc_waveform(&mut self, oscillator: Oscillator, waveform: Waveform);
+
+    /// Duty cycle of osc1's or osc2's square, in `[0, 1]`.
+    fn set_osc_pulse_width(&mut self, oscillator: Oscillator, pulse_width: f64);
+
+    fn set_lfo_frequency(&mut self, frequency: Hz);
+
+    /// Sync the LFO to the host's tempo, one cycle per `division`, or let it
+    /// run at its own frequency again with `None`. A synced LFO keeps the
+    /// last tempo it was given while rendering without a transport.
+    fn set_lfo_tempo_sync(&mut self, division: Option<Division>);
+
+    fn set_lfo_waveform(&mut self, waveform: LfoWaveform);
+
+    /// Duty cycle of the square LFO, in `[0, 1]`.
+    fn set_lfo_pulse_width(&mut self, pulse_width: f64);
+
+    fn set_lfo_mode(&mut self, mode: LfoMode);
+
+    /// Keep the LFO silent for `delay` seconds after each note, then fade it
+    /// in over `fade_in` seconds.
+    fn set_lfo_delay(&mut self, delay: f64, fade_in: f64);
+
+    /// Shape the amplitude of every oscillator with an ADSR envelope, gated by
+    /// `note_on` and `note_off`, or play at a constant level with `None`.
+    fn set_amp_envelope(&mut self, settings: Option<AdsrSettings>);
+
+    /// The amp envelope's stage and level, or `None` while it's off.
+    fn amp_envelope(&self) -> Option<AdsrState>;
+
+    /// Drive `destination` on every oscillator with a breakpoint envelope,
+    /// gated by `note_on` and `note_off`, or turn the envelope off with `None`.
+    /// The filter's cutoff also follows it, by the filter's
+    /// `cutoff_mod_envelope_amount`.
+    fn set_mod_envelope(
+        &mut self,
+        settings: Option<&BreakpointSettings>,
+        destination: ModDestination,
+    );
+
+    /// Run the oscillator mix through the filter selected by `settings.model`,
+    /// or leave it unfiltered with `None`. The cutoff follows the LFO, the amp
+    /// envelope and the mod envelope by the settings' `cutoff_*_amount`s.
+    fn set_filter(&mut self, settings: Option<FilterSettings>);
+
+    /// Set how pitch offsets and amplitudes glide to new values. Glides in
+    /// progress finish straight away.
+    fn set_parameter_smoothing(&mut self, smoothing: Smoothing);
+
+    /// Transpose an oscillator, gliding to the new pitch.
+    fn set_pitch_offset(
+        &mut self,
+        oscillator: Oscillator,
+        octaves: Octaves,
+        semitones: Semitones,
+        cents: Cents,
+    );
+
+    /// Scale an oscillator's output, gliding to the new level.
+    fn set_amplitude(&mut self, oscillator: Oscillator, amplitude: f64);
+
+    /// Set a parameter from the host, clamped to its range. Pitch offsets and
+    /// levels glide to the new value like they do through `set_pitch_offset`
+    /// and `set_amplitude`.
+    fn set_parameter(&mut self, id: ParamId, value: f64);
+
+    /// The current value of a parameter, or the value it's gliding to.
+    fn parameter(&self, id: ParamId) -> f64;
+}
+
+pub struct EngineDescriptor {
+    /// Short identifier, usable in file names.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub create: fn(f64) -> Box<dyn SynthEngine>,
+    /// Create the same engine rendering in `f32`.
+    pub create_f32: fn(f64) -> Box<dyn SynthEngine<f32>>,
+}
+
+fn create<S: Sample, E: SynthEngine<S> + 'static>(sample_rate: f64) -> Box<dyn SynthEngine<S>> {
+    Box::new(E::new(sample_rate))
+}
+
+/// Every available rendering strategy. New engines only need to be added here
+/// to show up in the plotter, the benchmarks and the tests.
+pub static ENGINES: &[EngineDescriptor] = &[
+    EngineDescriptor {
+        id: "one_frame_per_call",
+        name: "One frame per call",
+        create: create::<f64, one_frame_per_call::Synth>,
+        create_f32: create::<f32, one_frame_per_call::Synth<f32>>,
+    },
+    EngineDescriptor {
+        id: "fixed_batch_size",
+        name: "Fixed batch size (struct-of-arrays)",
+        create: create::<f64, fixed_batch_size::Synth>,
+        create_f32: create::<
+            f32,
+            fixed_batch_size::Synth<{ fixed_batch_size::DEFAULT_BATCH_SIZE }, f32>,
+        >,
+    },
+    EngineDescriptor {
+        id: "array_of_structs",
+        name: "Fixed batch size (array-of-structs)",
+        create: create::<f64, array_of_structs::Synth>,
+        create_f32: create::<
+            f32,
+            array_of_structs::Synth<{ array_of_structs::DEFAULT_BATCH_SIZE }, f32>,
+        >,
+    },
+];
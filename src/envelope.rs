@@ -0,0 +1,359 @@
+//! ADSR and multi-stage breakpoint envelope generators.
+//!
+//! Every stage is the recurrence `level = base + level * coef`, evaluated once
+//! per frame. Linear segments use a coefficient of one and step by a constant;
+//! exponential segments approach a target just past the end of the stage, so
+//! they reach it in the set time instead of only asymptotically. The engines
+//! lay out the envelope's output differently, but all of them step it with
+//! `AdsrState::next` or `BreakpointState::next` so they produce the same
+//! levels.
+
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Curve {
+    #[default]
+    Linear,
+    Exponential,
+}
+
+/// What a note does while the envelope is still gated by the previous one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Restart the attack from the current level.
+    #[default]
+    Retrigger,
+    /// Carry on where the envelope is, only starting a new attack once the
+    /// previous note has been released.
+    Legato,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdsrSettings {
+    /// Attack time in seconds, from silence to full level.
+    pub attack: f64,
+    /// Decay time in seconds, from full level to the sustain level.
+    pub decay: f64,
+    /// Sustain level in `[0, 1]`.
+    pub sustain: f64,
+    /// Release time in seconds. Linear releases take this long from full
+    /// level, so releasing from the sustain level is proportionally shorter.
+    pub release: f64,
+    pub curve: Curve,
+    pub trigger_mode: TriggerMode,
+}
+
+impl Default for AdsrSettings {
+    fn default() -> Self {
+        AdsrSettings {
+            attack: 0.01,
+            decay: 0.2,
+            sustain: 0.7,
+            release: 0.3,
+            curve: Curve::Linear,
+            trigger_mode: TriggerMode::Retrigger,
+        }
+    }
+}
+
+/// How far past the end of an exponential segment its target lies, as a
+/// fraction of full scale. The attack uses a larger overshoot, so it's closer
+/// to linear, like analog envelopes.
+const ATTACK_OVERSHOOT: f64 = 0.3;
+const DECAY_OVERSHOOT: f64 = 1e-4;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Segment {
+    base: f64,
+    coef: f64,
+}
+
+impl Segment {
+    /// A segment from `from` to `to` over `frames` frames. Exponential
+    /// segments aim for `to + overshoot`, which has to point in the direction
+    /// of the segment.
+    fn new(curve: Curve, from: f64, to: f64, frames: f64, overshoot: f64) -> Segment {
+        let frames = frames.max(1.0);
+        let span = (to - from).abs();
+
+        match curve {
+            // A flat exponential segment has nothing to approach, which is
+            // the same as a flat linear segment.
+            Curve::Exponential if span > 0.0 => {
+                let overshoot_abs = overshoot.abs();
+                let coef = (-((span + overshoot_abs) / overshoot_abs).ln() / frames).exp();
+                Segment {
+                    base: (to + overshoot) * (1.0 - coef),
+                    coef,
+                }
+            }
+            _ => Segment {
+                base: (to - from) / frames,
+                coef: 1.0,
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn next(self, level: f64) -> f64 {
+        self.base + level * self.coef
+    }
+}
+
+/// Per-frame segments for a set of `AdsrSettings` at a sample rate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdsrSegments {
+    attack: Segment,
+    decay: Segment,
+    sustain: f64,
+    release: Segment,
+    trigger_mode: TriggerMode,
+}
+
+impl AdsrSegments {
+    pub fn new(settings: &AdsrSettings, sample_rate: f64) -> AdsrSegments {
+        let sustain = settings.sustain.clamp(0.0, 1.0);
+        let curve = settings.curve;
+
+        AdsrSegments {
+            attack: Segment::new(
+                curve,
+                0.0,
+                1.0,
+                settings.attack * sample_rate,
+                ATTACK_OVERSHOOT,
+            ),
+            decay: Segment::new(
+                curve,
+                1.0,
+                sustain,
+                settings.decay * sample_rate,
+                -DECAY_OVERSHOOT,
+            ),
+            sustain,
+            release: Segment::new(
+                curve,
+                1.0,
+                0.0,
+                settings.release * sample_rate,
+                -DECAY_OVERSHOOT,
+            ),
+            trigger_mode: settings.trigger_mode,
+        }
+    }
+
+    pub fn sustain(&self) -> f64 {
+        self.sustain
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AdsrState {
+    pub stage: Stage,
+    pub level: f64,
+}
+
+impl AdsrState {
+    pub fn note_on(&mut self, segments: &AdsrSegments) {
+        let gated = !matches!(self.stage, Stage::Idle | Stage::Release);
+
+        if !gated || segments.trigger_mode == TriggerMode::Retrigger {
+            self.stage = Stage::Attack;
+        }
+    }
+
+    pub fn note_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.stage = Stage::Release;
+        }
+    }
+
+    /// Whether the level stays the same until the next note event.
+    #[inline(always)]
+    pub fn is_steady(&self) -> bool {
+        matches!(self.stage, Stage::Idle | Stage::Sustain)
+    }
+
+    /// Advance by one frame and return the new level.
+    #[inline(always)]
+    pub fn next(&mut self, segments: &AdsrSegments) -> f64 {
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Attack => {
+                self.level = segments.attack.next(self.level);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level = segments.decay.next(self.level);
+                if self.level <= segments.sustain {
+                    self.level = segments.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = segments.sustain,
+            Stage::Release => {
+                self.level = segments.release.next(self.level);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+
+        self.level
+    }
+}
+
+/// Overshoot of exponential breakpoint stages, relative to their span.
+const BREAKPOINT_OVERSHOOT: f64 = 1e-3;
+
+/// One stage of a breakpoint envelope: move from wherever the envelope is to
+/// `level` over `time` seconds. A stage that stays at the same level holds it
+/// for its time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Breakpoint {
+    pub level: f64,
+    pub time: f64,
+    pub curve: Curve,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BreakpointSettings {
+    pub stages: Vec<Breakpoint>,
+    /// Hold the level at the end of this stage while the note is held, and
+    /// continue with the next stage on release.
+    pub sustain: Option<usize>,
+    /// Repeat these stages while the note is held. On release the envelope
+    /// carries on past the end of the loop.
+    pub loop_stages: Option<Range<usize>>,
+}
+
+/// `BreakpointSettings` with the stage times converted to frames, and any
+/// sustain point or loop outside the stages dropped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BreakpointStages {
+    stages: Vec<(f64, usize, Curve)>,
+    sustain: Option<usize>,
+    loop_stages: Option<Range<usize>>,
+}
+
+impl BreakpointStages {
+    pub fn new(settings: &BreakpointSettings, sample_rate: f64) -> BreakpointStages {
+        let len = settings.stages.len();
+
+        BreakpointStages {
+            stages: settings
+                .stages
+                .iter()
+                .map(|stage| {
+                    let frames = (stage.time * sample_rate).round().max(1.0) as usize;
+                    (stage.level, frames, stage.curve)
+                })
+                .collect(),
+            sustain: settings.sustain.filter(|&stage| stage < len),
+            loop_stages: settings
+                .loop_stages
+                .clone()
+                .filter(|stages| stages.start < stages.end && stages.end <= len),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BreakpointState {
+    pub level: f64,
+    /// The stage being played, or `None` when the envelope has finished, or
+    /// hasn't started yet.
+    stage: Option<usize>,
+    remaining: usize,
+    segment: Segment,
+    gate: bool,
+    sustaining: bool,
+}
+
+impl BreakpointState {
+    pub fn note_on(&mut self, stages: &BreakpointStages) {
+        self.gate = true;
+        self.sustaining = false;
+        self.enter(0, stages);
+    }
+
+    pub fn note_off(&mut self, stages: &BreakpointStages) {
+        self.gate = false;
+
+        if self.sustaining {
+            self.sustaining = false;
+            if let Some(stage) = self.stage {
+                self.enter(stage + 1, stages);
+            }
+        }
+    }
+
+    /// Whether the level stays the same until the next note event.
+    #[inline(always)]
+    pub fn is_steady(&self) -> bool {
+        self.sustaining || self.stage.is_none()
+    }
+
+    /// Advance by one frame and return the new level.
+    #[inline(always)]
+    pub fn next(&mut self, stages: &BreakpointStages) -> f64 {
+        if let Some(stage) = self.stage {
+            if !self.sustaining {
+                self.remaining -= 1;
+
+                if self.remaining == 0 {
+                    self.level = stages.stages[stage].0;
+                    self.finish(stage, stages);
+                } else {
+                    self.level = self.segment.next(self.level);
+                }
+            }
+        }
+
+        self.level
+    }
+
+    fn finish(&mut self, stage: usize, stages: &BreakpointStages) {
+        if self.gate {
+            if let Some(ref loop_stages) = stages.loop_stages {
+                if stage + 1 == loop_stages.end {
+                    self.enter(loop_stages.start, stages);
+                    return;
+                }
+            }
+
+            if stages.sustain == Some(stage) {
+                self.sustaining = true;
+                return;
+            }
+        }
+
+        self.enter(stage + 1, stages);
+    }
+
+    fn enter(&mut self, stage: usize, stages: &BreakpointStages) {
+        match stages.stages.get(stage) {
+            Some(&(level, frames, curve)) => {
+                let overshoot = BREAKPOINT_OVERSHOOT * (level - self.level);
+                self.stage = Some(stage);
+                self.remaining = frames;
+                self.segment = Segment::new(curve, self.level, level, frames as f64, overshoot);
+            }
+            None => self.stage = None,
+        }
+    }
+}
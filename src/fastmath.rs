@@ -1,67 +1,103 @@
-pub fn parabolic_sine(x: f64) -> f64 {
+//! Fast approximations used on the signal path.
+//!
+//! Each function documents the inputs it's accurate for. The errors quoted are
+//! the ones `tests/fastmath.rs` measures and holds them to, against the `f64`
+//! functions of the standard library.
+
+use crate::sample::Sample;
+
+/// `sin(x)` for `x` in `[-pi, pi]`, from a parabola with a correction term.
+///
+/// The absolute error is below `1.1e-3` for both `f64` and `f32`. The
+/// relative error is below 1.4% except right next to `+-pi`, where the
+/// parabola's own zero comes from `4 - 4` cancelling. Outside `[-pi, pi]` the
+/// parabola keeps going, so wrap the phase first.
+pub fn parabolic_sine<S: Sample>(x: S) -> S {
     use std::f64::consts::PI;
 
     const B: f64 = 4.0 / PI;
     const C: f64 = -4.0 / (PI * PI);
     const P: f64 = 0.225;
-    let mut y = B * x + C * x * x.abs();
+    let mut y = S::from_f64(B) * x + S::from_f64(C) * x * x.abs();
 
-    y = P * (y * y.abs() - y) + y;
+    y = S::from_f64(P) * (y * y.abs() - y) + y;
 
     y
 }
 
-pub fn wrap01(x: f64) -> f64 {
-    if x >= 0.0 && x <= 1.0 {
+/// `x` wrapped into `[0, 1]`, for any finite `x`. Values already in `[0, 1]`
+/// come back unchanged, including `1.0`.
+///
+/// The result is exact in `f64`. In `f32`, a small negative `x` plus one is
+/// rounded, to within half a ULP. Infinities and NaN give NaN.
+pub fn wrap01<S: Sample>(x: S) -> S {
+    if (S::ZERO..=S::ONE).contains(&x) {
         x
     } else {
-        x.rem_euclid(1.0)
+        x.rem_euclid(S::ONE)
     }
 }
 
+/// `2^x`, for `x` whose result is a normal number: `[-1022, 1023.5)` for
+/// `f64`, `[-126, 127.5)` for `f32`.
+///
+/// Over that domain the error is within 1.1 ULP for `f64` and 1.8 ULP for
+/// `f32`. The result's exponent is written straight into the exponent bits,
+/// which only hold normal numbers, so beyond the domain the result saturates:
+/// from `1023.5` (`127.5` for `f32`) it's infinity, and below `-1022.5`
+/// (`-126.5`) it's zero, where `f64::exp2` would still give a subnormal. NaN
+/// gives NaN.
+///
+/// On macOS the domain is computed with the standard library's `powf`
+/// instead, but saturates the same way.
 // https://github.com/akohlmey/fastermath/blob/master/src/exp.c
 #[cfg(not(target_os="macos"))]
-pub fn exp2(mut x: f64) -> f64 {
-    #[repr(align(32))]
-    struct Aligned<T>(T);
-
-    const Q: Aligned<[f64; 2]> = Aligned([2.33184211722314911771e2, 4.36821166879210612817e3]);
-    const P: Aligned<[f64; 3]> = Aligned([
+#[allow(clippy::excessive_precision)]
+pub fn exp2<S: Sample>(mut x: S) -> S {
+    const Q: [f64; 2] = [2.33184211722314911771e2, 4.36821166879210612817e3];
+    const P: [f64; 3] = [
         2.30933477057345225087e-2,
         2.02020656693165307700e1,
         1.51390680115615096133e3,
-    ]);
-
-    union DoubleManip {
-        f: f64,
-        s: [i32; 2],
-    }
+    ];
 
-    const DOUBLE_BIAS: i32 = 1023;
-
-    let ipart = (x + 0.5).floor();
+    let ipart = (x + S::from_f64(0.5)).floor();
     let fpart = x - ipart;
 
-    let epart = DoubleManip {
-        s: [0, ((ipart as i32) + DOUBLE_BIAS) << 20],
-    };
+    // The exponent bits only hold normal numbers, so saturate beyond them.
+    let exponent = ipart.to_i32();
+    if exponent > S::MAX_EXPONENT {
+        return S::INFINITY;
+    }
+    if exponent < S::MIN_EXPONENT {
+        return S::ZERO;
+    }
+    let epart = S::pow2i(exponent);
 
     x = fpart * fpart;
 
-    let mut px = P.0[0];
-    px = px * x + P.0[1];
-    let mut qx = x + Q.0[0];
-    px = px * x + P.0[2];
-    qx = qx * x + Q.0[1];
+    let mut px = S::from_f64(P[0]);
+    px = px * x + S::from_f64(P[1]);
+    let mut qx = x + S::from_f64(Q[0]);
+    px = px * x + S::from_f64(P[2]);
+    qx = qx * x + S::from_f64(Q[1]);
 
-    px = px * fpart;
+    px *= fpart;
 
-    x = 1.0 + 2.0 * (px / (qx - px));
+    x = S::ONE + S::from_f64(2.0) * (px / (qx - px));
 
-    unsafe { epart.f * x }
+    epart * x
 }
 
 #[cfg(target_os="macos")]
-pub fn exp2(x: f64) -> f64 {
-    2.0f64.powf(x)
-}
\ No newline at end of file
+pub fn exp2<S: Sample>(x: S) -> S {
+    let exponent = (x + S::from_f64(0.5)).floor().to_i32();
+    if exponent > S::MAX_EXPONENT {
+        return S::INFINITY;
+    }
+    if exponent < S::MIN_EXPONENT {
+        return S::ZERO;
+    }
+
+    S::from_f64(2.0).powf(x)
+}
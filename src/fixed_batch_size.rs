@@ -1,190 +1,1123 @@
-use crate::fastmath::{exp2, parabolic_sine, wrap01};
+use std::sync::Arc;
+
+use crate::engine::{Oscillator, SynthEngine};
+use crate::envelope::{
+    AdsrSegments, AdsrSettings, AdsrState, BreakpointSettings, BreakpointStages, BreakpointState,
+};
+use crate::fastmath::wrap01;
+use crate::filter::{
+    FilterModel, FilterSettings, LadderCoefficients, LadderState, SvfCoefficients, SvfState,
+};
+use crate::modulation::{ModDestination, ModSource};
+use crate::params::{Choice, ParamId};
+use crate::pitch::{Cents, Hz, Octaves, Semitones};
+use crate::sample::Sample;
+use crate::simd;
+use crate::smoothing::{SmoothedParam, Smoothing};
+use crate::tempo::{Division, Transport};
+use crate::waveform::{self, LfoFade, LfoMode, LfoWaveform, RandomSteps, Waveform};
+use crate::wavetable::Wavetable;
 
 const OSC_MAX_FREQ: f64 = 20480.0;
 
-const BATCH_SIZE: usize = 64;
-type BatchData = [f64; BATCH_SIZE];
+pub const DEFAULT_BATCH_SIZE: usize = 64;
+type BatchData<const BATCH_SIZE: usize, S = f64> = [S; BATCH_SIZE];
 
-struct OscillatorHelper {
+struct OscillatorHelper<const BATCH_SIZE: usize, S> {
     pub sample_rate: f64,
-    pub input_frequency: f64,
-    pub octave_offset: f64,
-    pub semitone_offset: f64,
-    pub cent_offset: f64,
-    pub amplitude: f64,
+    pub input_frequency: Hz,
+    /// Static pitch offsets, in octaves, semitones and cents.
+    pub octave_offset: SmoothedParam,
+    pub semitone_offset: SmoothedParam,
+    pub cent_offset: SmoothedParam,
+    pub amplitude: SmoothedParam,
+
+    pub input_frequency_mod_ratio: BatchData<BATCH_SIZE, S>,
+    pub phase_mod: BatchData<BATCH_SIZE, S>,
+    /// Frequency modulation in octaves, added to the static pitch offsets.
+    pub frequency_mod: BatchData<BATCH_SIZE, S>,
 
-    pub input_frequency_mod_ratio: BatchData,
-    pub phase_mod: BatchData,
-    pub frequency_mod: BatchData,
+    /// Hard sync: reset the phase whenever the master oscillator, described by
+    /// the `sync_*` inputs, wraps.
+    pub sync: bool,
+    pub sync_wrap: [bool; BATCH_SIZE],
+    pub sync_modulo: BatchData<BATCH_SIZE, S>,
+    pub sync_phase_increment: BatchData<BATCH_SIZE, S>,
 
-    pub modulo: BatchData,
+    pub modulo: BatchData<BATCH_SIZE, S>,
     pub wrap_modulo: [bool; BATCH_SIZE],
-    pub amplitude_mod: BatchData,
+    pub phase_increment: BatchData<BATCH_SIZE, S>,
+    pub sync_from: BatchData<BATCH_SIZE, S>,
+    pub amplitude_mod: BatchData<BATCH_SIZE, S>,
+
+    /// Per-frame values of the smoothed settings, with the pitch offsets
+    /// combined into octaves.
+    pub pitch_offset: BatchData<BATCH_SIZE>,
+    pub amplitudes: BatchData<BATCH_SIZE, S>,
+    semitones: BatchData<BATCH_SIZE>,
+    cents: BatchData<BATCH_SIZE>,
+    /// `exp2` of the frequency modulation plus the pitch offset.
+    frequency_ratio: BatchData<BATCH_SIZE, S>,
 
-    last_modulo: f64,
+    /// `modulo` plus `phase_mod`, wrapped into `[0, 1]`: the phase the shape
+    /// is rendered at. Set by `wrap_phase`.
+    pub phase: BatchData<BATCH_SIZE, S>,
+
+    last_modulo: S,
 }
 
-impl OscillatorHelper {
+impl<const BATCH_SIZE: usize, S: Sample> OscillatorHelper<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         OscillatorHelper {
             sample_rate,
-            input_frequency: 0.0,
-            octave_offset: 0.0,
-            semitone_offset: 0.0,
-            cent_offset: 0.0,
-            amplitude: 1.0,
-            input_frequency_mod_ratio: [1.0; BATCH_SIZE],
-            frequency_mod: [0.0; BATCH_SIZE],
-            phase_mod: [0.0; BATCH_SIZE],
-            amplitude_mod: [1.0; BATCH_SIZE],
-            modulo: [0.0; BATCH_SIZE],
+            input_frequency: Hz(0.0),
+            octave_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            semitone_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            cent_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            amplitude: SmoothedParam::new(1.0, Smoothing::default(), sample_rate),
+            input_frequency_mod_ratio: [S::ONE; BATCH_SIZE],
+            frequency_mod: [S::ZERO; BATCH_SIZE],
+            phase_mod: [S::ZERO; BATCH_SIZE],
+            amplitude_mod: [S::ONE; BATCH_SIZE],
+            sync: false,
+            sync_wrap: [false; BATCH_SIZE],
+            sync_modulo: [S::ZERO; BATCH_SIZE],
+            sync_phase_increment: [S::ZERO; BATCH_SIZE],
+            modulo: [S::ZERO; BATCH_SIZE],
             wrap_modulo: [false; BATCH_SIZE],
-            last_modulo: 0.0,
+            phase_increment: [S::ZERO; BATCH_SIZE],
+            sync_from: [S::ZERO; BATCH_SIZE],
+            pitch_offset: [0.0; BATCH_SIZE],
+            amplitudes: [S::ONE; BATCH_SIZE],
+            semitones: [0.0; BATCH_SIZE],
+            cents: [0.0; BATCH_SIZE],
+            frequency_ratio: [S::ONE; BATCH_SIZE],
+            phase: [S::ZERO; BATCH_SIZE],
+            last_modulo: S::ZERO,
         }
     }
 
-    fn update(&mut self) {
-        let const_offset =
-            self.octave_offset * 12.0 + self.semitone_offset + self.cent_offset / 100.0;
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        let sample_rate = self.sample_rate;
+        self.octave_offset.set_smoothing(smoothing, sample_rate);
+        self.semitone_offset.set_smoothing(smoothing, sample_rate);
+        self.cent_offset.set_smoothing(smoothing, sample_rate);
+        self.amplitude.set_smoothing(smoothing, sample_rate);
+    }
+
+    /// Restart rendering. Parameters that are still gliding jump to their
+    /// targets.
+    fn reset(&mut self) {
+        self.octave_offset.finish();
+        self.semitone_offset.finish();
+        self.cent_offset.finish();
+        self.amplitude.finish();
+
+        self.last_modulo = S::ZERO;
+    }
+
+    /// Apply a modulation envelope on top of this batch's other modulation.
+    /// `amp_envelope` says whether `amplitude_mod` holds this batch's amp
+    /// envelope levels, rather than the previous batch's modulation.
+    fn modulate(&mut self, destination: ModDestination, levels: &[S], amp_envelope: bool) {
+        match destination {
+            ModDestination::Frequency => {
+                for (frequency_mod, level) in self.frequency_mod.iter_mut().zip(levels) {
+                    *frequency_mod += *level;
+                }
+            }
+            ModDestination::Phase => self.phase_mod[..levels.len()].copy_from_slice(levels),
+            ModDestination::Amplitude if amp_envelope => {
+                for (amplitude_mod, level) in self.amplitude_mod.iter_mut().zip(levels) {
+                    *amplitude_mod *= *level;
+                }
+            }
+            ModDestination::Amplitude => self.amplitude_mod[..levels.len()].copy_from_slice(levels),
+            ModDestination::WavetablePosition => {}
+        }
+    }
+
+    /// Undo the phase and amplitude modulation left by `modulate`. Frequency
+    /// modulation is set from scratch every batch.
+    fn clear_modulation(&mut self) {
+        self.phase_mod = [S::ZERO; BATCH_SIZE];
+        self.amplitude_mod = [S::ONE; BATCH_SIZE];
+    }
+
+    fn sync_to(&mut self, master: &OscillatorHelper<BATCH_SIZE, S>) {
+        self.sync_wrap = master.wrap_modulo;
+        self.sync_modulo = master.modulo;
+        self.sync_phase_increment = master.phase_increment;
+    }
+
+    fn update(&mut self, frames: usize) {
+        self.octave_offset.fill(&mut self.pitch_offset[..frames]);
+        self.semitone_offset.fill(&mut self.semitones[..frames]);
+        self.cent_offset.fill(&mut self.cents[..frames]);
+        self.amplitude.fill(&mut self.amplitudes[..frames]);
 
-        for (((out_modulo, out_wrap_modulo), input_frequency_mod_ratio), frequency_mod) in self
-            .modulo
+        for ((pitch_offset, semitones), cents) in self.pitch_offset[..frames]
+            .iter_mut()
+            .zip(self.semitones.iter())
+            .zip(self.cents.iter())
+        {
+            *pitch_offset = (Octaves(*pitch_offset)
+                + Octaves::from(Semitones(*semitones))
+                + Octaves::from(Cents(*cents)))
+            .0;
+        }
+
+        for ((frequency_ratio, frequency_mod), pitch_offset) in self.frequency_ratio[..frames]
             .iter_mut()
-            .zip(self.wrap_modulo.iter_mut())
-            .zip(self.input_frequency_mod_ratio.iter())
             .zip(self.frequency_mod.iter())
+            .zip(self.pitch_offset.iter())
         {
-            let frequency = (self.input_frequency
-                * input_frequency_mod_ratio
-                * exp2(frequency_mod + const_offset))
-            .max(-OSC_MAX_FREQ)
-            .min(OSC_MAX_FREQ);
+            *frequency_ratio = *frequency_mod + S::from_f64(*pitch_offset);
+        }
+        simd::exp2(&mut self.frequency_ratio[..frames]);
 
-            let phase_incr = frequency / self.sample_rate;
+        let input_frequency = S::from_f64(self.input_frequency.0);
+        let max_frequency = S::from_f64(OSC_MAX_FREQ);
+        let sample_rate = S::from_f64(self.sample_rate);
 
-            let wrap = if phase_incr > 0.0 && self.last_modulo >= 1.0 {
-                self.last_modulo -= 1.0;
+        for (
+            ((((out_modulo, out_wrap_modulo), out_phase_increment), out_sync_from), input),
+            sync_input,
+        ) in self.modulo[..frames]
+            .iter_mut()
+            .zip(self.wrap_modulo.iter_mut())
+            .zip(self.phase_increment.iter_mut())
+            .zip(self.sync_from.iter_mut())
+            .zip(
+                self.input_frequency_mod_ratio
+                    .iter()
+                    .zip(self.frequency_ratio.iter()),
+            )
+            .zip(
+                self.sync_wrap
+                    .iter()
+                    .zip(self.sync_modulo.iter())
+                    .zip(self.sync_phase_increment.iter()),
+            )
+        {
+            let (input_frequency_mod_ratio, frequency_ratio) = input;
+            let ((sync_wrap, sync_modulo), sync_phase_increment) = sync_input;
+
+            let frequency = (input_frequency * *input_frequency_mod_ratio * *frequency_ratio)
+                .clamp(-max_frequency, max_frequency);
+
+            let phase_incr = frequency / sample_rate;
+
+            let mut wrap = if phase_incr > S::ZERO && self.last_modulo >= S::ONE {
+                self.last_modulo -= S::ONE;
                 true
-            } else if phase_incr < 0.0 && self.last_modulo <= 0.0 {
-                self.last_modulo += 1.0;
+            } else if phase_incr < S::ZERO && self.last_modulo <= S::ZERO {
+                self.last_modulo += S::ONE;
                 true
             } else {
                 false
             };
 
+            if self.sync && *sync_wrap {
+                let advance =
+                    waveform::wrap_fraction(*sync_modulo, *sync_phase_increment) * phase_incr;
+                *out_sync_from = wrap01(self.last_modulo - advance);
+                self.last_modulo = wrap01(advance);
+                wrap = true;
+            }
+
             *out_modulo = self.last_modulo;
             *out_wrap_modulo = wrap;
+            *out_phase_increment = phase_incr;
 
             self.last_modulo += phase_incr;
         }
     }
+
+    fn wrap_phase(&mut self, frames: usize) {
+        for ((phase, modulo), phase_mod) in self.phase[..frames]
+            .iter_mut()
+            .zip(self.modulo.iter())
+            .zip(self.phase_mod.iter())
+        {
+            *phase = *modulo + *phase_mod;
+        }
+        simd::wrap01(&mut self.phase[..frames]);
+    }
 }
 
-struct BandLimitedOscillator {
-    helper: OscillatorHelper,
-    output: BatchData,
+struct BandLimitedOscillator<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    waveform: Waveform,
+    pulse_width: f64,
+    output: BatchData<BATCH_SIZE, S>,
 }
 
-impl BandLimitedOscillator {
+impl<const BATCH_SIZE: usize, S: Sample> BandLimitedOscillator<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         BandLimitedOscillator {
             helper: OscillatorHelper::new(sample_rate),
-            output: [0.0; BATCH_SIZE],
+            waveform: Waveform::Sine,
+            pulse_width: 0.5,
+            output: [S::ZERO; BATCH_SIZE],
         }
     }
 
-    fn render(&mut self) {
-        self.helper.update();
+    fn render(&mut self, frames: usize) {
+        self.helper.update(frames);
+        self.helper.wrap_phase(frames);
+
+        let pulse_width = S::from_f64(self.pulse_width);
+
+        match self.waveform {
+            Waveform::Sine if !self.helper.sync => self.render_sine(frames),
+            Waveform::Sine => self.render_shape(frames, |modulo, _| waveform::sine(modulo)),
+            Waveform::Saw => self.render_shape(frames, waveform::saw),
+            Waveform::Square => self.render_shape(frames, |modulo, phase_increment| {
+                waveform::square(modulo, phase_increment, pulse_width)
+            }),
+            Waveform::Triangle => self.render_shape(frames, waveform::triangle),
+        }
+    }
+
+    #[inline(always)]
+    fn render_shape<F: Fn(S, S) -> S>(&mut self, frames: usize, shape: F) {
+        if self.helper.sync {
+            self.render_synced_shape(frames, shape);
+            return;
+        }
 
-        for (((output, modulo), phase_mod), amplitude_mod) in self
-            .output
+        for ((((output, phase), phase_increment), amplitude), amplitude_mod) in self.output
+            [..frames]
             .iter_mut()
-            .zip(self.helper.modulo.iter())
-            .zip(self.helper.phase_mod.iter())
+            .zip(self.helper.phase.iter())
+            .zip(self.helper.phase_increment.iter())
+            .zip(self.helper.amplitudes.iter())
+            .zip(self.helper.amplitude_mod.iter())
+        {
+            *output = shape(*phase, *phase_increment) * *amplitude * *amplitude_mod;
+        }
+    }
+
+    /// `render_shape` for the sine, with the shape computed for the whole
+    /// batch at once.
+    fn render_sine(&mut self, frames: usize) {
+        let output = &mut self.output[..frames];
+
+        for (output, phase) in output.iter_mut().zip(self.helper.phase.iter()) {
+            *output = waveform::sine_argument(*phase);
+        }
+        simd::parabolic_sine(output);
+
+        for ((output, amplitude), amplitude_mod) in output
+            .iter_mut()
+            .zip(self.helper.amplitudes.iter())
             .zip(self.helper.amplitude_mod.iter())
         {
-            let modulo = wrap01(modulo + phase_mod);
-            let angle = modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            *output = parabolic_sine(-angle) * self.helper.amplitude * amplitude_mod;
+            *output = *output * *amplitude * *amplitude_mod;
+        }
+    }
+
+    fn render_synced_shape<F: Fn(S, S) -> S>(&mut self, frames: usize, shape: F) {
+        for (((((output, phase), phase_increment), amplitude), amplitude_mod), sync) in self.output
+            [..frames]
+            .iter_mut()
+            .zip(self.helper.phase.iter())
+            .zip(self.helper.phase_increment.iter())
+            .zip(self.helper.amplitudes.iter())
+            .zip(self.helper.amplitude_mod.iter())
+            .zip(
+                self.helper
+                    .sync_wrap
+                    .iter()
+                    .zip(self.helper.sync_modulo.iter())
+                    .zip(self.helper.sync_phase_increment.iter())
+                    .zip(self.helper.sync_from.iter()),
+            )
+        {
+            let (((sync_wrap, sync_modulo), sync_phase_increment), sync_from) = sync;
+
+            let out = shape(*phase, *phase_increment)
+                + waveform::sync_correction(
+                    |modulo| shape(modulo, S::ZERO),
+                    *sync_wrap,
+                    *sync_modulo,
+                    *sync_phase_increment,
+                    *sync_from,
+                    *phase,
+                    *phase_increment,
+                );
+            *output = out * *amplitude * *amplitude_mod;
         }
     }
 }
 
-struct LFO {
-    helper: OscillatorHelper,
-    output: BatchData,
-    quad_output: BatchData,
+struct WavetableOscillator<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    wavetable: Option<Arc<Wavetable>>,
+    position: f64,
+    position_mod: BatchData<BATCH_SIZE, S>,
+    output: BatchData<BATCH_SIZE, S>,
 }
 
-impl LFO {
+impl<const BATCH_SIZE: usize, S: Sample> WavetableOscillator<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        WavetableOscillator {
+            helper: OscillatorHelper::new(sample_rate),
+            wavetable: None,
+            position: 0.0,
+            position_mod: [S::ZERO; BATCH_SIZE],
+            output: [S::ZERO; BATCH_SIZE],
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.wavetable.is_some()
+    }
+
+    /// Apply a modulation envelope, which can also move the position.
+    fn modulate(&mut self, destination: ModDestination, levels: &[S], amp_envelope: bool) {
+        match destination {
+            ModDestination::WavetablePosition => {
+                self.position_mod[..levels.len()].copy_from_slice(levels)
+            }
+            _ => self.helper.modulate(destination, levels, amp_envelope),
+        }
+    }
+
+    fn clear_modulation(&mut self) {
+        self.helper.clear_modulation();
+        self.position_mod = [S::ZERO; BATCH_SIZE];
+    }
+
+    fn render(&mut self, frames: usize) {
+        let wavetable = match self.wavetable {
+            Some(ref wavetable) => wavetable,
+            None => return,
+        };
+
+        self.helper.update(frames);
+        self.helper.wrap_phase(frames);
+
+        let position = S::from_f64(self.position);
+
+        for (((((output, phase), phase_increment), amplitude), amplitude_mod), position_mod) in self
+            .output[..frames]
+            .iter_mut()
+            .zip(self.helper.phase.iter())
+            .zip(self.helper.phase_increment.iter())
+            .zip(self.helper.amplitudes.iter())
+            .zip(self.helper.amplitude_mod.iter())
+            .zip(self.position_mod.iter())
+        {
+            *output = wavetable.sample(*phase, *phase_increment, position + *position_mod)
+                * *amplitude
+                * *amplitude_mod;
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+struct LFO<const BATCH_SIZE: usize, S> {
+    helper: OscillatorHelper<BATCH_SIZE, S>,
+    waveform: LfoWaveform,
+    pulse_width: f64,
+    random: RandomSteps,
+    frequency: Hz,
+    tempo_sync: Option<Division>,
+    mode: LfoMode,
+    fade: LfoFade,
+    /// Set once a one-shot LFO has run its cycle.
+    finished: bool,
+    output: BatchData<BATCH_SIZE, S>,
+    quad_output: BatchData<BATCH_SIZE, S>,
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> LFO<BATCH_SIZE, S> {
     fn new(sample_rate: f64) -> Self {
         LFO {
             helper: OscillatorHelper::new(sample_rate),
-            output: [0.0; BATCH_SIZE],
-            quad_output: [0.0; BATCH_SIZE],
+            waveform: LfoWaveform::Sine,
+            pulse_width: 0.5,
+            random: RandomSteps::new(waveform::LFO_RANDOM_SEED),
+            frequency: Hz(0.0),
+            tempo_sync: None,
+            mode: LfoMode::FreeRunning,
+            fade: LfoFade::default(),
+            finished: false,
+            output: [S::ZERO; BATCH_SIZE],
+            quad_output: [S::ZERO; BATCH_SIZE],
         }
     }
 
-    fn render(&mut self) {
-        self.helper.update();
+    fn set_frequency(&mut self, frequency: Hz) {
+        self.frequency = frequency;
+        if self.tempo_sync.is_none() {
+            self.helper.input_frequency = frequency;
+        }
+    }
+
+    fn set_tempo_sync(&mut self, division: Option<Division>) {
+        self.tempo_sync = division;
+        if division.is_none() {
+            self.helper.input_frequency = self.frequency;
+        }
+    }
+
+    /// Lock the rate and phase of a tempo-synced LFO to the transport.
+    fn follow_transport(&mut self, transport: &Transport) {
+        if let Some(division) = self.tempo_sync {
+            self.helper.input_frequency = division.frequency(transport.bpm);
+            self.helper.last_modulo = S::from_f64(division.phase(transport.position));
+        }
+    }
+
+    fn set_mode(&mut self, mode: LfoMode) {
+        self.mode = mode;
+        self.finished = false;
+    }
+
+    fn set_delay(&mut self, delay: f64, fade_in: f64) {
+        self.fade.set_times(delay, fade_in, self.helper.sample_rate);
+    }
+
+    /// Start a note: restart the delay and fade-in and, unless the LFO is
+    /// free running, the cycle.
+    fn trigger(&mut self) {
+        self.fade.restart();
+
+        if self.mode != LfoMode::FreeRunning {
+            self.helper.last_modulo = S::ZERO;
+            self.random.advance();
+            self.finished = false;
+        }
+    }
 
-        for (((output, quad_output), modulo), amplitude_mod) in self
-            .output
+    fn reset(&mut self) {
+        self.helper.reset();
+        self.random = RandomSteps::new(waveform::LFO_RANDOM_SEED);
+        self.fade.restart();
+        self.finished = false;
+    }
+
+    fn render(&mut self, frames: usize) {
+        self.helper.update(frames);
+
+        for amplitude_mod in self.helper.amplitude_mod[..frames].iter_mut() {
+            *amplitude_mod = S::from_f64(self.fade.next_gain());
+        }
+
+        let pulse_width = S::from_f64(self.pulse_width);
+        let quarter = S::from_f64(0.25);
+
+        match self.waveform {
+            LfoWaveform::Sine => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::sine(modulo))
+            }
+            LfoWaveform::Triangle => self.render_shape(frames, quarter, |modulo, _| {
+                waveform::triangle(modulo, S::ZERO)
+            }),
+            LfoWaveform::RampUp => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::ramp_up(modulo))
+            }
+            LfoWaveform::RampDown => {
+                self.render_shape(frames, quarter, |modulo, _| waveform::ramp_down(modulo))
+            }
+            LfoWaveform::Square => self.render_shape(frames, quarter, |modulo, _| {
+                waveform::pulse(modulo, pulse_width)
+            }),
+            // The random shapes have no phase to shift, so their quadrature
+            // output is the same as the main output.
+            LfoWaveform::SampleAndHold => {
+                self.render_shape(frames, S::ZERO, |_, random| S::from_f64(random.held()))
+            }
+            LfoWaveform::SmoothRandom => self.render_shape(frames, S::ZERO, |modulo, random| {
+                S::from_f64(random.smoothed(modulo.to_f64()))
+            }),
+        }
+    }
+
+    #[inline(always)]
+    fn render_shape<F: Fn(S, &RandomSteps) -> S>(
+        &mut self,
+        frames: usize,
+        quad_offset: S,
+        shape: F,
+    ) {
+        for (((((output, quad_output), modulo), wrap_modulo), amplitude), amplitude_mod) in self
+            .output[..frames]
             .iter_mut()
             .zip(self.quad_output.iter_mut())
             .zip(self.helper.modulo.iter())
+            .zip(self.helper.wrap_modulo.iter())
+            .zip(self.helper.amplitudes.iter())
             .zip(self.helper.amplitude_mod.iter())
         {
-            let angle = modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            *output = parabolic_sine(-angle) * self.helper.amplitude * amplitude_mod;
+            if *wrap_modulo {
+                if self.mode == LfoMode::OneShot {
+                    self.finished = true;
+                } else {
+                    self.random.advance();
+                }
+            }
+
+            // A finished one-shot holds the end of its cycle.
+            let modulo = if self.finished { S::ONE } else { *modulo };
 
-            let quad_angle = (modulo + 0.25) * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            *quad_output = parabolic_sine(-quad_angle) * self.helper.amplitude * amplitude_mod;
+            *output = shape(modulo, &self.random) * *amplitude * *amplitude_mod;
+
+            let quad_modulo = {
+                let quad_modulo = modulo + quad_offset;
+                if quad_modulo >= S::ONE {
+                    quad_modulo - S::ONE
+                } else {
+                    quad_modulo
+                }
+            };
+            *quad_output = shape(quad_modulo, &self.random) * *amplitude * *amplitude_mod;
         }
     }
 }
 
-pub struct Synth {
-    osc1: BandLimitedOscillator,
-    osc2: BandLimitedOscillator,
-    lfo: LFO,
+struct Envelope<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    segments: AdsrSegments,
+    state: AdsrState,
+    output: BatchData<BATCH_SIZE, S>,
 }
 
-impl Synth {
-    pub fn new(sample_rate: f64) -> Self {
+impl<const BATCH_SIZE: usize, S: Sample> Envelope<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        Envelope {
+            enabled: false,
+            segments: AdsrSegments::new(&AdsrSettings::default(), sample_rate),
+            state: AdsrState::default(),
+            output: [S::ONE; BATCH_SIZE],
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        // Note events only happen between batches, so an idle or sustaining
+        // envelope stays at the same level for the whole batch.
+        if self.state.is_steady() {
+            let level = S::from_f64(self.state.next(&self.segments));
+            for output in self.output[..frames].iter_mut() {
+                *output = level;
+            }
+            return;
+        }
+
+        for output in self.output[..frames].iter_mut() {
+            *output = S::from_f64(self.state.next(&self.segments));
+        }
+    }
+}
+
+struct ModEnvelope<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    destination: ModDestination,
+    stages: BreakpointStages,
+    state: BreakpointState,
+    output: BatchData<BATCH_SIZE, S>,
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> ModEnvelope<BATCH_SIZE, S> {
+    fn new() -> Self {
+        ModEnvelope {
+            enabled: false,
+            destination: ModDestination::default(),
+            stages: BreakpointStages::default(),
+            state: BreakpointState::default(),
+            output: [S::ZERO; BATCH_SIZE],
+        }
+    }
+
+    fn render(&mut self, frames: usize) {
+        if self.state.is_steady() {
+            let level = S::from_f64(self.state.next(&self.stages));
+            for output in self.output[..frames].iter_mut() {
+                *output = level;
+            }
+            return;
+        }
+
+        for output in self.output[..frames].iter_mut() {
+            *output = S::from_f64(self.state.next(&self.stages));
+        }
+    }
+}
+
+struct Filter<const BATCH_SIZE: usize, S> {
+    enabled: bool,
+    settings: FilterSettings,
+    sample_rate: f64,
+    svf: SvfState<S>,
+    ladder: LadderState<S>,
+
+    /// Cutoff modulation in octaves, added to the static cutoff.
+    pub cutoff_mod: BatchData<BATCH_SIZE, S>,
+    pub resonance: BatchData<BATCH_SIZE, S>,
+
+    cutoff: BatchData<BATCH_SIZE, S>,
+    svf_coefficients: [SvfCoefficients<S>; BATCH_SIZE],
+    ladder_coefficients: [LadderCoefficients<S>; BATCH_SIZE],
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> Filter<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
+        Filter {
+            enabled: false,
+            settings: FilterSettings::default(),
+            sample_rate,
+            svf: SvfState::default(),
+            ladder: LadderState::default(),
+            cutoff_mod: [S::ZERO; BATCH_SIZE],
+            resonance: [S::ZERO; BATCH_SIZE],
+            cutoff: [S::ZERO; BATCH_SIZE],
+            svf_coefficients: [SvfCoefficients::default(); BATCH_SIZE],
+            ladder_coefficients: [LadderCoefficients::default(); BATCH_SIZE],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.svf = SvfState::default();
+        self.ladder = LadderState::default();
+    }
+
+    /// Set the cutoff modulation and resonance from the LFO and, where
+    /// they're enabled, the amp and mod envelopes.
+    fn modulate(
+        &mut self,
+        frames: usize,
+        lfo: &BatchData<BATCH_SIZE, S>,
+        envelope: Option<&BatchData<BATCH_SIZE, S>>,
+        mod_envelope: Option<&BatchData<BATCH_SIZE, S>>,
+    ) {
+        for i in 0..frames {
+            let envelope = envelope.map_or(S::ZERO, |levels| levels[i]);
+            let mod_envelope = mod_envelope.map_or(S::ZERO, |levels| levels[i]);
+            let (cutoff_mod, resonance) = self.settings.modulation(lfo[i], envelope, mod_envelope);
+            self.cutoff_mod[i] = cutoff_mod;
+            self.resonance[i] = resonance;
+        }
+    }
+
+    /// Filter a batch in place.
+    fn render(&mut self, output: &mut [S]) {
+        let frames = output.len();
+
+        let cutoff = &mut self.cutoff[..frames];
+        cutoff.copy_from_slice(&self.cutoff_mod[..frames]);
+        simd::exp2(cutoff);
+        let static_cutoff = S::from_f64(self.settings.cutoff.0);
+        for cutoff in cutoff.iter_mut() {
+            *cutoff *= static_cutoff;
+        }
+
+        match self.settings.model {
+            FilterModel::StateVariable => {
+                for ((coefficients, cutoff), resonance) in self.svf_coefficients[..frames]
+                    .iter_mut()
+                    .zip(self.cutoff.iter())
+                    .zip(self.resonance.iter())
+                {
+                    *coefficients = SvfCoefficients::new(*cutoff, *resonance, self.sample_rate);
+                }
+
+                let mode = self.settings.mode;
+                for (output, coefficients) in output.iter_mut().zip(self.svf_coefficients.iter()) {
+                    *output = self.svf.process(*output, coefficients, mode);
+                }
+            }
+            FilterModel::Ladder(oversampling) => {
+                for ((coefficients, cutoff), resonance) in self.ladder_coefficients[..frames]
+                    .iter_mut()
+                    .zip(self.cutoff.iter())
+                    .zip(self.resonance.iter())
+                {
+                    *coefficients = LadderCoefficients::new(
+                        *cutoff,
+                        *resonance,
+                        self.sample_rate,
+                        oversampling,
+                    );
+                }
+
+                for (output, coefficients) in output.iter_mut().zip(self.ladder_coefficients.iter())
+                {
+                    *output = self.ladder.process(*output, coefficients);
+                }
+            }
+        }
+    }
+}
+
+pub struct Synth<const BATCH_SIZE: usize = DEFAULT_BATCH_SIZE, S = f64> {
+    osc1: BandLimitedOscillator<BATCH_SIZE, S>,
+    osc2: BandLimitedOscillator<BATCH_SIZE, S>,
+    wavetable: WavetableOscillator<BATCH_SIZE, S>,
+    lfo: LFO<BATCH_SIZE, S>,
+    osc2_frequency_mod_source: ModSource,
+    envelope: Envelope<BATCH_SIZE, S>,
+    mod_envelope: ModEnvelope<BATCH_SIZE, S>,
+    filter: Filter<BATCH_SIZE, S>,
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> Synth<BATCH_SIZE, S> {
+    fn helper(&self, oscillator: Oscillator) -> &OscillatorHelper<BATCH_SIZE, S> {
+        match oscillator {
+            Oscillator::Osc1 => &self.osc1.helper,
+            Oscillator::Osc2 => &self.osc2.helper,
+            Oscillator::Wavetable => &self.wavetable.helper,
+            Oscillator::Lfo => &self.lfo.helper,
+        }
+    }
+
+    fn helper_mut(&mut self, oscillator: Oscillator) -> &mut OscillatorHelper<BATCH_SIZE, S> {
+        match oscillator {
+            Oscillator::Osc1 => &mut self.osc1.helper,
+            Oscillator::Osc2 => &mut self.osc2.helper,
+            Oscillator::Wavetable => &mut self.wavetable.helper,
+            Oscillator::Lfo => &mut self.lfo.helper,
+        }
+    }
+}
+
+impl<const BATCH_SIZE: usize, S: Sample> SynthEngine<S> for Synth<BATCH_SIZE, S> {
+    fn new(sample_rate: f64) -> Self {
         let mut synth = Synth {
             osc1: BandLimitedOscillator::new(sample_rate),
             osc2: BandLimitedOscillator::new(sample_rate),
+            wavetable: WavetableOscillator::new(sample_rate),
             lfo: LFO::new(sample_rate),
+            osc2_frequency_mod_source: ModSource::default(),
+            envelope: Envelope::new(sample_rate),
+            mod_envelope: ModEnvelope::new(),
+            filter: Filter::new(sample_rate),
         };
 
-        synth.osc1.helper.input_frequency = 440.0;
-        synth.osc2.helper.input_frequency = 440.0;
+        synth.osc1.helper.input_frequency = Hz(440.0);
+        synth.osc2.helper.input_frequency = Hz(440.0);
+        synth.wavetable.helper.input_frequency = Hz(440.0);
 
-        synth.osc2.helper.cent_offset = 2.5;
+        synth.osc2.helper.cent_offset.set_immediate(2.5);
 
-        synth.lfo.helper.input_frequency = 0.5;
+        synth.lfo.set_frequency(Hz(0.5));
 
         synth
     }
 
-    pub fn render(&mut self, buffer: &mut [f64]) {
-        for output_batch in buffer.chunks_exact_mut(BATCH_SIZE) {
-            self.lfo.render();
+    fn render(&mut self, buffer: &mut [S]) {
+        let half = S::from_f64(0.5);
+
+        for output_batch in buffer.chunks_mut(BATCH_SIZE) {
+            let frames = output_batch.len();
+
+            if self.envelope.enabled {
+                self.envelope.render(frames);
+                self.osc1.helper.amplitude_mod = self.envelope.output;
+                self.osc2.helper.amplitude_mod = self.envelope.output;
+                self.wavetable.helper.amplitude_mod = self.envelope.output;
+            }
+
+            self.lfo.render(frames);
 
             self.osc1.helper.frequency_mod = self.lfo.output;
-            self.osc2.helper.frequency_mod = self.lfo.output;
+            self.osc2.helper.frequency_mod = match self.osc2_frequency_mod_source {
+                ModSource::Lfo => self.lfo.output,
+                ModSource::LfoQuadrature => self.lfo.quad_output,
+            };
 
-            self.osc1.render();
-            self.osc2.render();
+            if self.mod_envelope.enabled {
+                self.mod_envelope.render(frames);
+                let levels = &self.mod_envelope.output[..frames];
+                let destination = self.mod_envelope.destination;
+                let amp_envelope = self.envelope.enabled;
+
+                self.osc1.helper.modulate(destination, levels, amp_envelope);
+                self.osc2.helper.modulate(destination, levels, amp_envelope);
+            }
+
+            self.osc1.render(frames);
+
+            if self.osc2.helper.sync {
+                self.osc2.helper.sync_to(&self.osc1.helper);
+            }
+
+            self.osc2.render(frames);
 
             for ((output, osc1_out), osc2_out) in output_batch
                 .iter_mut()
                 .zip(self.osc1.output.iter())
                 .zip(self.osc2.output.iter())
             {
-                *output = 0.5 * osc1_out + 0.5 * osc2_out;
+                *output = half * *osc1_out + half * *osc2_out;
+            }
+
+            if self.wavetable.enabled() {
+                self.wavetable.helper.frequency_mod = self.lfo.output;
+
+                if self.mod_envelope.enabled {
+                    self.wavetable.modulate(
+                        self.mod_envelope.destination,
+                        &self.mod_envelope.output[..frames],
+                        self.envelope.enabled,
+                    );
+                }
+
+                self.wavetable.render(frames);
+
+                for (output, wavetable_out) in
+                    output_batch.iter_mut().zip(self.wavetable.output.iter())
+                {
+                    *output += half * *wavetable_out;
+                }
+            }
+
+            if self.filter.enabled {
+                let envelope = if self.envelope.enabled {
+                    Some(&self.envelope.output)
+                } else {
+                    None
+                };
+                let mod_envelope = if self.mod_envelope.enabled {
+                    Some(&self.mod_envelope.output)
+                } else {
+                    None
+                };
+
+                self.filter
+                    .modulate(frames, &self.lfo.output, envelope, mod_envelope);
+                self.filter.render(output_batch);
+            }
+        }
+    }
+
+    fn render_with_transport(&mut self, buffer: &mut [S], transport: &Transport) {
+        self.lfo.follow_transport(transport);
+        self.render(buffer);
+    }
+
+    fn reset(&mut self) {
+        self.osc1.helper.reset();
+        self.osc2.helper.reset();
+        self.wavetable.helper.reset();
+        self.lfo.reset();
+        self.envelope.state = AdsrState::default();
+        self.mod_envelope.state = BreakpointState::default();
+        self.filter.reset();
+    }
+
+    fn sample_rate(&self) -> f64 {
+        self.osc1.helper.sample_rate
+    }
+
+    fn note_on(&mut self) {
+        self.lfo.trigger();
+        self.envelope.state.note_on(&self.envelope.segments);
+        self.mod_envelope.state.note_on(&self.mod_envelope.stages);
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.state.note_off();
+        self.mod_envelope.state.note_off(&self.mod_envelope.stages);
+    }
+
+    fn set_wavetable(&mut self, wavetable: Option<Arc<Wavetable>>) {
+        self.wavetable.wavetable = wavetable;
+    }
+
+    fn set_wavetable_position(&mut self, position: f64) {
+        self.wavetable.position = position;
+    }
+
+    fn set_hard_sync(&mut self, enabled: bool) {
+        self.osc2.helper.sync = enabled;
+    }
+
+    fn set_osc2_frequency_mod_source(&mut self, source: ModSource) {
+        self.osc2_frequency_mod_source = source;
+    }
+
+    fn set_osc_waveform(&mut self, oscillator: Oscillator, waveform: Waveform) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.waveform = waveform,
+            Oscillator::Osc2 => self.osc2.waveform = waveform,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_osc_pulse_width(&mut self, oscillator: Oscillator, pulse_width: f64) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.pulse_width = pulse_width,
+            Oscillator::Osc2 => self.osc2.pulse_width = pulse_width,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_lfo_frequency(&mut self, frequency: Hz) {
+        self.lfo.set_frequency(frequency);
+    }
+
+    fn set_lfo_tempo_sync(&mut self, division: Option<Division>) {
+        self.lfo.set_tempo_sync(division);
+    }
+
+    fn set_lfo_waveform(&mut self, waveform: LfoWaveform) {
+        self.lfo.waveform = waveform;
+    }
+
+    fn set_lfo_pulse_width(&mut self, pulse_width: f64) {
+        self.lfo.pulse_width = pulse_width;
+    }
+
+    fn set_lfo_mode(&mut self, mode: LfoMode) {
+        self.lfo.set_mode(mode);
+    }
+
+    fn set_lfo_delay(&mut self, delay: f64, fade_in: f64) {
+        self.lfo.set_delay(delay, fade_in);
+    }
+
+    fn set_amp_envelope(&mut self, settings: Option<AdsrSettings>) {
+        match settings {
+            Some(settings) => {
+                self.envelope.segments = AdsrSegments::new(&settings, self.sample_rate());
+                self.envelope.enabled = true;
             }
+            None => {
+                self.envelope.enabled = false;
+                self.osc1.helper.amplitude_mod = [S::ONE; BATCH_SIZE];
+                self.osc2.helper.amplitude_mod = [S::ONE; BATCH_SIZE];
+                self.wavetable.helper.amplitude_mod = [S::ONE; BATCH_SIZE];
+            }
+        }
+    }
+
+    fn amp_envelope(&self) -> Option<AdsrState> {
+        if self.envelope.enabled {
+            Some(self.envelope.state)
+        } else {
+            None
+        }
+    }
+
+    fn set_mod_envelope(
+        &mut self,
+        settings: Option<&BreakpointSettings>,
+        destination: ModDestination,
+    ) {
+        self.osc1.helper.clear_modulation();
+        self.osc2.helper.clear_modulation();
+        self.wavetable.clear_modulation();
+
+        self.mod_envelope.enabled = settings.is_some();
+        self.mod_envelope.destination = destination;
+        if let Some(settings) = settings {
+            self.mod_envelope.stages = BreakpointStages::new(settings, self.sample_rate());
+        }
+    }
+
+    fn set_filter(&mut self, settings: Option<FilterSettings>) {
+        self.filter.enabled = settings.is_some();
+        if let Some(settings) = settings {
+            self.filter.settings = settings;
+        }
+    }
+
+    fn set_parameter_smoothing(&mut self, smoothing: Smoothing) {
+        self.osc1.helper.set_smoothing(smoothing);
+        self.osc2.helper.set_smoothing(smoothing);
+        self.wavetable.helper.set_smoothing(smoothing);
+        self.lfo.helper.set_smoothing(smoothing);
+    }
+
+    fn set_pitch_offset(
+        &mut self,
+        oscillator: Oscillator,
+        octaves: Octaves,
+        semitones: Semitones,
+        cents: Cents,
+    ) {
+        let helper = self.helper_mut(oscillator);
+        helper.octave_offset.set_target(octaves.0);
+        helper.semitone_offset.set_target(semitones.0);
+        helper.cent_offset.set_target(cents.0);
+    }
+
+    fn set_amplitude(&mut self, oscillator: Oscillator, amplitude: f64) {
+        self.helper_mut(oscillator).amplitude.set_target(amplitude);
+    }
+
+    fn set_parameter(&mut self, id: ParamId, value: f64) {
+        let value = id.info().clamp(value);
+
+        match id {
+            ParamId::Frequency => {
+                self.osc1.helper.input_frequency = Hz(value);
+                self.osc2.helper.input_frequency = Hz(value);
+                self.wavetable.helper.input_frequency = Hz(value);
+            }
+            ParamId::Octaves(oscillator) => {
+                self.helper_mut(oscillator).octave_offset.set_target(value);
+            }
+            ParamId::Semitones(oscillator) => {
+                self.helper_mut(oscillator)
+                    .semitone_offset
+                    .set_target(value);
+            }
+            ParamId::Cents(oscillator) => {
+                self.helper_mut(oscillator).cent_offset.set_target(value);
+            }
+            ParamId::Level(oscillator) => {
+                self.helper_mut(oscillator).amplitude.set_target(value);
+            }
+            ParamId::Osc1Waveform => {
+                self.set_osc_waveform(Oscillator::Osc1, Waveform::from_value(value))
+            }
+            ParamId::Osc1PulseWidth => self.set_osc_pulse_width(Oscillator::Osc1, value),
+            ParamId::Osc2Waveform => {
+                self.set_osc_waveform(Oscillator::Osc2, Waveform::from_value(value))
+            }
+            ParamId::Osc2PulseWidth => self.set_osc_pulse_width(Oscillator::Osc2, value),
+            ParamId::HardSync => self.osc2.helper.sync = bool::from_value(value),
+            ParamId::WavetablePosition => self.set_wavetable_position(value),
+            ParamId::LfoFrequency => self.lfo.set_frequency(Hz(value)),
+            ParamId::LfoTempoSync => self.lfo.set_tempo_sync(Choice::from_value(value)),
+            ParamId::LfoWaveform => self.lfo.waveform = LfoWaveform::from_value(value),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width = value,
+            ParamId::LfoMode => self.lfo.set_mode(LfoMode::from_value(value)),
+            ParamId::LfoDelay => {
+                let (_, fade_in) = self.lfo.fade.times();
+                self.lfo.set_delay(value, fade_in);
+            }
+            ParamId::LfoFadeIn => {
+                let (delay, _) = self.lfo.fade.times();
+                self.lfo.set_delay(delay, value);
+            }
+        }
+    }
+
+    fn parameter(&self, id: ParamId) -> f64 {
+        match id {
+            ParamId::Frequency => self.osc1.helper.input_frequency.0,
+            ParamId::Octaves(oscillator) => self.helper(oscillator).octave_offset.target(),
+            ParamId::Semitones(oscillator) => self.helper(oscillator).semitone_offset.target(),
+            ParamId::Cents(oscillator) => self.helper(oscillator).cent_offset.target(),
+            ParamId::Level(oscillator) => self.helper(oscillator).amplitude.target(),
+            ParamId::Osc1Waveform => self.osc1.waveform.value(),
+            ParamId::Osc1PulseWidth => self.osc1.pulse_width,
+            ParamId::Osc2Waveform => self.osc2.waveform.value(),
+            ParamId::Osc2PulseWidth => self.osc2.pulse_width,
+            ParamId::HardSync => self.osc2.helper.sync.value(),
+            ParamId::WavetablePosition => self.wavetable.position,
+            ParamId::LfoFrequency => self.lfo.frequency.0,
+            ParamId::LfoTempoSync => self.lfo.tempo_sync.value(),
+            ParamId::LfoWaveform => self.lfo.waveform.value(),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width,
+            ParamId::LfoMode => self.lfo.mode.value(),
+            ParamId::LfoDelay => self.lfo.fade.times().0,
+            ParamId::LfoFadeIn => self.lfo.fade.times().1,
         }
     }
 }
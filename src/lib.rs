@@ -1,5 +1,21 @@
 pub mod array_of_structs;
+pub mod biquad;
+pub mod engine;
+pub mod envelope;
+pub mod fastmath;
+pub mod filter;
 pub mod fixed_batch_size;
+pub mod modulation;
 pub mod one_frame_per_call;
+pub mod params;
+pub mod pitch;
+pub mod polyphony;
+pub mod sample;
+pub mod simd;
+pub mod smoothing;
+pub mod tempo;
+pub mod voice_per_lane;
+pub mod waveform;
+pub mod wavetable;
 
-mod fastmath;
+pub use engine::{EngineDescriptor, SynthEngine, ENGINES};
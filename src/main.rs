@@ -2,7 +2,7 @@ use std::error::Error;
 
 // use criterion::black_box;
 
-use plotters::style::{IntoFont, BLUE, GREEN, RED, WHITE};
+use plotters::style::{IntoFont, BLUE, CYAN, GREEN, MAGENTA, RED, WHITE};
 use plotters::{
     chart::ChartBuilder,
     drawing::{BitMapBackend, IntoDrawingArea},
@@ -22,40 +22,18 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     chart.configure_mesh().draw()?;
 
-    {
-        let mut data = vec![0.0f64; 128];
-        let mut synth = dsp_perf::one_frame_per_call::Synth::new(44100.0);
-        synth.render(&mut data);
-        chart
-            .draw_series(LineSeries::new(
-                data.into_iter().enumerate().map(|(i, y)| (i as f64, y)),
-                &RED,
-            ))?
-            .label("One frame per call");
-    }
-
-    {
-        let mut data = vec![0.0f64; 128];
-        let mut synth = dsp_perf::fixed_batch_size::Synth::new(44100.0);
-        synth.render(&mut data);
-        chart
-            .draw_series(LineSeries::new(
-                data.into_iter().enumerate().map(|(i, y)| (i as f64, y)),
-                &BLUE,
-            ))?
-            .label("Fixed bach size, struct-of-arrays");
-    }
+    let colors = [&RED, &BLUE, &GREEN, &MAGENTA, &CYAN];
 
-    {
+    for (engine, color) in dsp_perf::ENGINES.iter().zip(colors.iter().cycle()) {
         let mut data = vec![0.0f64; 128];
-        let mut synth = dsp_perf::array_of_structs::Synth::new(44100.0);
+        let mut synth = (engine.create)(44100.0);
         synth.render(&mut data);
         chart
             .draw_series(LineSeries::new(
                 data.into_iter().enumerate().map(|(i, y)| (i as f64, y)),
-                &GREEN,
+                *color,
             ))?
-            .label("Fixed bach size, array-of-structs");
+            .label(engine.name);
     }
 
     Ok(())
@@ -63,8 +41,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 // fn main() {
 //     let mut data = vec![0.0f64; 4096];
-//     // let mut synth = dsp_perf::one_frame_per_call::Synth::new(44100.0);
-//     let mut synth = dsp_perf::fixed_batch_size::Synth::new(44100.0);
+//     // let mut synth = (dsp_perf::ENGINES[0].create)(44100.0);
+//     let mut synth = (dsp_perf::ENGINES[1].create)(44100.0);
 //     for _ in 0..100000 {
 //         synth.render(&mut data);
 
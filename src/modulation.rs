@@ -0,0 +1,27 @@
+//! Modulation routing between the parts of a patch.
+
+/// A signal that can drive an oscillator's modulation inputs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModSource {
+    /// The LFO's main output.
+    #[default]
+    Lfo,
+    /// The LFO's output shifted by a quarter cycle. Driving one oscillator
+    /// with each LFO output gives quadrature patches like stereo vibrato.
+    LfoQuadrature,
+}
+
+/// An oscillator input that a modulation envelope can drive.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModDestination {
+    /// Frequency modulation in octaves, added to the LFO's.
+    #[default]
+    Frequency,
+    /// Phase modulation in cycles.
+    Phase,
+    /// Amplitude modulation, applied on top of the amp envelope.
+    Amplitude,
+    /// Morph position of the wavetable oscillator, added to its
+    /// `WavetablePosition` parameter. The other oscillators ignore it.
+    WavetablePosition,
+}
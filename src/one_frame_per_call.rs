@@ -1,88 +1,190 @@
-use crate::fastmath::{exp2, parabolic_sine, wrap01};
+use std::sync::Arc;
+
+use crate::engine::{Oscillator, SynthEngine};
+use crate::envelope::{
+    AdsrSegments, AdsrSettings, AdsrState, BreakpointSettings, BreakpointStages, BreakpointState,
+};
+use crate::fastmath::{exp2, wrap01};
+use crate::filter::{
+    FilterModel, FilterSettings, LadderCoefficients, LadderState, SvfCoefficients, SvfState,
+};
+use crate::modulation::{ModDestination, ModSource};
+use crate::params::{Choice, ParamId};
+use crate::pitch::{Cents, Hz, Octaves, Semitones};
+use crate::sample::Sample;
+use crate::smoothing::{SmoothedParam, Smoothing};
+use crate::tempo::{Division, Transport};
+use crate::waveform::{self, LfoFade, LfoMode, LfoWaveform, RandomSteps, Waveform};
+use crate::wavetable::Wavetable;
 
 const OSC_MAX_FREQ: f64 = 20480.0;
 
-struct OscillatorHelper {
+struct OscillatorHelper<S> {
     pub sample_rate: f64,
-    pub input_frequency: f64,
-    pub octave_offset: f64,
-    pub semitone_offset: f64,
-    pub cent_offset: f64,
-    pub amplitude: f64,
-
-    pub input_frequency_mod_ratio: f64,
-    pub phase_mod: f64,
-    pub amplitude_mod: f64,
-    pub frequency_mod: f64,
-
-    computed_frequency: f64,
-    phase_increment: f64,
-    modulo: f64,
+    pub input_frequency: Hz,
+    /// Static pitch offsets, in octaves, semitones and cents.
+    pub octave_offset: SmoothedParam,
+    pub semitone_offset: SmoothedParam,
+    pub cent_offset: SmoothedParam,
+    pub amplitude: SmoothedParam,
+
+    pub input_frequency_mod_ratio: S,
+    pub phase_mod: S,
+    pub amplitude_mod: S,
+    /// Frequency modulation in octaves, added to the static pitch offsets.
+    pub frequency_mod: S,
+
+    /// Hard sync: reset the phase whenever the master oscillator, described by
+    /// the `sync_*` inputs, wraps.
+    pub sync: bool,
+    pub sync_wrap: bool,
+    pub sync_modulo: S,
+    pub sync_phase_increment: S,
+
+    computed_frequency: S,
+    computed_amplitude: S,
+    phase_increment: S,
+    modulo: S,
+
+    current_modulo: S,
+    wrap_modulo: bool,
+    sync_from: S,
 }
 
-impl OscillatorHelper {
+impl<S: Sample> OscillatorHelper<S> {
     fn new(sample_rate: f64) -> Self {
         OscillatorHelper {
             sample_rate,
-            input_frequency: 0.0,
-            input_frequency_mod_ratio: 1.0,
-            octave_offset: 0.0,
-            semitone_offset: 0.0,
-            cent_offset: 0.0,
-            frequency_mod: 0.0,
-            amplitude: 1.0,
-            amplitude_mod: 1.0,
-            phase_mod: 0.0,
-            computed_frequency: 0.0,
-            phase_increment: 0.0,
-            modulo: 0.0,
+            input_frequency: Hz(0.0),
+            input_frequency_mod_ratio: S::ONE,
+            octave_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            semitone_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            cent_offset: SmoothedParam::new(0.0, Smoothing::default(), sample_rate),
+            frequency_mod: S::ZERO,
+            amplitude: SmoothedParam::new(1.0, Smoothing::default(), sample_rate),
+            amplitude_mod: S::ONE,
+            phase_mod: S::ZERO,
+            sync: false,
+            sync_wrap: false,
+            sync_modulo: S::ZERO,
+            sync_phase_increment: S::ZERO,
+            computed_frequency: S::ZERO,
+            computed_amplitude: S::ONE,
+            phase_increment: S::ZERO,
+            modulo: S::ZERO,
+            current_modulo: S::ZERO,
+            wrap_modulo: false,
+            sync_from: S::ZERO,
         }
     }
 
     fn update(&mut self) {
-        self.computed_frequency = self.input_frequency
+        let const_offset = Octaves(self.octave_offset.tick())
+            + Octaves::from(Semitones(self.semitone_offset.tick()))
+            + Octaves::from(Cents(self.cent_offset.tick()));
+        self.computed_amplitude = S::from_f64(self.amplitude.tick());
+
+        let max_frequency = S::from_f64(OSC_MAX_FREQ);
+        self.computed_frequency = (S::from_f64(self.input_frequency.0)
             * self.input_frequency_mod_ratio
-            * exp2(
-                self.frequency_mod
-                    + self.octave_offset * 12.0
-                    + self.semitone_offset
-                    + self.cent_offset / 100.0,
-            );
+            * exp2(self.frequency_mod + S::from_f64(const_offset.0)))
+        .clamp(-max_frequency, max_frequency);
 
-        if self.computed_frequency > OSC_MAX_FREQ {
-            self.computed_frequency = OSC_MAX_FREQ
-        } else if self.computed_frequency < -OSC_MAX_FREQ {
-            self.computed_frequency = -OSC_MAX_FREQ;
-        }
+        self.phase_increment = self.computed_frequency / S::from_f64(self.sample_rate);
+    }
+
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        let sample_rate = self.sample_rate;
+        self.octave_offset.set_smoothing(smoothing, sample_rate);
+        self.semitone_offset.set_smoothing(smoothing, sample_rate);
+        self.cent_offset.set_smoothing(smoothing, sample_rate);
+        self.amplitude.set_smoothing(smoothing, sample_rate);
+    }
 
-        self.phase_increment = self.computed_frequency / self.sample_rate;
+    /// Restart rendering. Parameters that are still gliding jump to their
+    /// targets.
+    fn reset(&mut self) {
+        self.octave_offset.finish();
+        self.semitone_offset.finish();
+        self.cent_offset.finish();
+        self.amplitude.finish();
+
+        self.computed_frequency = S::ZERO;
+        self.phase_increment = S::ZERO;
+        self.modulo = S::ZERO;
+        self.current_modulo = S::ZERO;
+        self.wrap_modulo = false;
+        self.sync_from = S::ZERO;
     }
 
     fn check_wrap_modulo(&mut self) -> bool {
-        if self.phase_increment > 0.0 && self.modulo >= 1.0 {
-            self.modulo -= 1.0;
+        let mut wrap = if self.phase_increment > S::ZERO && self.modulo >= S::ONE {
+            self.modulo -= S::ONE;
             true
-        } else if self.phase_increment < 0.0 && self.modulo <= 0.0 {
-            self.modulo += 1.0;
+        } else if self.phase_increment < S::ZERO && self.modulo <= S::ZERO {
+            self.modulo += S::ONE;
             true
         } else {
             false
+        };
+
+        if self.sync && self.sync_wrap {
+            let advance = waveform::wrap_fraction(self.sync_modulo, self.sync_phase_increment)
+                * self.phase_increment;
+            self.sync_from = wrap01(self.modulo - advance);
+            self.modulo = wrap01(advance);
+            wrap = true;
         }
+
+        self.current_modulo = self.modulo;
+        self.wrap_modulo = wrap;
+
+        wrap
+    }
+
+    fn sync_to(&mut self, master: &OscillatorHelper<S>) {
+        self.sync_wrap = master.wrap_modulo;
+        self.sync_modulo = master.current_modulo;
+        self.sync_phase_increment = master.phase_increment;
     }
 
     fn increment_modulo(&mut self) {
         self.modulo += self.phase_increment;
     }
+
+    /// Apply a modulation envelope on top of this frame's other modulation.
+    /// `amp_envelope` says whether `amplitude_mod` holds this frame's amp
+    /// envelope level, rather than the previous frame's modulation.
+    fn modulate(&mut self, destination: ModDestination, level: S, amp_envelope: bool) {
+        match destination {
+            ModDestination::Frequency => self.frequency_mod += level,
+            ModDestination::Phase => self.phase_mod = level,
+            ModDestination::Amplitude if amp_envelope => self.amplitude_mod *= level,
+            ModDestination::Amplitude => self.amplitude_mod = level,
+            ModDestination::WavetablePosition => {}
+        }
+    }
+
+    /// Undo the phase and amplitude modulation left by `modulate`. Frequency
+    /// modulation is set from scratch every frame.
+    fn clear_modulation(&mut self) {
+        self.phase_mod = S::ZERO;
+        self.amplitude_mod = S::ONE;
+    }
 }
 
-struct BandLimitedOscillator {
-    helper: OscillatorHelper,
+struct BandLimitedOscillator<S> {
+    helper: OscillatorHelper<S>,
+    waveform: Waveform,
+    pulse_width: f64,
 }
 
-impl BandLimitedOscillator {
+impl<S: Sample> BandLimitedOscillator<S> {
     fn new(sample_rate: f64) -> Self {
         BandLimitedOscillator {
             helper: OscillatorHelper::new(sample_rate),
+            waveform: Waveform::Sine,
+            pulse_width: 0.5,
         }
     }
 
@@ -90,32 +192,134 @@ impl BandLimitedOscillator {
         self.helper.update();
     }
 
-    fn render(&mut self) -> f64 {
+    fn render(&mut self) -> S {
         self.helper.check_wrap_modulo();
 
         let modulo = wrap01(self.helper.modulo + self.helper.phase_mod);
+        let phase_increment = self.helper.phase_increment;
+
+        let pulse_width = S::from_f64(self.pulse_width);
+
+        let out = match self.waveform {
+            Waveform::Sine => {
+                self.shape(modulo, phase_increment, |modulo, _| waveform::sine(modulo))
+            }
+            Waveform::Saw => self.shape(modulo, phase_increment, waveform::saw),
+            Waveform::Square => self.shape(modulo, phase_increment, |modulo, phase_increment| {
+                waveform::square(modulo, phase_increment, pulse_width)
+            }),
+            Waveform::Triangle => self.shape(modulo, phase_increment, waveform::triangle),
+        };
+
+        self.helper.increment_modulo();
+
+        out * self.helper.computed_amplitude * self.helper.amplitude_mod
+    }
 
-        let out: f64;
+    #[inline(always)]
+    fn shape<F: Fn(S, S) -> S>(&self, modulo: S, phase_increment: S, shape: F) -> S {
+        let out = shape(modulo, phase_increment);
+
+        if self.helper.sync {
+            out + waveform::sync_correction(
+                |modulo| shape(modulo, S::ZERO),
+                self.helper.sync_wrap,
+                self.helper.sync_modulo,
+                self.helper.sync_phase_increment,
+                self.helper.sync_from,
+                modulo,
+                phase_increment,
+            )
+        } else {
+            out
+        }
+    }
+}
 
-        {
-            let angle = modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            out = parabolic_sine(-angle);
+struct WavetableOscillator<S> {
+    helper: OscillatorHelper<S>,
+    wavetable: Option<Arc<Wavetable>>,
+    position: f64,
+    position_mod: S,
+}
+
+impl<S: Sample> WavetableOscillator<S> {
+    fn new(sample_rate: f64) -> Self {
+        WavetableOscillator {
+            helper: OscillatorHelper::new(sample_rate),
+            wavetable: None,
+            position: 0.0,
+            position_mod: S::ZERO,
         }
+    }
+
+    fn enabled(&self) -> bool {
+        self.wavetable.is_some()
+    }
+
+    fn update(&mut self) {
+        self.helper.update();
+    }
+
+    /// Apply a modulation envelope, which can also move the position.
+    fn modulate(&mut self, destination: ModDestination, level: S, amp_envelope: bool) {
+        match destination {
+            ModDestination::WavetablePosition => self.position_mod = level,
+            _ => self.helper.modulate(destination, level, amp_envelope),
+        }
+    }
+
+    fn clear_modulation(&mut self) {
+        self.helper.clear_modulation();
+        self.position_mod = S::ZERO;
+    }
+
+    fn render(&mut self) -> S {
+        self.helper.check_wrap_modulo();
+
+        let modulo = wrap01(self.helper.modulo + self.helper.phase_mod);
+
+        let out = match self.wavetable {
+            Some(ref wavetable) => wavetable.sample(
+                modulo,
+                self.helper.phase_increment,
+                S::from_f64(self.position) + self.position_mod,
+            ),
+            None => S::ZERO,
+        };
 
         self.helper.increment_modulo();
 
-        out * self.helper.amplitude * self.helper.amplitude_mod
+        out * self.helper.computed_amplitude * self.helper.amplitude_mod
     }
 }
 
-struct LFO {
-    helper: OscillatorHelper,
+#[allow(clippy::upper_case_acronyms)]
+struct LFO<S> {
+    helper: OscillatorHelper<S>,
+    waveform: LfoWaveform,
+    pulse_width: f64,
+    random: RandomSteps,
+    frequency: Hz,
+    tempo_sync: Option<Division>,
+    mode: LfoMode,
+    fade: LfoFade,
+    /// Set once a one-shot LFO has run its cycle.
+    finished: bool,
 }
 
-impl LFO {
+impl<S: Sample> LFO<S> {
     fn new(sample_rate: f64) -> Self {
         LFO {
             helper: OscillatorHelper::new(sample_rate),
+            waveform: LfoWaveform::Sine,
+            pulse_width: 0.5,
+            random: RandomSteps::new(waveform::LFO_RANDOM_SEED),
+            frequency: Hz(0.0),
+            tempo_sync: None,
+            mode: LfoMode::FreeRunning,
+            fade: LfoFade::default(),
+            finished: false,
         }
     }
 
@@ -123,74 +327,546 @@ impl LFO {
         self.helper.update();
     }
 
-    fn render(&mut self) -> (f64, f64) {
-        self.helper.check_wrap_modulo();
+    fn set_frequency(&mut self, frequency: Hz) {
+        self.frequency = frequency;
+        if self.tempo_sync.is_none() {
+            self.helper.input_frequency = frequency;
+        }
+    }
+
+    fn set_tempo_sync(&mut self, division: Option<Division>) {
+        self.tempo_sync = division;
+        if division.is_none() {
+            self.helper.input_frequency = self.frequency;
+        }
+    }
+
+    /// Lock the rate and phase of a tempo-synced LFO to the transport.
+    fn follow_transport(&mut self, transport: &Transport) {
+        if let Some(division) = self.tempo_sync {
+            self.helper.input_frequency = division.frequency(transport.bpm);
+            self.helper.modulo = S::from_f64(division.phase(transport.position));
+        }
+    }
+
+    fn set_mode(&mut self, mode: LfoMode) {
+        self.mode = mode;
+        self.finished = false;
+    }
+
+    fn set_delay(&mut self, delay: f64, fade_in: f64) {
+        self.fade.set_times(delay, fade_in, self.helper.sample_rate);
+    }
+
+    /// Start a note: restart the delay and fade-in and, unless the LFO is
+    /// free running, the cycle.
+    fn trigger(&mut self) {
+        self.fade.restart();
+
+        if self.mode != LfoMode::FreeRunning {
+            self.helper.modulo = S::ZERO;
+            self.random.advance();
+            self.finished = false;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.helper.reset();
+        self.random = RandomSteps::new(waveform::LFO_RANDOM_SEED);
+        self.fade.restart();
+        self.finished = false;
+    }
+
+    fn render(&mut self) -> (S, S) {
+        if self.helper.check_wrap_modulo() {
+            if self.mode == LfoMode::OneShot {
+                self.finished = true;
+            } else {
+                self.random.advance();
+            }
+        }
+
+        self.helper.amplitude_mod = S::from_f64(self.fade.next_gain());
+
+        // A finished one-shot holds the end of its cycle.
+        let modulo = if self.finished {
+            S::ONE
+        } else {
+            self.helper.modulo
+        };
+
+        // The random shapes have no phase to shift, so their quadrature
+        // output is the same as the main output.
+        let quad_offset = if self.waveform.is_random() {
+            S::ZERO
+        } else {
+            S::from_f64(0.25)
+        };
 
         let quad_modulo = {
-            let quad_modulo = self.helper.modulo + 0.25;
-            if quad_modulo >= 1.0 {
-                quad_modulo - 1.0
+            let quad_modulo = modulo + quad_offset;
+            if quad_modulo >= S::ONE {
+                quad_modulo - S::ONE
             } else {
                 quad_modulo
             }
         };
 
-        let out: f64;
-        let quad_out: f64;
+        let out = self.shape(modulo);
+        let quad_out = self.shape(quad_modulo);
 
-        {
-            let angle = self.helper.modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            out = parabolic_sine(-angle);
+        self.helper.increment_modulo();
 
-            let quad_angle = quad_modulo * 2.0 * std::f64::consts::PI - std::f64::consts::PI;
-            quad_out = parabolic_sine(-quad_angle);
+        (
+            out * self.helper.computed_amplitude * self.helper.amplitude_mod,
+            quad_out * self.helper.computed_amplitude * self.helper.amplitude_mod,
+        )
+    }
+
+    #[inline(always)]
+    fn shape(&self, modulo: S) -> S {
+        match self.waveform {
+            LfoWaveform::Sine => waveform::sine(modulo),
+            LfoWaveform::Triangle => waveform::triangle(modulo, S::ZERO),
+            LfoWaveform::RampUp => waveform::ramp_up(modulo),
+            LfoWaveform::RampDown => waveform::ramp_down(modulo),
+            LfoWaveform::Square => waveform::pulse(modulo, S::from_f64(self.pulse_width)),
+            LfoWaveform::SampleAndHold => S::from_f64(self.random.held()),
+            LfoWaveform::SmoothRandom => S::from_f64(self.random.smoothed(modulo.to_f64())),
         }
+    }
+}
 
-        self.helper.increment_modulo();
+struct Envelope {
+    enabled: bool,
+    segments: AdsrSegments,
+    state: AdsrState,
+}
 
-        (out, quad_out)
+impl Envelope {
+    fn new(sample_rate: f64) -> Self {
+        Envelope {
+            enabled: false,
+            segments: AdsrSegments::new(&AdsrSettings::default(), sample_rate),
+            state: AdsrState::default(),
+        }
+    }
+
+    fn render(&mut self) -> f64 {
+        self.state.next(&self.segments)
     }
 }
 
-pub struct Synth {
-    osc1: BandLimitedOscillator,
-    osc2: BandLimitedOscillator,
-    lfo: LFO,
+struct ModEnvelope {
+    enabled: bool,
+    destination: ModDestination,
+    stages: BreakpointStages,
+    state: BreakpointState,
 }
 
-impl Synth {
-    pub fn new(sample_rate: f64) -> Self {
+impl ModEnvelope {
+    fn new() -> Self {
+        ModEnvelope {
+            enabled: false,
+            destination: ModDestination::default(),
+            stages: BreakpointStages::default(),
+            state: BreakpointState::default(),
+        }
+    }
+
+    fn render(&mut self) -> f64 {
+        self.state.next(&self.stages)
+    }
+}
+
+struct Filter<S> {
+    enabled: bool,
+    settings: FilterSettings,
+    sample_rate: f64,
+    svf: SvfState<S>,
+    ladder: LadderState<S>,
+}
+
+impl<S: Sample> Filter<S> {
+    fn new(sample_rate: f64) -> Self {
+        Filter {
+            enabled: false,
+            settings: FilterSettings::default(),
+            sample_rate,
+            svf: SvfState::default(),
+            ladder: LadderState::default(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.svf = SvfState::default();
+        self.ladder = LadderState::default();
+    }
+
+    /// Filter one frame, with the cutoff and resonance modulated by this
+    /// frame's LFO output and envelope levels.
+    fn render(&mut self, input: S, lfo: S, envelope: S, mod_envelope: S) -> S {
+        let (cutoff_mod, resonance) = self.settings.modulation(lfo, envelope, mod_envelope);
+        let cutoff = S::from_f64(self.settings.cutoff.0) * exp2(cutoff_mod);
+
+        match self.settings.model {
+            FilterModel::StateVariable => {
+                let coefficients = SvfCoefficients::new(cutoff, resonance, self.sample_rate);
+                self.svf.process(input, &coefficients, self.settings.mode)
+            }
+            FilterModel::Ladder(oversampling) => {
+                let coefficients =
+                    LadderCoefficients::new(cutoff, resonance, self.sample_rate, oversampling);
+                self.ladder.process(input, &coefficients)
+            }
+        }
+    }
+}
+
+pub struct Synth<S = f64> {
+    osc1: BandLimitedOscillator<S>,
+    osc2: BandLimitedOscillator<S>,
+    wavetable: WavetableOscillator<S>,
+    lfo: LFO<S>,
+    osc2_frequency_mod_source: ModSource,
+    envelope: Envelope,
+    mod_envelope: ModEnvelope,
+    filter: Filter<S>,
+}
+
+impl<S: Sample> Synth<S> {
+    fn helper(&self, oscillator: Oscillator) -> &OscillatorHelper<S> {
+        match oscillator {
+            Oscillator::Osc1 => &self.osc1.helper,
+            Oscillator::Osc2 => &self.osc2.helper,
+            Oscillator::Wavetable => &self.wavetable.helper,
+            Oscillator::Lfo => &self.lfo.helper,
+        }
+    }
+
+    fn helper_mut(&mut self, oscillator: Oscillator) -> &mut OscillatorHelper<S> {
+        match oscillator {
+            Oscillator::Osc1 => &mut self.osc1.helper,
+            Oscillator::Osc2 => &mut self.osc2.helper,
+            Oscillator::Wavetable => &mut self.wavetable.helper,
+            Oscillator::Lfo => &mut self.lfo.helper,
+        }
+    }
+}
+
+impl<S: Sample> SynthEngine<S> for Synth<S> {
+    fn new(sample_rate: f64) -> Self {
         let mut synth = Synth {
             osc1: BandLimitedOscillator::new(sample_rate),
             osc2: BandLimitedOscillator::new(sample_rate),
+            wavetable: WavetableOscillator::new(sample_rate),
             lfo: LFO::new(sample_rate),
+            osc2_frequency_mod_source: ModSource::default(),
+            envelope: Envelope::new(sample_rate),
+            mod_envelope: ModEnvelope::new(),
+            filter: Filter::new(sample_rate),
         };
 
-        synth.osc1.helper.input_frequency = 440.0;
-        synth.osc2.helper.input_frequency = 440.0;
+        synth.osc1.helper.input_frequency = Hz(440.0);
+        synth.osc2.helper.input_frequency = Hz(440.0);
+        synth.wavetable.helper.input_frequency = Hz(440.0);
 
-        synth.osc2.helper.cent_offset = 2.5;
+        synth.osc2.helper.cent_offset.set_immediate(2.5);
 
-        synth.lfo.helper.input_frequency = 0.5;
+        synth.lfo.set_frequency(Hz(0.5));
 
         synth
     }
 
-    pub fn render(&mut self, buffer: &mut [f64]) {
+    fn render(&mut self, buffer: &mut [S]) {
+        let half = S::from_f64(0.5);
+
         for output in buffer {
+            let envelope_level = if self.envelope.enabled {
+                let level = S::from_f64(self.envelope.render());
+                self.osc1.helper.amplitude_mod = level;
+                self.osc2.helper.amplitude_mod = level;
+                self.wavetable.helper.amplitude_mod = level;
+                level
+            } else {
+                S::ZERO
+            };
+
             self.lfo.update();
-            let (lfo_out, _) = self.lfo.render();
+            let (lfo_out, lfo_quad_out) = self.lfo.render();
 
             self.osc1.helper.frequency_mod = lfo_out;
-            self.osc1.update();
+            self.osc2.helper.frequency_mod = match self.osc2_frequency_mod_source {
+                ModSource::Lfo => lfo_out,
+                ModSource::LfoQuadrature => lfo_quad_out,
+            };
+            self.wavetable.helper.frequency_mod = lfo_out;
+
+            let mod_envelope_level = if self.mod_envelope.enabled {
+                let level = S::from_f64(self.mod_envelope.render());
+                let destination = self.mod_envelope.destination;
+                let amp_envelope = self.envelope.enabled;
+
+                self.osc1.helper.modulate(destination, level, amp_envelope);
+                self.osc2.helper.modulate(destination, level, amp_envelope);
+                self.wavetable.modulate(destination, level, amp_envelope);
+                level
+            } else {
+                S::ZERO
+            };
 
-            self.osc2.helper.frequency_mod = lfo_out;
+            self.osc1.update();
             self.osc2.update();
 
             let osc1_out = self.osc1.render();
+
+            if self.osc2.helper.sync {
+                self.osc2.helper.sync_to(&self.osc1.helper);
+            }
+
             let osc2_out = self.osc2.render();
-            *output = 0.5 * osc1_out + 0.5 * osc2_out;
+            *output = half * osc1_out + half * osc2_out;
             // *output = osc1_out;
+
+            if self.wavetable.enabled() {
+                self.wavetable.update();
+
+                *output += half * self.wavetable.render();
+            }
+
+            if self.filter.enabled {
+                *output = self
+                    .filter
+                    .render(*output, lfo_out, envelope_level, mod_envelope_level);
+            }
+        }
+    }
+
+    fn render_with_transport(&mut self, buffer: &mut [S], transport: &Transport) {
+        self.lfo.follow_transport(transport);
+        self.render(buffer);
+    }
+
+    fn reset(&mut self) {
+        self.osc1.helper.reset();
+        self.osc2.helper.reset();
+        self.wavetable.helper.reset();
+        self.lfo.reset();
+        self.envelope.state = AdsrState::default();
+        self.mod_envelope.state = BreakpointState::default();
+        self.filter.reset();
+    }
+
+    fn sample_rate(&self) -> f64 {
+        self.osc1.helper.sample_rate
+    }
+
+    fn note_on(&mut self) {
+        self.lfo.trigger();
+        self.envelope.state.note_on(&self.envelope.segments);
+        self.mod_envelope.state.note_on(&self.mod_envelope.stages);
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.state.note_off();
+        self.mod_envelope.state.note_off(&self.mod_envelope.stages);
+    }
+
+    fn set_wavetable(&mut self, wavetable: Option<Arc<Wavetable>>) {
+        self.wavetable.wavetable = wavetable;
+    }
+
+    fn set_wavetable_position(&mut self, position: f64) {
+        self.wavetable.position = position;
+    }
+
+    fn set_hard_sync(&mut self, enabled: bool) {
+        self.osc2.helper.sync = enabled;
+    }
+
+    fn set_osc2_frequency_mod_source(&mut self, source: ModSource) {
+        self.osc2_frequency_mod_source = source;
+    }
+
+    fn set_osc_waveform(&mut self, oscillator: Oscillator, waveform: Waveform) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.waveform = waveform,
+            Oscillator::Osc2 => self.osc2.waveform = waveform,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_osc_pulse_width(&mut self, oscillator: Oscillator, pulse_width: f64) {
+        match oscillator {
+            Oscillator::Osc1 => self.osc1.pulse_width = pulse_width,
+            Oscillator::Osc2 => self.osc2.pulse_width = pulse_width,
+            Oscillator::Wavetable | Oscillator::Lfo => {}
+        }
+    }
+
+    fn set_lfo_frequency(&mut self, frequency: Hz) {
+        self.lfo.set_frequency(frequency);
+    }
+
+    fn set_lfo_tempo_sync(&mut self, division: Option<Division>) {
+        self.lfo.set_tempo_sync(division);
+    }
+
+    fn set_lfo_waveform(&mut self, waveform: LfoWaveform) {
+        self.lfo.waveform = waveform;
+    }
+
+    fn set_lfo_pulse_width(&mut self, pulse_width: f64) {
+        self.lfo.pulse_width = pulse_width;
+    }
+
+    fn set_lfo_mode(&mut self, mode: LfoMode) {
+        self.lfo.set_mode(mode);
+    }
+
+    fn set_lfo_delay(&mut self, delay: f64, fade_in: f64) {
+        self.lfo.set_delay(delay, fade_in);
+    }
+
+    fn set_amp_envelope(&mut self, settings: Option<AdsrSettings>) {
+        match settings {
+            Some(settings) => {
+                self.envelope.segments = AdsrSegments::new(&settings, self.sample_rate());
+                self.envelope.enabled = true;
+            }
+            None => {
+                self.envelope.enabled = false;
+                self.osc1.helper.amplitude_mod = S::ONE;
+                self.osc2.helper.amplitude_mod = S::ONE;
+                self.wavetable.helper.amplitude_mod = S::ONE;
+            }
+        }
+    }
+
+    fn amp_envelope(&self) -> Option<AdsrState> {
+        if self.envelope.enabled {
+            Some(self.envelope.state)
+        } else {
+            None
+        }
+    }
+
+    fn set_mod_envelope(
+        &mut self,
+        settings: Option<&BreakpointSettings>,
+        destination: ModDestination,
+    ) {
+        self.osc1.helper.clear_modulation();
+        self.osc2.helper.clear_modulation();
+        self.wavetable.clear_modulation();
+
+        self.mod_envelope.enabled = settings.is_some();
+        self.mod_envelope.destination = destination;
+        if let Some(settings) = settings {
+            self.mod_envelope.stages = BreakpointStages::new(settings, self.sample_rate());
+        }
+    }
+
+    fn set_filter(&mut self, settings: Option<FilterSettings>) {
+        self.filter.enabled = settings.is_some();
+        if let Some(settings) = settings {
+            self.filter.settings = settings;
+        }
+    }
+
+    fn set_parameter_smoothing(&mut self, smoothing: Smoothing) {
+        self.osc1.helper.set_smoothing(smoothing);
+        self.osc2.helper.set_smoothing(smoothing);
+        self.wavetable.helper.set_smoothing(smoothing);
+        self.lfo.helper.set_smoothing(smoothing);
+    }
+
+    fn set_pitch_offset(
+        &mut self,
+        oscillator: Oscillator,
+        octaves: Octaves,
+        semitones: Semitones,
+        cents: Cents,
+    ) {
+        let helper = self.helper_mut(oscillator);
+        helper.octave_offset.set_target(octaves.0);
+        helper.semitone_offset.set_target(semitones.0);
+        helper.cent_offset.set_target(cents.0);
+    }
+
+    fn set_amplitude(&mut self, oscillator: Oscillator, amplitude: f64) {
+        self.helper_mut(oscillator).amplitude.set_target(amplitude);
+    }
+
+    fn set_parameter(&mut self, id: ParamId, value: f64) {
+        let value = id.info().clamp(value);
+
+        match id {
+            ParamId::Frequency => {
+                self.osc1.helper.input_frequency = Hz(value);
+                self.osc2.helper.input_frequency = Hz(value);
+                self.wavetable.helper.input_frequency = Hz(value);
+            }
+            ParamId::Octaves(oscillator) => {
+                self.helper_mut(oscillator).octave_offset.set_target(value);
+            }
+            ParamId::Semitones(oscillator) => {
+                self.helper_mut(oscillator)
+                    .semitone_offset
+                    .set_target(value);
+            }
+            ParamId::Cents(oscillator) => {
+                self.helper_mut(oscillator).cent_offset.set_target(value);
+            }
+            ParamId::Level(oscillator) => {
+                self.helper_mut(oscillator).amplitude.set_target(value);
+            }
+            ParamId::Osc1Waveform => {
+                self.set_osc_waveform(Oscillator::Osc1, Waveform::from_value(value))
+            }
+            ParamId::Osc1PulseWidth => self.set_osc_pulse_width(Oscillator::Osc1, value),
+            ParamId::Osc2Waveform => {
+                self.set_osc_waveform(Oscillator::Osc2, Waveform::from_value(value))
+            }
+            ParamId::Osc2PulseWidth => self.set_osc_pulse_width(Oscillator::Osc2, value),
+            ParamId::HardSync => self.osc2.helper.sync = bool::from_value(value),
+            ParamId::WavetablePosition => self.set_wavetable_position(value),
+            ParamId::LfoFrequency => self.lfo.set_frequency(Hz(value)),
+            ParamId::LfoTempoSync => self.lfo.set_tempo_sync(Choice::from_value(value)),
+            ParamId::LfoWaveform => self.lfo.waveform = LfoWaveform::from_value(value),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width = value,
+            ParamId::LfoMode => self.lfo.set_mode(LfoMode::from_value(value)),
+            ParamId::LfoDelay => {
+                let (_, fade_in) = self.lfo.fade.times();
+                self.lfo.set_delay(value, fade_in);
+            }
+            ParamId::LfoFadeIn => {
+                let (delay, _) = self.lfo.fade.times();
+                self.lfo.set_delay(delay, value);
+            }
+        }
+    }
+
+    fn parameter(&self, id: ParamId) -> f64 {
+        match id {
+            ParamId::Frequency => self.osc1.helper.input_frequency.0,
+            ParamId::Octaves(oscillator) => self.helper(oscillator).octave_offset.target(),
+            ParamId::Semitones(oscillator) => self.helper(oscillator).semitone_offset.target(),
+            ParamId::Cents(oscillator) => self.helper(oscillator).cent_offset.target(),
+            ParamId::Level(oscillator) => self.helper(oscillator).amplitude.target(),
+            ParamId::Osc1Waveform => self.osc1.waveform.value(),
+            ParamId::Osc1PulseWidth => self.osc1.pulse_width,
+            ParamId::Osc2Waveform => self.osc2.waveform.value(),
+            ParamId::Osc2PulseWidth => self.osc2.pulse_width,
+            ParamId::HardSync => self.osc2.helper.sync.value(),
+            ParamId::WavetablePosition => self.wavetable.position,
+            ParamId::LfoFrequency => self.lfo.frequency.0,
+            ParamId::LfoTempoSync => self.lfo.tempo_sync.value(),
+            ParamId::LfoWaveform => self.lfo.waveform.value(),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width,
+            ParamId::LfoMode => self.lfo.mode.value(),
+            ParamId::LfoDelay => self.lfo.fade.times().0,
+            ParamId::LfoFadeIn => self.lfo.fade.times().1,
         }
     }
 }
@@ -0,0 +1,428 @@
+//! Host-facing parameters.
+//!
+//! Every setting a host or UI can change has a `ParamId`, and an entry in
+//! `PARAMS` describing its range, default and unit. Values are plain numbers
+//! in the parameter's unit, and choices are the index of the selected option.
+//! `SynthEngine::set_parameter` clamps values to the parameter's range.
+
+use crate::engine::Oscillator;
+use crate::tempo::Division;
+use crate::waveform::{LfoMode, LfoWaveform, Waveform};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParamId {
+    /// Pitch of the note, played by both oscillators and the wavetable
+    /// oscillator.
+    Frequency,
+    Octaves(Oscillator),
+    Semitones(Oscillator),
+    Cents(Oscillator),
+    /// Output level. The LFO's level is its depth, in octaves of frequency
+    /// modulation.
+    Level(Oscillator),
+    Osc1Waveform,
+    Osc1PulseWidth,
+    Osc2Waveform,
+    Osc2PulseWidth,
+    /// Hard sync of osc2 to osc1.
+    HardSync,
+    /// Morph position in the loaded wavetable.
+    WavetablePosition,
+    /// Rate of the LFO while it isn't tempo-synced.
+    LfoFrequency,
+    /// The note length of one LFO cycle, or off to run at `LfoFrequency`.
+    LfoTempoSync,
+    LfoWaveform,
+    LfoPulseWidth,
+    LfoMode,
+    /// Time the LFO stays silent after each note.
+    LfoDelay,
+    /// Time the LFO takes to fade in after its delay.
+    LfoFadeIn,
+}
+
+impl ParamId {
+    pub fn info(self) -> &'static ParamInfo {
+        PARAMS
+            .iter()
+            .find(|info| info.id == self)
+            .expect("every parameter is listed in PARAMS")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Unit {
+    Hz,
+    Octaves,
+    Semitones,
+    Cents,
+    /// A linear gain, shown in dB.
+    Gain,
+    /// A fraction in `[0, 1]`, shown as a percentage.
+    Fraction,
+    Seconds,
+    /// The index of one of these options.
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Debug)]
+pub struct ParamInfo {
+    pub id: ParamId,
+    /// Short identifier that stays the same between versions, for saving
+    /// patches and host automation.
+    pub key: &'static str,
+    pub name: &'static str,
+    pub unit: Unit,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+impl ParamInfo {
+    /// The nearest valid value: inside the range, and a whole option index for
+    /// choices. NaN falls back to the default.
+    pub fn clamp(&self, value: f64) -> f64 {
+        if value.is_nan() {
+            return self.default;
+        }
+
+        let value = value.clamp(self.min, self.max);
+        match self.unit {
+            Unit::Choice(_) => value.round(),
+            _ => value,
+        }
+    }
+
+    /// Format `value` for display, with its unit.
+    pub fn display(&self, value: f64) -> String {
+        let value = self.clamp(value);
+        match self.unit {
+            Unit::Hz if value >= 1000.0 => format!("{:.2} kHz", value / 1000.0),
+            Unit::Hz if value < 10.0 => format!("{:.2} Hz", value),
+            Unit::Hz => format!("{:.1} Hz", value),
+            Unit::Octaves => format!("{:+.2} oct", value),
+            Unit::Semitones => format!("{:+.2} st", value),
+            Unit::Cents => format!("{:+.1} ct", value),
+            Unit::Gain if value <= 0.0 => "-inf dB".to_string(),
+            Unit::Gain => format!("{:.1} dB", 20.0 * value.log10()),
+            Unit::Fraction => format!("{:.0} %", 100.0 * value),
+            Unit::Seconds if value < 1.0 => format!("{:.0} ms", 1000.0 * value),
+            Unit::Seconds => format!("{:.2} s", value),
+            Unit::Choice(options) => options[value as usize].to_string(),
+        }
+    }
+}
+
+/// Just below MIDI note 0, at 8.18 Hz, so every note plays at its pitch.
+const MIN_FREQUENCY: f64 = 8.0;
+const MAX_FREQUENCY: f64 = 20000.0;
+const MIN_LFO_FREQUENCY: f64 = 0.01;
+const MAX_LFO_FREQUENCY: f64 = 100.0;
+const MAX_OCTAVES: f64 = 4.0;
+const MAX_LFO_DEPTH: f64 = 4.0;
+const MAX_LFO_DELAY: f64 = 10.0;
+
+const WAVEFORM_NAMES: &[&str] = &["Sine", "Saw", "Square", "Triangle"];
+const LFO_WAVEFORM_NAMES: &[&str] = &[
+    "Sine",
+    "Triangle",
+    "Ramp up",
+    "Ramp down",
+    "Square",
+    "Sample & hold",
+    "Smooth random",
+];
+const LFO_MODE_NAMES: &[&str] = &["Free running", "Retrigger", "One shot"];
+const SWITCH_NAMES: &[&str] = &["Off", "On"];
+const TEMPO_SYNC_NAMES: &[&str] = &[
+    "Off",
+    "4 bars",
+    "2 bars",
+    "1 bar",
+    "1/2",
+    "1/2 dotted",
+    "1/2 triplet",
+    "1/4",
+    "1/4 dotted",
+    "1/4 triplet",
+    "1/8",
+    "1/8 dotted",
+    "1/8 triplet",
+    "1/16",
+    "1/16 dotted",
+    "1/16 triplet",
+    "1/32",
+];
+
+const fn param(
+    id: ParamId,
+    key: &'static str,
+    name: &'static str,
+    unit: Unit,
+    min: f64,
+    max: f64,
+    default: f64,
+) -> ParamInfo {
+    ParamInfo {
+        id,
+        key,
+        name,
+        unit,
+        min,
+        max,
+        default,
+    }
+}
+
+const fn choice(
+    id: ParamId,
+    key: &'static str,
+    name: &'static str,
+    options: &'static [&'static str],
+) -> ParamInfo {
+    param(
+        id,
+        key,
+        name,
+        Unit::Choice(options),
+        0.0,
+        (options.len() - 1) as f64,
+        0.0,
+    )
+}
+
+const fn fraction(id: ParamId, key: &'static str, name: &'static str, default: f64) -> ParamInfo {
+    param(id, key, name, Unit::Fraction, 0.0, 1.0, default)
+}
+
+const fn seconds(id: ParamId, key: &'static str, name: &'static str) -> ParamInfo {
+    param(id, key, name, Unit::Seconds, 0.0, MAX_LFO_DELAY, 0.0)
+}
+
+const fn octaves(oscillator: Oscillator, key: &'static str, name: &'static str) -> ParamInfo {
+    let id = ParamId::Octaves(oscillator);
+    param(id, key, name, Unit::Octaves, -MAX_OCTAVES, MAX_OCTAVES, 0.0)
+}
+
+const fn semitones(oscillator: Oscillator, key: &'static str, name: &'static str) -> ParamInfo {
+    let id = ParamId::Semitones(oscillator);
+    param(id, key, name, Unit::Semitones, -12.0, 12.0, 0.0)
+}
+
+const fn cents(
+    oscillator: Oscillator,
+    key: &'static str,
+    name: &'static str,
+    default: f64,
+) -> ParamInfo {
+    let id = ParamId::Cents(oscillator);
+    param(id, key, name, Unit::Cents, -100.0, 100.0, default)
+}
+
+const fn level(oscillator: Oscillator, key: &'static str, name: &'static str) -> ParamInfo {
+    param(
+        ParamId::Level(oscillator),
+        key,
+        name,
+        Unit::Gain,
+        0.0,
+        1.0,
+        1.0,
+    )
+}
+
+/// The LFO's level, which is a depth in octaves rather than a gain.
+const fn lfo_depth(key: &'static str, name: &'static str) -> ParamInfo {
+    let id = ParamId::Level(Oscillator::Lfo);
+    param(id, key, name, Unit::Octaves, 0.0, MAX_LFO_DEPTH, 1.0)
+}
+
+/// Every parameter, in the order a UI would show them. The defaults are the
+/// patch every engine starts with.
+pub static PARAMS: &[ParamInfo] = &[
+    param(
+        ParamId::Frequency,
+        "frequency",
+        "Frequency",
+        Unit::Hz,
+        MIN_FREQUENCY,
+        MAX_FREQUENCY,
+        440.0,
+    ),
+    choice(
+        ParamId::Osc1Waveform,
+        "osc1_waveform",
+        "Osc 1 waveform",
+        WAVEFORM_NAMES,
+    ),
+    fraction(
+        ParamId::Osc1PulseWidth,
+        "osc1_pulse_width",
+        "Osc 1 pulse width",
+        0.5,
+    ),
+    octaves(Oscillator::Osc1, "osc1_octaves", "Osc 1 octave"),
+    semitones(Oscillator::Osc1, "osc1_semitones", "Osc 1 semitone"),
+    cents(Oscillator::Osc1, "osc1_cents", "Osc 1 fine tune", 0.0),
+    level(Oscillator::Osc1, "osc1_level", "Osc 1 level"),
+    choice(
+        ParamId::Osc2Waveform,
+        "osc2_waveform",
+        "Osc 2 waveform",
+        WAVEFORM_NAMES,
+    ),
+    fraction(
+        ParamId::Osc2PulseWidth,
+        "osc2_pulse_width",
+        "Osc 2 pulse width",
+        0.5,
+    ),
+    octaves(Oscillator::Osc2, "osc2_octaves", "Osc 2 octave"),
+    semitones(Oscillator::Osc2, "osc2_semitones", "Osc 2 semitone"),
+    cents(Oscillator::Osc2, "osc2_cents", "Osc 2 fine tune", 2.5),
+    level(Oscillator::Osc2, "osc2_level", "Osc 2 level"),
+    choice(ParamId::HardSync, "hard_sync", "Hard sync", SWITCH_NAMES),
+    fraction(
+        ParamId::WavetablePosition,
+        "wavetable_position",
+        "Wavetable position",
+        0.0,
+    ),
+    octaves(
+        Oscillator::Wavetable,
+        "wavetable_octaves",
+        "Wavetable octave",
+    ),
+    semitones(
+        Oscillator::Wavetable,
+        "wavetable_semitones",
+        "Wavetable semitone",
+    ),
+    cents(
+        Oscillator::Wavetable,
+        "wavetable_cents",
+        "Wavetable fine tune",
+        0.0,
+    ),
+    level(Oscillator::Wavetable, "wavetable_level", "Wavetable level"),
+    param(
+        ParamId::LfoFrequency,
+        "lfo_frequency",
+        "LFO frequency",
+        Unit::Hz,
+        MIN_LFO_FREQUENCY,
+        MAX_LFO_FREQUENCY,
+        0.5,
+    ),
+    choice(
+        ParamId::LfoTempoSync,
+        "lfo_tempo_sync",
+        "LFO tempo sync",
+        TEMPO_SYNC_NAMES,
+    ),
+    choice(
+        ParamId::LfoWaveform,
+        "lfo_waveform",
+        "LFO waveform",
+        LFO_WAVEFORM_NAMES,
+    ),
+    fraction(
+        ParamId::LfoPulseWidth,
+        "lfo_pulse_width",
+        "LFO pulse width",
+        0.5,
+    ),
+    choice(ParamId::LfoMode, "lfo_mode", "LFO mode", LFO_MODE_NAMES),
+    seconds(ParamId::LfoDelay, "lfo_delay", "LFO delay"),
+    seconds(ParamId::LfoFadeIn, "lfo_fade_in", "LFO fade-in"),
+    octaves(Oscillator::Lfo, "lfo_octaves", "LFO octave"),
+    semitones(Oscillator::Lfo, "lfo_semitones", "LFO semitone"),
+    cents(Oscillator::Lfo, "lfo_cents", "LFO fine tune", 0.0),
+    lfo_depth("lfo_level", "LFO depth"),
+];
+
+/// An enum set through a choice parameter, whose value is the index in
+/// `OPTIONS`. The options are in the same order as the names in `PARAMS`.
+pub(crate) trait Choice: Copy + PartialEq + 'static {
+    const OPTIONS: &'static [Self];
+
+    fn from_value(value: f64) -> Self {
+        Self::OPTIONS[(value.max(0.0) as usize).min(Self::OPTIONS.len() - 1)]
+    }
+
+    fn value(self) -> f64 {
+        Self::OPTIONS
+            .iter()
+            .position(|&option| option == self)
+            .unwrap_or(0) as f64
+    }
+}
+
+impl Choice for Waveform {
+    const OPTIONS: &'static [Waveform] = &[
+        Waveform::Sine,
+        Waveform::Saw,
+        Waveform::Square,
+        Waveform::Triangle,
+    ];
+}
+
+impl Choice for LfoWaveform {
+    const OPTIONS: &'static [LfoWaveform] = &[
+        LfoWaveform::Sine,
+        LfoWaveform::Triangle,
+        LfoWaveform::RampUp,
+        LfoWaveform::RampDown,
+        LfoWaveform::Square,
+        LfoWaveform::SampleAndHold,
+        LfoWaveform::SmoothRandom,
+    ];
+}
+
+impl Choice for LfoMode {
+    const OPTIONS: &'static [LfoMode] =
+        &[LfoMode::FreeRunning, LfoMode::Retrigger, LfoMode::OneShot];
+}
+
+impl Choice for bool {
+    const OPTIONS: &'static [bool] = &[false, true];
+}
+
+impl Choice for Option<Division> {
+    const OPTIONS: &'static [Option<Division>] = &[
+        None,
+        Some(Division::bars(4)),
+        Some(Division::bars(2)),
+        Some(Division::bars(1)),
+        Some(Division::new(1, 2)),
+        Some(Division::new(1, 2).dotted()),
+        Some(Division::new(1, 2).triplet()),
+        Some(Division::new(1, 4)),
+        Some(Division::new(1, 4).dotted()),
+        Some(Division::new(1, 4).triplet()),
+        Some(Division::new(1, 8)),
+        Some(Division::new(1, 8).dotted()),
+        Some(Division::new(1, 8).triplet()),
+        Some(Division::new(1, 16)),
+        Some(Division::new(1, 16).dotted()),
+        Some(Division::new(1, 16).triplet()),
+        Some(Division::new(1, 32)),
+    ];
+
+    /// A division that isn't listed, set through `set_lfo_tempo_sync`, gives
+    /// the listed one closest in length.
+    fn value(self) -> f64 {
+        let beats = match self {
+            Some(division) => division.beats(),
+            None => return 0.0,
+        };
+        let distance = |option: &Option<Division>| match option {
+            Some(division) => (beats / division.beats()).ln().abs(),
+            None => f64::INFINITY,
+        };
+
+        (0..Self::OPTIONS.len())
+            .min_by(|&a, &b| distance(&Self::OPTIONS[a]).total_cmp(&distance(&Self::OPTIONS[b])))
+            .unwrap_or(0) as f64
+    }
+}
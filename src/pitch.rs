@@ -0,0 +1,124 @@
+//! Typed pitch units.
+//!
+//! Oscillator pitch is computed in octaves relative to the input frequency,
+//! since that's what `exp2` takes. Semitones and cents convert into octaves,
+//! and absolute pitch is either a frequency in Hz or a (fractional) MIDI note.
+
+use std::ops::{Add, Neg, Sub};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Octaves(pub f64);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Semitones(pub f64);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Cents(pub f64);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Hz(pub f64);
+
+/// MIDI note number, where 69 is A4 (440 Hz). Fractional notes are allowed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct MidiNote(pub f64);
+
+const A4_NOTE: f64 = 69.0;
+const A4_FREQUENCY: f64 = 440.0;
+
+impl Octaves {
+    /// The frequency ratio of this interval, e.g. 2.0 for one octave.
+    pub fn ratio(self) -> f64 {
+        self.0.exp2()
+    }
+
+    /// The interval between two frequencies.
+    pub fn between(from: Hz, to: Hz) -> Octaves {
+        Octaves((to.0 / from.0).log2())
+    }
+}
+
+impl Hz {
+    pub fn transpose(self, interval: Octaves) -> Hz {
+        Hz(self.0 * interval.ratio())
+    }
+}
+
+impl From<Semitones> for Octaves {
+    fn from(semitones: Semitones) -> Octaves {
+        Octaves(semitones.0 / 12.0)
+    }
+}
+
+impl From<Cents> for Octaves {
+    fn from(cents: Cents) -> Octaves {
+        Octaves(cents.0 / 1200.0)
+    }
+}
+
+impl From<Octaves> for Semitones {
+    fn from(octaves: Octaves) -> Semitones {
+        Semitones(octaves.0 * 12.0)
+    }
+}
+
+impl From<Cents> for Semitones {
+    fn from(cents: Cents) -> Semitones {
+        Semitones(cents.0 / 100.0)
+    }
+}
+
+impl From<Octaves> for Cents {
+    fn from(octaves: Octaves) -> Cents {
+        Cents(octaves.0 * 1200.0)
+    }
+}
+
+impl From<Semitones> for Cents {
+    fn from(semitones: Semitones) -> Cents {
+        Cents(semitones.0 * 100.0)
+    }
+}
+
+impl From<MidiNote> for Hz {
+    fn from(note: MidiNote) -> Hz {
+        Hz(A4_FREQUENCY).transpose(Semitones(note.0 - A4_NOTE).into())
+    }
+}
+
+impl From<Hz> for MidiNote {
+    fn from(frequency: Hz) -> MidiNote {
+        MidiNote(A4_NOTE + Semitones::from(Octaves::between(Hz(A4_FREQUENCY), frequency)).0)
+    }
+}
+
+macro_rules! impl_interval_ops {
+    ($t:ident) => {
+        impl Add for $t {
+            type Output = $t;
+
+            fn add(self, rhs: $t) -> $t {
+                $t(self.0 + rhs.0)
+            }
+        }
+
+        impl Sub for $t {
+            type Output = $t;
+
+            fn sub(self, rhs: $t) -> $t {
+                $t(self.0 - rhs.0)
+            }
+        }
+
+        impl Neg for $t {
+            type Output = $t;
+
+            fn neg(self) -> $t {
+                $t(-self.0)
+            }
+        }
+    };
+}
+
+impl_interval_ops!(Octaves);
+impl_interval_ops!(Semitones);
+impl_interval_ops!(Cents);
@@ -0,0 +1,319 @@
+//! Polyphony on top of the single-voice engines.
+//!
+//! A `PolySynth` owns a fixed pool of voices, each a complete synth with its
+//! own oscillators, LFO and envelopes, and sums them into the output. Notes
+//! are allocated to free voices first, and steal a sounding voice once the
+//! pool is full. The pool and the mixing buffer are allocated up front, so
+//! neither note events nor `render` allocate.
+//!
+//! `VoiceAllocator` only keeps track of which voice plays which note, so
+//! engines that lay out their voices differently, like `voice_per_lane`, can
+//! share it.
+
+use std::cmp::Ordering;
+
+use crate::engine::SynthEngine;
+use crate::envelope::Stage;
+use crate::params::ParamId;
+use crate::pitch::{Hz, MidiNote};
+use crate::sample::Sample;
+
+/// Which sounding voice a note takes over once every voice is in use.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StealPolicy {
+    /// The voice whose note started first.
+    #[default]
+    Oldest,
+    /// The voice with the lowest amp envelope level, or the oldest of the
+    /// quietest ones.
+    Quietest,
+    /// The voice already playing the same note, which is retriggered. Other
+    /// notes steal the oldest voice.
+    SameNote,
+}
+
+/// Frames mixed at a time. Longer buffers are rendered in chunks.
+const MIX_BUFFER_SIZE: usize = 512;
+
+#[derive(Copy, Clone, Debug)]
+struct VoiceSlot {
+    note: MidiNote,
+    /// Whether the note is still held, as opposed to releasing.
+    held: bool,
+    /// Whether the voice is sounding, either held or releasing.
+    active: bool,
+    /// Order of the voice's note-on, for finding the oldest voice.
+    started: u64,
+}
+
+/// Which voice plays which note, for engines that keep the voices' state
+/// themselves.
+pub(crate) struct VoiceAllocator {
+    voices: Vec<VoiceSlot>,
+    steal_policy: StealPolicy,
+    notes_started: u64,
+}
+
+impl VoiceAllocator {
+    pub fn new(voice_count: usize) -> VoiceAllocator {
+        let free = VoiceSlot {
+            note: MidiNote(0.0),
+            held: false,
+            active: false,
+            started: 0,
+        };
+
+        VoiceAllocator {
+            voices: vec![free; voice_count],
+            steal_policy: StealPolicy::default(),
+            notes_started: 0,
+        }
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.steal_policy = policy;
+    }
+
+    pub fn is_active(&self, index: usize) -> bool {
+        self.voices[index].active
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.iter().filter(|voice| voice.active).count()
+    }
+
+    pub fn sounding_notes(&self) -> impl Iterator<Item = MidiNote> + '_ {
+        self.voices
+            .iter()
+            .filter(|voice| voice.active)
+            .map(|voice| voice.note)
+    }
+
+    /// Assign `note` to a voice and mark it as held. `level` gives a voice's
+    /// amp envelope level, for stealing the quietest one. Returns the voice,
+    /// and whether it was free, in which case it should start from its
+    /// initial state.
+    pub fn note_on<L: Fn(usize) -> f64>(
+        &mut self,
+        note: MidiNote,
+        level: L,
+    ) -> Option<(usize, bool)> {
+        let index = self.find_voice(note, level)?;
+        let voice = &mut self.voices[index];
+        let free = !voice.active;
+
+        voice.note = note;
+        voice.held = true;
+        voice.active = true;
+        voice.started = self.notes_started;
+        self.notes_started += 1;
+
+        Some((index, free))
+    }
+
+    /// Mark every voice holding `note`, or every held voice with `None`, as
+    /// released, and call `release` with its index.
+    pub fn note_off<R: FnMut(usize)>(&mut self, note: Option<MidiNote>, mut release: R) {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            if voice.held && note.is_none_or(|note| voice.note == note) {
+                voice.held = false;
+                release(index);
+            }
+        }
+    }
+
+    /// Free the released voices that have gone silent. `releasing` says
+    /// whether a voice's amp envelope is still sounding; without one, voices
+    /// stop as soon as they're released.
+    pub fn update<R: Fn(usize) -> bool>(&mut self, releasing: R) {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            if voice.active && !voice.held {
+                voice.active = releasing(index);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.held = false;
+            voice.active = false;
+        }
+    }
+
+    fn find_voice<L: Fn(usize) -> f64>(&self, note: MidiNote, level: L) -> Option<usize> {
+        let active = || {
+            self.voices
+                .iter()
+                .enumerate()
+                .filter(|(_, voice)| voice.active)
+        };
+
+        if self.steal_policy == StealPolicy::SameNote {
+            if let Some((index, _)) = active().find(|(_, voice)| voice.note == note) {
+                return Some(index);
+            }
+        }
+
+        if let Some(index) = self.voices.iter().position(|voice| !voice.active) {
+            return Some(index);
+        }
+
+        let stolen = match self.steal_policy {
+            StealPolicy::Oldest | StealPolicy::SameNote => {
+                active().min_by_key(|(_, voice)| voice.started)
+            }
+            StealPolicy::Quietest => active().min_by(|(a_index, a), (b_index, b)| {
+                level(*a_index)
+                    .partial_cmp(&level(*b_index))
+                    .unwrap_or(Ordering::Equal)
+                    .then(a.started.cmp(&b.started))
+            }),
+        };
+        stolen.map(|(index, _)| index)
+    }
+}
+
+/// Whether a released voice's amp envelope is still sounding.
+fn releasing<S: Sample>(synth: &dyn SynthEngine<S>) -> bool {
+    matches!(
+        synth.amp_envelope(),
+        Some(envelope) if envelope.stage != Stage::Idle
+    )
+}
+
+pub struct PolySynth<S: Sample = f64> {
+    voices: Vec<Box<dyn SynthEngine<S>>>,
+    allocator: VoiceAllocator,
+    mix_buffer: Vec<S>,
+}
+
+impl<S: Sample> PolySynth<S> {
+    /// A pool of `voice_count` voices, each created with `create`, e.g. an
+    /// `EngineDescriptor`'s `create`, or `create_f32` to render in `f32`.
+    pub fn new(
+        create: fn(f64) -> Box<dyn SynthEngine<S>>,
+        sample_rate: f64,
+        voice_count: usize,
+    ) -> PolySynth<S> {
+        PolySynth {
+            voices: (0..voice_count).map(|_| create(sample_rate)).collect(),
+            allocator: VoiceAllocator::new(voice_count),
+            mix_buffer: vec![S::ZERO; MIX_BUFFER_SIZE],
+        }
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.allocator.voice_count()
+    }
+
+    /// The number of voices that are held or still releasing.
+    pub fn active_voices(&self) -> usize {
+        self.allocator.active_voices()
+    }
+
+    /// The notes of the sounding voices, in voice order.
+    pub fn sounding_notes(&self) -> impl Iterator<Item = MidiNote> + '_ {
+        self.allocator.sounding_notes()
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.allocator.set_steal_policy(policy);
+    }
+
+    /// Change a setting on every voice, e.g. `set_amp_envelope`.
+    pub fn for_each_voice<F: FnMut(&mut dyn SynthEngine<S>)>(&mut self, mut f: F) {
+        for synth in self.voices.iter_mut() {
+            f(&mut **synth);
+        }
+    }
+
+    /// Set a parameter on every voice. The frequency is set by each voice's
+    /// note instead.
+    pub fn set_parameter(&mut self, id: ParamId, value: f64) {
+        if id != ParamId::Frequency {
+            self.for_each_voice(|synth| synth.set_parameter(id, value));
+        }
+    }
+
+    /// Start a note on a free voice, or steal one if they're all in use.
+    pub fn note_on(&mut self, note: MidiNote) {
+        let voices = &self.voices;
+        let level = |index: usize| {
+            voices[index]
+                .amp_envelope()
+                .map_or(1.0, |envelope| envelope.level)
+        };
+        let (index, free) = match self.allocator.note_on(note, level) {
+            Some(voice) => voice,
+            None => return,
+        };
+        let synth = &mut self.voices[index];
+
+        // A free voice starts from scratch. A stolen one carries on from
+        // where it is, with the envelope attacking from its current level,
+        // so it doesn't click.
+        if free {
+            synth.reset();
+        }
+
+        synth.set_parameter(ParamId::Frequency, Hz::from(note).0);
+        synth.note_on();
+    }
+
+    /// Release every voice holding `note`.
+    pub fn note_off(&mut self, note: MidiNote) {
+        self.release(Some(note));
+    }
+
+    /// Release every held note.
+    pub fn all_notes_off(&mut self) {
+        self.release(None);
+    }
+
+    fn release(&mut self, note: Option<MidiNote>) {
+        let voices = &mut self.voices;
+        self.allocator
+            .note_off(note, |index| voices[index].note_off());
+
+        let voices = &self.voices;
+        self.allocator.update(|index| releasing(&*voices[index]));
+    }
+
+    /// Render the sum of every sounding voice into `buffer`.
+    pub fn render(&mut self, buffer: &mut [S]) {
+        for output in buffer.iter_mut() {
+            *output = S::ZERO;
+        }
+
+        for (index, synth) in self.voices.iter_mut().enumerate() {
+            if !self.allocator.is_active(index) {
+                continue;
+            }
+
+            for chunk in buffer.chunks_mut(MIX_BUFFER_SIZE) {
+                let mix = &mut self.mix_buffer[..chunk.len()];
+                synth.render(mix);
+
+                for (output, sample) in chunk.iter_mut().zip(mix.iter()) {
+                    *output += *sample;
+                }
+            }
+        }
+
+        let voices = &self.voices;
+        self.allocator.update(|index| releasing(&*voices[index]));
+    }
+
+    /// Silence every voice and restore their initial state, keeping the
+    /// settings.
+    pub fn reset(&mut self) {
+        for synth in self.voices.iter_mut() {
+            synth.reset();
+        }
+        self.allocator.reset();
+    }
+}
@@ -0,0 +1,285 @@
+//! The sample type the engines render in.
+//!
+//! Settings and control values, like frequencies in Hz, pitch offsets,
+//! envelope levels and smoothed parameters, are always `f64`. Everything that
+//! runs on the signal itself, from the oscillators' phases to the filter and
+//! the output buffers, is generic over `Sample`, so the same engine renders in
+//! `f64`, or in `f32` like most hosts and plugin APIs expect. The `f64`
+//! engines do the same operations in the same order as before, so they
+//! produce exactly the same output.
+
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use crate::fastmath;
+use crate::simd::{self, SimdLevel};
+
+pub trait Sample:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const INFINITY: Self;
+
+    /// The range of exponents `e` for which `2^e` is a normal number.
+    const MIN_EXPONENT: i32;
+    const MAX_EXPONENT: i32;
+
+    /// The nearest value of this type.
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    /// Round towards zero, saturating at the ends of the `i32` range like
+    /// `as` does.
+    fn to_i32(self) -> i32;
+    /// Round towards zero, saturating like `as` does.
+    fn to_usize(self) -> usize;
+
+    /// `2^exponent`, built straight from the exponent bits. Only valid for
+    /// exponents from `MIN_EXPONENT` to `MAX_EXPONENT`.
+    fn pow2i(exponent: i32) -> Self;
+
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn log2(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+
+    /// `fastmath::exp2` of every value, in place. Used by `simd::exp2`, and
+    /// overridden with the packed kernels of the type.
+    fn exp2_slice(values: &mut [Self]) {
+        for value in values.iter_mut() {
+            *value = fastmath::exp2(*value);
+        }
+    }
+
+    /// `fastmath::wrap01` of every value, in place. Used by `simd::wrap01`.
+    fn wrap01_slice(values: &mut [Self]) {
+        for value in values.iter_mut() {
+            *value = fastmath::wrap01(*value);
+        }
+    }
+
+    /// `fastmath::parabolic_sine` of every value, in place. Used by
+    /// `simd::parabolic_sine`.
+    fn parabolic_sine_slice(values: &mut [Self]) {
+        for value in values.iter_mut() {
+            *value = fastmath::parabolic_sine(*value);
+        }
+    }
+}
+
+impl Sample for f64 {
+    const ZERO: f64 = 0.0;
+    const ONE: f64 = 1.0;
+    const INFINITY: f64 = f64::INFINITY;
+
+    const MIN_EXPONENT: i32 = -1022;
+    const MAX_EXPONENT: i32 = 1023;
+
+    #[inline(always)]
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline(always)]
+    fn to_i32(self) -> i32 {
+        self as i32
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+
+    #[inline(always)]
+    fn pow2i(exponent: i32) -> f64 {
+        const BIAS: i32 = 1023;
+        // The biased exponent goes in the upper word, the mantissa is zero.
+        let upper = ((exponent + BIAS) << 20) as u32;
+        f64::from_bits((upper as u64) << 32)
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f64 {
+        self.abs()
+    }
+
+    #[inline(always)]
+    fn floor(self) -> f64 {
+        self.floor()
+    }
+
+    #[inline(always)]
+    fn rem_euclid(self, rhs: f64) -> f64 {
+        self.rem_euclid(rhs)
+    }
+
+    #[inline(always)]
+    fn min(self, other: f64) -> f64 {
+        self.min(other)
+    }
+
+    #[inline(always)]
+    fn max(self, other: f64) -> f64 {
+        self.max(other)
+    }
+
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> f64 {
+        self.clamp(min, max)
+    }
+
+    #[inline(always)]
+    fn log2(self) -> f64 {
+        self.log2()
+    }
+
+    #[inline(always)]
+    fn powf(self, exponent: f64) -> f64 {
+        self.powf(exponent)
+    }
+
+    #[inline(always)]
+    fn tan(self) -> f64 {
+        self.tan()
+    }
+
+    #[inline(always)]
+    fn tanh(self) -> f64 {
+        self.tanh()
+    }
+
+    fn exp2_slice(values: &mut [f64]) {
+        simd::exp2_with(SimdLevel::detect(), values);
+    }
+
+    fn wrap01_slice(values: &mut [f64]) {
+        simd::wrap01_with(SimdLevel::detect(), values);
+    }
+
+    fn parabolic_sine_slice(values: &mut [f64]) {
+        simd::parabolic_sine_with(SimdLevel::detect(), values);
+    }
+}
+
+impl Sample for f32 {
+    const ZERO: f32 = 0.0;
+    const ONE: f32 = 1.0;
+    const INFINITY: f32 = f32::INFINITY;
+
+    const MIN_EXPONENT: i32 = -126;
+    const MAX_EXPONENT: i32 = 127;
+
+    #[inline(always)]
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline(always)]
+    fn to_i32(self) -> i32 {
+        self as i32
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+
+    #[inline(always)]
+    fn pow2i(exponent: i32) -> f32 {
+        const BIAS: i32 = 127;
+        f32::from_bits(((exponent + BIAS) << 23) as u32)
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f32 {
+        self.abs()
+    }
+
+    #[inline(always)]
+    fn floor(self) -> f32 {
+        self.floor()
+    }
+
+    #[inline(always)]
+    fn rem_euclid(self, rhs: f32) -> f32 {
+        self.rem_euclid(rhs)
+    }
+
+    #[inline(always)]
+    fn min(self, other: f32) -> f32 {
+        self.min(other)
+    }
+
+    #[inline(always)]
+    fn max(self, other: f32) -> f32 {
+        self.max(other)
+    }
+
+    #[inline(always)]
+    fn clamp(self, min: f32, max: f32) -> f32 {
+        self.clamp(min, max)
+    }
+
+    #[inline(always)]
+    fn log2(self) -> f32 {
+        self.log2()
+    }
+
+    #[inline(always)]
+    fn powf(self, exponent: f32) -> f32 {
+        self.powf(exponent)
+    }
+
+    #[inline(always)]
+    fn tan(self) -> f32 {
+        self.tan()
+    }
+
+    #[inline(always)]
+    fn tanh(self) -> f32 {
+        self.tanh()
+    }
+
+    fn exp2_slice(values: &mut [f32]) {
+        simd::exp2_f32_with(SimdLevel::detect(), values);
+    }
+
+    fn wrap01_slice(values: &mut [f32]) {
+        simd::wrap01_f32_with(SimdLevel::detect(), values);
+    }
+
+    fn parabolic_sine_slice(values: &mut [f32]) {
+        simd::parabolic_sine_f32_with(SimdLevel::detect(), values);
+    }
+}
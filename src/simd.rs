@@ -0,0 +1,637 @@
+//! Explicit SIMD versions of the `fastmath` functions, over whole slices.
+//!
+//! The scalar functions rely on the compiler vectorising the loops that call
+//! them, which the branch in `wrap01` or the bit twiddling in `exp2` easily
+//! defeats. These kernels run the same operations in the same order on packed
+//! `f64x2` and `f32x4` (SSE2) or `f64x4` and `f32x8` (AVX2) vectors, so they
+//! return exactly what the scalar functions would. The best level the CPU
+//! supports is picked at runtime, with the scalar functions as the fallback.
+//!
+//! The AVX2 kernels leave what's left after their chunks to the SSE2 ones,
+//! so short slices, like the lanes of a few voices, still get packed.
+//!
+//! Packed conversions to integers only cover `i32`, so a vector with an input
+//! they can't represent is computed with the scalar function instead. So is
+//! an `exp2` vector with an input whose result isn't a normal number.
+
+use crate::fastmath;
+use crate::sample::Sample;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    Scalar,
+    /// Two `f64` or four `f32` lanes. Every x86_64 CPU has SSE2.
+    Sse2,
+    /// Four `f64` or eight `f32` lanes.
+    Avx2,
+}
+
+impl SimdLevel {
+    /// The best level this CPU supports.
+    pub fn detect() -> SimdLevel {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                SimdLevel::Avx2
+            } else {
+                SimdLevel::Sse2
+            }
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        SimdLevel::Scalar
+    }
+
+    /// Every level this CPU can run, lowest first.
+    pub fn available() -> impl Iterator<Item = SimdLevel> {
+        let best = SimdLevel::detect();
+        [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx2]
+            .iter()
+            .copied()
+            .filter(move |&level| level <= best)
+    }
+
+    /// This level, or the best one the CPU supports if it's lower.
+    fn supported(self) -> SimdLevel {
+        self.min(SimdLevel::detect())
+    }
+}
+
+/// `fastmath::exp2` of every value, in place, with the packed kernels of the
+/// sample type.
+pub fn exp2<S: Sample>(values: &mut [S]) {
+    S::exp2_slice(values);
+}
+
+/// `fastmath::wrap01` of every value, in place.
+pub fn wrap01<S: Sample>(values: &mut [S]) {
+    S::wrap01_slice(values);
+}
+
+/// `fastmath::parabolic_sine` of every value, in place.
+pub fn parabolic_sine<S: Sample>(values: &mut [S]) {
+    S::parabolic_sine_slice(values);
+}
+
+/// Like `exp2`, with the kernels for `level`, e.g. to compare the levels.
+/// Levels the CPU doesn't support fall back to the best one it does.
+pub fn exp2_with(level: SimdLevel, values: &mut [f64]) {
+    // There, the scalar function is the standard library's, which the kernels
+    // don't reproduce.
+    if cfg!(target_os = "macos") {
+        return scalar(values, fastmath::exp2);
+    }
+
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::exp2_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::exp2_sse2(values) },
+        _ => scalar(values, fastmath::exp2),
+    }
+}
+
+pub fn wrap01_with(level: SimdLevel, values: &mut [f64]) {
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::wrap01_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::wrap01_sse2(values) },
+        _ => scalar(values, fastmath::wrap01),
+    }
+}
+
+pub fn parabolic_sine_with(level: SimdLevel, values: &mut [f64]) {
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::parabolic_sine_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::parabolic_sine_sse2(values) },
+        _ => scalar(values, fastmath::parabolic_sine),
+    }
+}
+
+/// Like `exp2_with`, for `f32`.
+pub fn exp2_f32_with(level: SimdLevel, values: &mut [f32]) {
+    if cfg!(target_os = "macos") {
+        return scalar(values, fastmath::exp2);
+    }
+
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::exp2_f32_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::exp2_f32_sse2(values) },
+        _ => scalar(values, fastmath::exp2),
+    }
+}
+
+pub fn wrap01_f32_with(level: SimdLevel, values: &mut [f32]) {
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::wrap01_f32_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::wrap01_f32_sse2(values) },
+        _ => scalar(values, fastmath::wrap01),
+    }
+}
+
+pub fn parabolic_sine_f32_with(level: SimdLevel, values: &mut [f32]) {
+    match level.supported() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::parabolic_sine_f32_avx2(values) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::parabolic_sine_f32_sse2(values) },
+        _ => scalar(values, fastmath::parabolic_sine),
+    }
+}
+
+fn scalar<S: Sample>(values: &mut [S], f: fn(S) -> S) {
+    for value in values.iter_mut() {
+        *value = f(*value);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[allow(clippy::excessive_precision)]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::scalar;
+    use crate::fastmath;
+
+    /// Inputs whose magnitude is below this round to integers that fit an
+    /// `i32`, with room to spare.
+    const INT_RANGE: f64 = 1073741824.0;
+
+    /// Inputs whose magnitude is below this have normal `exp2` results, which
+    /// the exponent bits can hold.
+    const EXP2_RANGE: f64 = 1022.0;
+    const EXP2_RANGE_F32: f32 = 126.0;
+
+    // The constants of `fastmath::exp2`.
+    const EXP2_P: [f64; 3] = [
+        2.30933477057345225087e-2,
+        2.02020656693165307700e1,
+        1.51390680115615096133e3,
+    ];
+    const EXP2_Q: [f64; 2] = [2.33184211722314911771e2, 4.36821166879210612817e3];
+    const DOUBLE_BIAS: i32 = 1023;
+    const FLOAT_BIAS: i32 = 127;
+
+    // The constants of `fastmath::parabolic_sine`.
+    const SINE_B: f64 = 4.0 / std::f64::consts::PI;
+    const SINE_C: f64 = -4.0 / (std::f64::consts::PI * std::f64::consts::PI);
+    const SINE_P: f64 = 0.225;
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn abs_f64x4(x: __m256d) -> __m256d {
+        _mm256_andnot_pd(_mm256_set1_pd(-0.0), x)
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn in_range_f64x4(x: __m256d, range: f64) -> bool {
+        let in_range = _mm256_cmp_pd(abs_f64x4(x), _mm256_set1_pd(range), _CMP_LT_OQ);
+        _mm256_movemask_pd(in_range) == 0b1111
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn exp2_avx2(values: &mut [f64]) {
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_pd(chunk.as_ptr());
+            if !in_range_f64x4(x, EXP2_RANGE) {
+                scalar(chunk, fastmath::exp2);
+                continue;
+            }
+
+            let ipart = _mm256_floor_pd(_mm256_add_pd(x, _mm256_set1_pd(0.5)));
+            let fpart = _mm256_sub_pd(x, ipart);
+
+            // The biased exponent goes in the upper half of each lane, the
+            // lower half stays zero.
+            let exponent = _mm_slli_epi32(
+                _mm_add_epi32(_mm256_cvtpd_epi32(ipart), _mm_set1_epi32(DOUBLE_BIAS)),
+                20,
+            );
+            let zero = _mm_setzero_si128();
+            let epart = _mm256_castsi256_pd(_mm256_set_m128i(
+                _mm_unpackhi_epi32(zero, exponent),
+                _mm_unpacklo_epi32(zero, exponent),
+            ));
+
+            let x = _mm256_mul_pd(fpart, fpart);
+
+            let mut px = _mm256_set1_pd(EXP2_P[0]);
+            px = _mm256_add_pd(_mm256_mul_pd(px, x), _mm256_set1_pd(EXP2_P[1]));
+            let mut qx = _mm256_add_pd(x, _mm256_set1_pd(EXP2_Q[0]));
+            px = _mm256_add_pd(_mm256_mul_pd(px, x), _mm256_set1_pd(EXP2_P[2]));
+            qx = _mm256_add_pd(_mm256_mul_pd(qx, x), _mm256_set1_pd(EXP2_Q[1]));
+
+            px = _mm256_mul_pd(px, fpart);
+
+            let ratio = _mm256_div_pd(px, _mm256_sub_pd(qx, px));
+            let x = _mm256_add_pd(
+                _mm256_set1_pd(1.0),
+                _mm256_mul_pd(_mm256_set1_pd(2.0), ratio),
+            );
+
+            _mm256_storeu_pd(chunk.as_mut_ptr(), _mm256_mul_pd(epart, x));
+        }
+
+        exp2_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn wrap01_avx2(values: &mut [f64]) {
+        let one = _mm256_set1_pd(1.0);
+        let sign = _mm256_set1_pd(-0.0);
+
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_pd(chunk.as_ptr());
+
+            // `x.rem_euclid(1.0)`: the remainder keeps the sign of `x`, even
+            // when it's zero, and negative ones wrap around.
+            let truncated = _mm256_round_pd(x, _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC);
+            let remainder = _mm256_or_pd(_mm256_sub_pd(x, truncated), _mm256_and_pd(x, sign));
+            let negative = _mm256_cmp_pd(remainder, _mm256_setzero_pd(), _CMP_LT_OQ);
+            let wrapped = _mm256_blendv_pd(remainder, _mm256_add_pd(remainder, one), negative);
+
+            let in_range = _mm256_and_pd(
+                _mm256_cmp_pd(x, _mm256_setzero_pd(), _CMP_GE_OQ),
+                _mm256_cmp_pd(x, one, _CMP_LE_OQ),
+            );
+
+            _mm256_storeu_pd(chunk.as_mut_ptr(), _mm256_blendv_pd(wrapped, x, in_range));
+        }
+
+        wrap01_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn parabolic_sine_avx2(values: &mut [f64]) {
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_pd(chunk.as_ptr());
+
+            let mut y = _mm256_add_pd(
+                _mm256_mul_pd(_mm256_set1_pd(SINE_B), x),
+                _mm256_mul_pd(_mm256_mul_pd(_mm256_set1_pd(SINE_C), x), abs_f64x4(x)),
+            );
+            y = _mm256_add_pd(
+                _mm256_mul_pd(
+                    _mm256_set1_pd(SINE_P),
+                    _mm256_sub_pd(_mm256_mul_pd(y, abs_f64x4(y)), y),
+                ),
+                y,
+            );
+
+            _mm256_storeu_pd(chunk.as_mut_ptr(), y);
+        }
+
+        parabolic_sine_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn abs_f64x2(x: __m128d) -> __m128d {
+        _mm_andnot_pd(_mm_set1_pd(-0.0), x)
+    }
+
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn in_range_f64x2(x: __m128d, range: f64) -> bool {
+        _mm_movemask_pd(_mm_cmplt_pd(abs_f64x2(x), _mm_set1_pd(range))) == 0b11
+    }
+
+    /// Round towards zero, for inputs in the `i32` range. SSE2 has no
+    /// rounding instruction.
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn trunc_f64x2(x: __m128d) -> __m128d {
+        _mm_cvtepi32_pd(_mm_cvttpd_epi32(x))
+    }
+
+    /// Select `b` where `mask` is set, and `a` elsewhere.
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn select_f64x2(mask: __m128d, a: __m128d, b: __m128d) -> __m128d {
+        _mm_or_pd(_mm_and_pd(mask, b), _mm_andnot_pd(mask, a))
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn exp2_sse2(values: &mut [f64]) {
+        let mut chunks = values.chunks_exact_mut(2);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_pd(chunk.as_ptr());
+            if !in_range_f64x2(x, EXP2_RANGE) {
+                scalar(chunk, fastmath::exp2);
+                continue;
+            }
+
+            // Floor is truncation, minus one where that rounded up.
+            let half_up = _mm_add_pd(x, _mm_set1_pd(0.5));
+            let truncated = trunc_f64x2(half_up);
+            let rounded_up = _mm_cmpgt_pd(truncated, half_up);
+            let ipart = _mm_sub_pd(truncated, _mm_and_pd(rounded_up, _mm_set1_pd(1.0)));
+            let fpart = _mm_sub_pd(x, ipart);
+
+            let exponent = _mm_slli_epi32(
+                _mm_add_epi32(_mm_cvttpd_epi32(ipart), _mm_set1_epi32(DOUBLE_BIAS)),
+                20,
+            );
+            let epart = _mm_castsi128_pd(_mm_unpacklo_epi32(_mm_setzero_si128(), exponent));
+
+            let x = _mm_mul_pd(fpart, fpart);
+
+            let mut px = _mm_set1_pd(EXP2_P[0]);
+            px = _mm_add_pd(_mm_mul_pd(px, x), _mm_set1_pd(EXP2_P[1]));
+            let mut qx = _mm_add_pd(x, _mm_set1_pd(EXP2_Q[0]));
+            px = _mm_add_pd(_mm_mul_pd(px, x), _mm_set1_pd(EXP2_P[2]));
+            qx = _mm_add_pd(_mm_mul_pd(qx, x), _mm_set1_pd(EXP2_Q[1]));
+
+            px = _mm_mul_pd(px, fpart);
+
+            let ratio = _mm_div_pd(px, _mm_sub_pd(qx, px));
+            let x = _mm_add_pd(_mm_set1_pd(1.0), _mm_mul_pd(_mm_set1_pd(2.0), ratio));
+
+            _mm_storeu_pd(chunk.as_mut_ptr(), _mm_mul_pd(epart, x));
+        }
+
+        scalar(chunks.into_remainder(), fastmath::exp2);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn wrap01_sse2(values: &mut [f64]) {
+        let one = _mm_set1_pd(1.0);
+        let sign = _mm_set1_pd(-0.0);
+
+        let mut chunks = values.chunks_exact_mut(2);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_pd(chunk.as_ptr());
+            if !in_range_f64x2(x, INT_RANGE) {
+                scalar(chunk, fastmath::wrap01);
+                continue;
+            }
+
+            let remainder = _mm_or_pd(_mm_sub_pd(x, trunc_f64x2(x)), _mm_and_pd(x, sign));
+            let negative = _mm_cmplt_pd(remainder, _mm_setzero_pd());
+            let wrapped = select_f64x2(negative, remainder, _mm_add_pd(remainder, one));
+
+            let in_range = _mm_and_pd(_mm_cmpge_pd(x, _mm_setzero_pd()), _mm_cmple_pd(x, one));
+
+            _mm_storeu_pd(chunk.as_mut_ptr(), select_f64x2(in_range, wrapped, x));
+        }
+
+        scalar(chunks.into_remainder(), fastmath::wrap01);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn parabolic_sine_sse2(values: &mut [f64]) {
+        let mut chunks = values.chunks_exact_mut(2);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_pd(chunk.as_ptr());
+
+            let mut y = _mm_add_pd(
+                _mm_mul_pd(_mm_set1_pd(SINE_B), x),
+                _mm_mul_pd(_mm_mul_pd(_mm_set1_pd(SINE_C), x), abs_f64x2(x)),
+            );
+            y = _mm_add_pd(
+                _mm_mul_pd(
+                    _mm_set1_pd(SINE_P),
+                    _mm_sub_pd(_mm_mul_pd(y, abs_f64x2(y)), y),
+                ),
+                y,
+            );
+
+            _mm_storeu_pd(chunk.as_mut_ptr(), y);
+        }
+
+        scalar(chunks.into_remainder(), fastmath::parabolic_sine);
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn abs_f32x8(x: __m256) -> __m256 {
+        _mm256_andnot_ps(_mm256_set1_ps(-0.0), x)
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn in_range_f32x8(x: __m256, range: f32) -> bool {
+        let in_range = _mm256_cmp_ps(abs_f32x8(x), _mm256_set1_ps(range), _CMP_LT_OQ);
+        _mm256_movemask_ps(in_range) == 0b1111_1111
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn exp2_f32_avx2(values: &mut [f32]) {
+        let mut chunks = values.chunks_exact_mut(8);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_ps(chunk.as_ptr());
+            if !in_range_f32x8(x, EXP2_RANGE_F32) {
+                scalar(chunk, fastmath::exp2);
+                continue;
+            }
+
+            let ipart = _mm256_floor_ps(_mm256_add_ps(x, _mm256_set1_ps(0.5)));
+            let fpart = _mm256_sub_ps(x, ipart);
+
+            let exponent = _mm256_slli_epi32(
+                _mm256_add_epi32(_mm256_cvttps_epi32(ipart), _mm256_set1_epi32(FLOAT_BIAS)),
+                23,
+            );
+            let epart = _mm256_castsi256_ps(exponent);
+
+            let x = _mm256_mul_ps(fpart, fpart);
+
+            let mut px = _mm256_set1_ps(EXP2_P[0] as f32);
+            px = _mm256_add_ps(_mm256_mul_ps(px, x), _mm256_set1_ps(EXP2_P[1] as f32));
+            let mut qx = _mm256_add_ps(x, _mm256_set1_ps(EXP2_Q[0] as f32));
+            px = _mm256_add_ps(_mm256_mul_ps(px, x), _mm256_set1_ps(EXP2_P[2] as f32));
+            qx = _mm256_add_ps(_mm256_mul_ps(qx, x), _mm256_set1_ps(EXP2_Q[1] as f32));
+
+            px = _mm256_mul_ps(px, fpart);
+
+            let ratio = _mm256_div_ps(px, _mm256_sub_ps(qx, px));
+            let x = _mm256_add_ps(
+                _mm256_set1_ps(1.0),
+                _mm256_mul_ps(_mm256_set1_ps(2.0), ratio),
+            );
+
+            _mm256_storeu_ps(chunk.as_mut_ptr(), _mm256_mul_ps(epart, x));
+        }
+
+        exp2_f32_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn wrap01_f32_avx2(values: &mut [f32]) {
+        let one = _mm256_set1_ps(1.0);
+        let sign = _mm256_set1_ps(-0.0);
+
+        let mut chunks = values.chunks_exact_mut(8);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_ps(chunk.as_ptr());
+
+            let truncated = _mm256_round_ps(x, _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC);
+            let remainder = _mm256_or_ps(_mm256_sub_ps(x, truncated), _mm256_and_ps(x, sign));
+            let negative = _mm256_cmp_ps(remainder, _mm256_setzero_ps(), _CMP_LT_OQ);
+            let wrapped = _mm256_blendv_ps(remainder, _mm256_add_ps(remainder, one), negative);
+
+            let in_range = _mm256_and_ps(
+                _mm256_cmp_ps(x, _mm256_setzero_ps(), _CMP_GE_OQ),
+                _mm256_cmp_ps(x, one, _CMP_LE_OQ),
+            );
+
+            _mm256_storeu_ps(chunk.as_mut_ptr(), _mm256_blendv_ps(wrapped, x, in_range));
+        }
+
+        wrap01_f32_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn parabolic_sine_f32_avx2(values: &mut [f32]) {
+        let mut chunks = values.chunks_exact_mut(8);
+        for chunk in &mut chunks {
+            let x = _mm256_loadu_ps(chunk.as_ptr());
+
+            let mut y = _mm256_add_ps(
+                _mm256_mul_ps(_mm256_set1_ps(SINE_B as f32), x),
+                _mm256_mul_ps(
+                    _mm256_mul_ps(_mm256_set1_ps(SINE_C as f32), x),
+                    abs_f32x8(x),
+                ),
+            );
+            y = _mm256_add_ps(
+                _mm256_mul_ps(
+                    _mm256_set1_ps(SINE_P as f32),
+                    _mm256_sub_ps(_mm256_mul_ps(y, abs_f32x8(y)), y),
+                ),
+                y,
+            );
+
+            _mm256_storeu_ps(chunk.as_mut_ptr(), y);
+        }
+
+        parabolic_sine_f32_sse2(chunks.into_remainder());
+    }
+
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn abs_f32x4(x: __m128) -> __m128 {
+        _mm_andnot_ps(_mm_set1_ps(-0.0), x)
+    }
+
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn in_range_f32x4(x: __m128, range: f32) -> bool {
+        _mm_movemask_ps(_mm_cmplt_ps(abs_f32x4(x), _mm_set1_ps(range))) == 0b1111
+    }
+
+    /// Round towards zero, for inputs in the `i32` range.
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn trunc_f32x4(x: __m128) -> __m128 {
+        _mm_cvtepi32_ps(_mm_cvttps_epi32(x))
+    }
+
+    /// Select `b` where `mask` is set, and `a` elsewhere.
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn select_f32x4(mask: __m128, a: __m128, b: __m128) -> __m128 {
+        _mm_or_ps(_mm_and_ps(mask, b), _mm_andnot_ps(mask, a))
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn exp2_f32_sse2(values: &mut [f32]) {
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_ps(chunk.as_ptr());
+            if !in_range_f32x4(x, EXP2_RANGE_F32) {
+                scalar(chunk, fastmath::exp2);
+                continue;
+            }
+
+            let half_up = _mm_add_ps(x, _mm_set1_ps(0.5));
+            let truncated = trunc_f32x4(half_up);
+            let rounded_up = _mm_cmpgt_ps(truncated, half_up);
+            let ipart = _mm_sub_ps(truncated, _mm_and_ps(rounded_up, _mm_set1_ps(1.0)));
+            let fpart = _mm_sub_ps(x, ipart);
+
+            let exponent = _mm_slli_epi32(
+                _mm_add_epi32(_mm_cvttps_epi32(ipart), _mm_set1_epi32(FLOAT_BIAS)),
+                23,
+            );
+            let epart = _mm_castsi128_ps(exponent);
+
+            let x = _mm_mul_ps(fpart, fpart);
+
+            let mut px = _mm_set1_ps(EXP2_P[0] as f32);
+            px = _mm_add_ps(_mm_mul_ps(px, x), _mm_set1_ps(EXP2_P[1] as f32));
+            let mut qx = _mm_add_ps(x, _mm_set1_ps(EXP2_Q[0] as f32));
+            px = _mm_add_ps(_mm_mul_ps(px, x), _mm_set1_ps(EXP2_P[2] as f32));
+            qx = _mm_add_ps(_mm_mul_ps(qx, x), _mm_set1_ps(EXP2_Q[1] as f32));
+
+            px = _mm_mul_ps(px, fpart);
+
+            let ratio = _mm_div_ps(px, _mm_sub_ps(qx, px));
+            let x = _mm_add_ps(_mm_set1_ps(1.0), _mm_mul_ps(_mm_set1_ps(2.0), ratio));
+
+            _mm_storeu_ps(chunk.as_mut_ptr(), _mm_mul_ps(epart, x));
+        }
+
+        scalar(chunks.into_remainder(), fastmath::exp2);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn wrap01_f32_sse2(values: &mut [f32]) {
+        let one = _mm_set1_ps(1.0);
+        let sign = _mm_set1_ps(-0.0);
+
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_ps(chunk.as_ptr());
+            if !in_range_f32x4(x, INT_RANGE as f32) {
+                scalar(chunk, fastmath::wrap01);
+                continue;
+            }
+
+            let remainder = _mm_or_ps(_mm_sub_ps(x, trunc_f32x4(x)), _mm_and_ps(x, sign));
+            let negative = _mm_cmplt_ps(remainder, _mm_setzero_ps());
+            let wrapped = select_f32x4(negative, remainder, _mm_add_ps(remainder, one));
+
+            let in_range = _mm_and_ps(_mm_cmpge_ps(x, _mm_setzero_ps()), _mm_cmple_ps(x, one));
+
+            _mm_storeu_ps(chunk.as_mut_ptr(), select_f32x4(in_range, wrapped, x));
+        }
+
+        scalar(chunks.into_remainder(), fastmath::wrap01);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn parabolic_sine_f32_sse2(values: &mut [f32]) {
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let x = _mm_loadu_ps(chunk.as_ptr());
+
+            let mut y = _mm_add_ps(
+                _mm_mul_ps(_mm_set1_ps(SINE_B as f32), x),
+                _mm_mul_ps(_mm_mul_ps(_mm_set1_ps(SINE_C as f32), x), abs_f32x4(x)),
+            );
+            y = _mm_add_ps(
+                _mm_mul_ps(
+                    _mm_set1_ps(SINE_P as f32),
+                    _mm_sub_ps(_mm_mul_ps(y, abs_f32x4(y)), y),
+                ),
+                y,
+            );
+
+            _mm_storeu_ps(chunk.as_mut_ptr(), y);
+        }
+
+        scalar(chunks.into_remainder(), fastmath::parabolic_sine);
+    }
+}
@@ -0,0 +1,166 @@
+//! Smoothed parameters.
+//!
+//! Settings that can change while a note plays glide to their new value
+//! instead of jumping to it, which would cause zipper noise. A
+//! `SmoothedParam` is either ticked once per frame with `tick`, or expanded
+//! into per-frame values for a whole batch with `fill`, which ticks it the
+//! same way so both produce the same values.
+
+use crate::sample::Sample;
+
+/// How a parameter moves to a new target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Smoothing {
+    /// Jump to the target straight away.
+    Off,
+    /// Move at a constant rate, reaching the target after this many seconds.
+    Linear(f64),
+    /// Approach the target exponentially, with this time constant in
+    /// seconds. Under a frame, it jumps like `Off`.
+    OnePole(f64),
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing::Linear(DEFAULT_SMOOTHING_TIME)
+    }
+}
+
+pub const DEFAULT_SMOOTHING_TIME: f64 = 0.02;
+
+/// A one-pole glide snaps to its target once it's this close.
+const ONE_POLE_SETTLED: f64 = 1e-9;
+
+#[derive(Copy, Clone, Debug)]
+enum Glide {
+    Linear { frames: usize },
+    OnePole { coef: f64 },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SmoothedParam {
+    value: f64,
+    target: f64,
+    glide: Glide,
+    /// Per-frame step and frames left of a linear glide.
+    step: f64,
+    remaining: usize,
+}
+
+impl SmoothedParam {
+    pub fn new(value: f64, smoothing: Smoothing, sample_rate: f64) -> SmoothedParam {
+        let mut param = SmoothedParam {
+            value,
+            target: value,
+            glide: Glide::Linear { frames: 0 },
+            step: 0.0,
+            remaining: 0,
+        };
+        param.set_smoothing(smoothing, sample_rate);
+        param
+    }
+
+    /// Change how future targets are approached. A glide in progress
+    /// finishes straight away.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing, sample_rate: f64) {
+        self.glide = match smoothing {
+            Smoothing::Off => Glide::Linear { frames: 0 },
+            Smoothing::Linear(time) => Glide::Linear {
+                frames: (time * sample_rate).round().max(0.0) as usize,
+            },
+            // A time constant shorter than a frame jumps, like a linear glide
+            // that rounds to no frames.
+            Smoothing::OnePole(time) if time * sample_rate >= 1.0 => Glide::OnePole {
+                coef: (-1.0 / (time * sample_rate)).exp(),
+            },
+            Smoothing::OnePole(_) => Glide::Linear { frames: 0 },
+        };
+        self.finish();
+    }
+
+    /// Glide from the current value to `target`.
+    pub fn set_target(&mut self, target: f64) {
+        self.target = target;
+
+        match self.glide {
+            Glide::Linear { frames } if frames > 0 => {
+                self.step = (target - self.value) / frames as f64;
+                self.remaining = frames;
+            }
+            Glide::Linear { .. } => self.set_immediate(target),
+            Glide::OnePole { .. } => {}
+        }
+    }
+
+    /// Jump to `value`, e.g. when loading a patch.
+    pub fn set_immediate(&mut self, value: f64) {
+        self.value = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    /// Jump to the target of the glide in progress, if any.
+    pub fn finish(&mut self) {
+        self.set_immediate(self.target);
+    }
+
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// The value returned by the last call to `tick`.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    #[inline(always)]
+    pub fn is_settled(&self) -> bool {
+        self.value == self.target
+    }
+
+    /// Advance by one frame and return the new value.
+    #[inline(always)]
+    pub fn tick(&mut self) -> f64 {
+        if self.is_settled() {
+            return self.value;
+        }
+
+        match self.glide {
+            // A NaN target never compares equal to the value, so a linear
+            // glide ends on its frame count rather than on `is_settled`.
+            Glide::Linear { .. } => {
+                self.value = if self.remaining <= 1 {
+                    self.remaining = 0;
+                    self.target
+                } else {
+                    self.remaining -= 1;
+                    self.value + self.step
+                };
+            }
+            Glide::OnePole { coef } => {
+                self.value = self.target + coef * (self.value - self.target);
+                if (self.value - self.target).abs() < ONE_POLE_SETTLED {
+                    self.value = self.target;
+                }
+            }
+        }
+
+        self.value
+    }
+
+    /// Write the values of the next `output.len()` frames.
+    #[inline(always)]
+    pub fn fill<S: Sample>(&mut self, output: &mut [S]) {
+        if self.is_settled() {
+            let value = S::from_f64(self.value);
+            for output in output.iter_mut() {
+                *output = value;
+            }
+            return;
+        }
+
+        for output in output.iter_mut() {
+            *output = S::from_f64(self.tick());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Musical time, for syncing modulation to the host's tempo.
+//!
+//! Positions and lengths are measured in beats, i.e. quarter notes. Bars are
+//! assumed to be in 4/4, so a whole note is one bar.
+
+use crate::pitch::Hz;
+
+/// The state of the host's transport at the first frame of a render call.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transport {
+    /// Tempo in beats per minute.
+    pub bpm: f64,
+    /// Song position in beats since the start of the song.
+    pub position: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Feel {
+    #[default]
+    Straight,
+    /// One and a half times as long as the straight note.
+    Dotted,
+    /// Three in the time of two straight notes.
+    Triplet,
+}
+
+/// A note length such as 1/4, dotted 1/8, 1/8 triplet, or 2/1 for two bars.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Division {
+    pub numerator: u32,
+    pub denominator: u32,
+    pub feel: Feel,
+}
+
+impl Division {
+    pub const fn new(numerator: u32, denominator: u32) -> Division {
+        Division {
+            numerator,
+            denominator,
+            feel: Feel::Straight,
+        }
+    }
+
+    pub const fn bars(bars: u32) -> Division {
+        Division::new(bars, 1)
+    }
+
+    pub const fn dotted(self) -> Division {
+        Division {
+            feel: Feel::Dotted,
+            ..self
+        }
+    }
+
+    pub const fn triplet(self) -> Division {
+        Division {
+            feel: Feel::Triplet,
+            ..self
+        }
+    }
+
+    /// Length in beats.
+    pub fn beats(self) -> f64 {
+        let straight = 4.0 * self.numerator as f64 / self.denominator as f64;
+        match self.feel {
+            Feel::Straight => straight,
+            Feel::Dotted => straight * 1.5,
+            Feel::Triplet => straight * 2.0 / 3.0,
+        }
+    }
+
+    /// The rate at which one cycle lasts this long.
+    pub fn frequency(self, bpm: f64) -> Hz {
+        Hz(bpm / 60.0 / self.beats())
+    }
+
+    /// How far into the current cycle the song is, in `[0, 1)`.
+    pub fn phase(self, position: f64) -> f64 {
+        let phase = (position / self.beats()).rem_euclid(1.0);
+
+        // Tiny negative positions round up to a whole cycle.
+        if phase < 1.0 {
+            phase
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,766 @@
+//! Voice-parallel layout: one voice per SIMD lane.
+//!
+//! The other engines render a single voice and batch along time, which fights
+//! the serial phase accumulation of every oscillator: each frame's phase
+//! depends on the previous one. This engine packs `LANES` voices side by side
+//! instead, and steps all of them together one frame at a time. The voices
+//! are independent, so every per-frame operation is the same across the lanes
+//! and can be vectorised.
+//!
+//! Each voice has its own osc1, osc2, LFO and amp envelope, and renders the
+//! same samples as a `one_frame_per_call` voice of a `PolySynth`. The control
+//! signals that step through states of their own, i.e. the smoothed
+//! parameters, envelopes and LFO fades, are worked out a block of frames
+//! ahead, one voice at a time. That leaves the per-frame step over the lanes
+//! without any branch on a lane's data, but for the end of an LFO cycle: on
+//! the rare frames where a lane wraps, its one-shot finishing or its next
+//! random value is dealt with voice by voice. The wavetable oscillator,
+//! filter, modulation envelope, hard sync and tempo sync are only available on
+//! the single-voice engines.
+//!
+//! Like the other engines, it renders in `S`, either `f64` or `f32`. The
+//! `f32` voices fit twice as many lanes in a register.
+
+use crate::engine::Oscillator;
+use crate::envelope::{AdsrSegments, AdsrSettings, AdsrState, Stage};
+use crate::modulation::ModSource;
+use crate::params::{Choice, ParamId};
+use crate::pitch::{Cents, Hz, MidiNote, Octaves, Semitones};
+use crate::polyphony::{StealPolicy, VoiceAllocator};
+use crate::sample::Sample;
+use crate::simd;
+use crate::smoothing::{SmoothedParam, Smoothing};
+use crate::waveform::{self, LfoFade, LfoMode, LfoWaveform, RandomSteps, Waveform};
+
+const OSC_MAX_FREQ: f64 = 20480.0;
+
+/// Frames of control signals worked out ahead of the per-frame step.
+const BLOCK_SIZE: usize = 32;
+
+type LaneData<const LANES: usize, S> = [S; LANES];
+
+/// A control signal of every lane for a block, frame by frame.
+type LaneBlock<const LANES: usize, S> = [LaneData<LANES, S>; BLOCK_SIZE];
+
+/// Fill the first `frames` frames of `block`, one lane at a time: `fill`
+/// writes the values of a lane into a slice of `frames` values, which are
+/// then converted to `S`.
+#[inline(always)]
+fn fill_lanes<const LANES: usize, S: Sample>(
+    block: &mut LaneBlock<LANES, S>,
+    frames: usize,
+    mut fill: impl FnMut(usize, &mut [f64]),
+) {
+    let mut values = [0.0; BLOCK_SIZE];
+    for lane in 0..LANES {
+        fill(lane, &mut values[..frames]);
+        for (frame, value) in block[..frames].iter_mut().zip(values.iter()) {
+            frame[lane] = S::from_f64(*value);
+        }
+    }
+}
+
+/// `1` where `condition` holds and `0` elsewhere.
+#[inline(always)]
+fn select_one<S: Sample>(condition: bool) -> S {
+    S::from_f64(f64::from(u8::from(condition)))
+}
+
+/// Glide every lane to a new value of one of the smoothed parameters.
+fn set_target<const LANES: usize>(params: &mut [SmoothedParam; LANES], value: f64) {
+    for param in params.iter_mut() {
+        param.set_target(value);
+    }
+}
+
+struct OscillatorHelper<const LANES: usize, S> {
+    pub sample_rate: f64,
+    pub input_frequency: LaneData<LANES, S>,
+    /// Static pitch offsets, in octaves, semitones and cents.
+    pub octave_offset: [SmoothedParam; LANES],
+    pub semitone_offset: [SmoothedParam; LANES],
+    pub cent_offset: [SmoothedParam; LANES],
+    pub amplitude: [SmoothedParam; LANES],
+
+    pub amplitude_mod: LaneData<LANES, S>,
+    /// Frequency modulation in octaves, added to the static pitch offsets.
+    pub frequency_mod: LaneData<LANES, S>,
+
+    /// The pitch offsets, summed in octaves, and the amplitudes of the
+    /// current block.
+    pitch_offset: LaneBlock<LANES, S>,
+    amplitudes: LaneBlock<LANES, S>,
+
+    computed_amplitude: LaneData<LANES, S>,
+    phase_increment: LaneData<LANES, S>,
+    modulo: LaneData<LANES, S>,
+}
+
+impl<const LANES: usize, S: Sample> OscillatorHelper<LANES, S> {
+    fn new(sample_rate: f64) -> Self {
+        let param = |value| SmoothedParam::new(value, Smoothing::default(), sample_rate);
+
+        OscillatorHelper {
+            sample_rate,
+            input_frequency: [S::ZERO; LANES],
+            octave_offset: [param(0.0); LANES],
+            semitone_offset: [param(0.0); LANES],
+            cent_offset: [param(0.0); LANES],
+            amplitude: [param(1.0); LANES],
+            amplitude_mod: [S::ONE; LANES],
+            frequency_mod: [S::ZERO; LANES],
+            pitch_offset: [[S::ZERO; LANES]; BLOCK_SIZE],
+            amplitudes: [[S::ONE; LANES]; BLOCK_SIZE],
+            computed_amplitude: [S::ONE; LANES],
+            phase_increment: [S::ZERO; LANES],
+            modulo: [S::ZERO; LANES],
+        }
+    }
+
+    /// Work out the smoothed parameters of the next `frames` frames.
+    fn prepare(&mut self, frames: usize) {
+        let octave_offset = &mut self.octave_offset;
+        let semitone_offset = &mut self.semitone_offset;
+        let cent_offset = &mut self.cent_offset;
+        fill_lanes(&mut self.pitch_offset, frames, |lane, pitch_offset| {
+            let mut semitones = [0.0; BLOCK_SIZE];
+            let mut cents = [0.0; BLOCK_SIZE];
+            octave_offset[lane].fill(pitch_offset);
+            semitone_offset[lane].fill(&mut semitones[..frames]);
+            cent_offset[lane].fill(&mut cents[..frames]);
+
+            for ((pitch_offset, semitones), cents) in pitch_offset
+                .iter_mut()
+                .zip(semitones.iter())
+                .zip(cents.iter())
+            {
+                *pitch_offset = (Octaves(*pitch_offset)
+                    + Octaves::from(Semitones(*semitones))
+                    + Octaves::from(Cents(*cents)))
+                .0;
+            }
+        });
+
+        let amplitude = &mut self.amplitude;
+        fill_lanes(&mut self.amplitudes, frames, |lane, amplitudes| {
+            amplitude[lane].fill(amplitudes)
+        });
+    }
+
+    #[inline(always)]
+    fn update(&mut self, frame: usize) {
+        let mut frequency_factor = self.frequency_mod;
+        for (factor, pitch_offset) in frequency_factor
+            .iter_mut()
+            .zip(self.pitch_offset[frame].iter())
+        {
+            *factor += *pitch_offset;
+        }
+        simd::exp2(&mut frequency_factor);
+
+        self.computed_amplitude = self.amplitudes[frame];
+
+        let max_frequency = S::from_f64(OSC_MAX_FREQ);
+        let sample_rate = S::from_f64(self.sample_rate);
+        for ((phase_increment, input_frequency), factor) in self
+            .phase_increment
+            .iter_mut()
+            .zip(self.input_frequency.iter())
+            .zip(frequency_factor.iter())
+        {
+            let frequency = (*input_frequency * *factor).clamp(-max_frequency, max_frequency);
+
+            *phase_increment = frequency / sample_rate;
+        }
+    }
+
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        let sample_rate = self.sample_rate;
+        for lane in 0..LANES {
+            self.octave_offset[lane].set_smoothing(smoothing, sample_rate);
+            self.semitone_offset[lane].set_smoothing(smoothing, sample_rate);
+            self.cent_offset[lane].set_smoothing(smoothing, sample_rate);
+            self.amplitude[lane].set_smoothing(smoothing, sample_rate);
+        }
+    }
+
+    /// Restart one voice. Parameters that are still gliding jump to their
+    /// targets.
+    fn reset(&mut self, lane: usize) {
+        self.octave_offset[lane].finish();
+        self.semitone_offset[lane].finish();
+        self.cent_offset[lane].finish();
+        self.amplitude[lane].finish();
+
+        self.phase_increment[lane] = S::ZERO;
+        self.modulo[lane] = S::ZERO;
+    }
+
+    /// Wrap the phase of every lane that has run past the end of its cycle
+    /// back into `[0, 1)`, and return a mask with the bits of those lanes set.
+    /// The wrap is added as a `0` or `1` from the comparisons, rather than
+    /// branched on.
+    #[inline(always)]
+    fn wrap_modulo(&mut self) -> u64 {
+        let mut wrapped = 0;
+        for lane in 0..LANES {
+            let phase_increment = self.phase_increment[lane];
+            let modulo = self.modulo[lane];
+
+            let forwards = (phase_increment > S::ZERO) & (modulo >= S::ONE);
+            let backwards = (phase_increment < S::ZERO) & (modulo <= S::ZERO);
+            self.modulo[lane] = modulo - select_one(forwards) + select_one(backwards);
+            wrapped |= u64::from(forwards | backwards) << lane;
+        }
+        wrapped
+    }
+
+    /// The phase of every lane, wrapped into `[0, 1]`.
+    #[inline(always)]
+    fn wrapped_modulo(&self) -> LaneData<LANES, S> {
+        let mut modulo = self.modulo;
+        simd::wrap01(&mut modulo);
+        modulo
+    }
+
+    #[inline(always)]
+    fn increment_modulo(&mut self) {
+        for lane in 0..LANES {
+            self.modulo[lane] += self.phase_increment[lane];
+        }
+    }
+}
+
+struct BandLimitedOscillator<const LANES: usize, S> {
+    helper: OscillatorHelper<LANES, S>,
+    waveform: Waveform,
+    pulse_width: f64,
+    output: LaneData<LANES, S>,
+}
+
+impl<const LANES: usize, S: Sample> BandLimitedOscillator<LANES, S> {
+    fn new(sample_rate: f64) -> Self {
+        BandLimitedOscillator {
+            helper: OscillatorHelper::new(sample_rate),
+            waveform: Waveform::Sine,
+            pulse_width: 0.5,
+            output: [S::ZERO; LANES],
+        }
+    }
+
+    fn render(&mut self, frame: usize) {
+        self.helper.update(frame);
+        self.helper.wrap_modulo();
+
+        let pulse_width = S::from_f64(self.pulse_width);
+
+        match self.waveform {
+            Waveform::Sine => self.render_shape(|modulo, _| waveform::sine(modulo)),
+            Waveform::Saw => self.render_shape(waveform::saw),
+            Waveform::Square => self.render_shape(|modulo, phase_increment| {
+                waveform::square(modulo, phase_increment, pulse_width)
+            }),
+            Waveform::Triangle => self.render_shape(waveform::triangle),
+        }
+    }
+
+    #[inline(always)]
+    fn render_shape<F: Fn(S, S) -> S>(&mut self, shape: F) {
+        let modulo = self.helper.wrapped_modulo();
+
+        for (lane, modulo) in modulo.iter().enumerate() {
+            self.output[lane] = shape(*modulo, self.helper.phase_increment[lane])
+                * self.helper.computed_amplitude[lane]
+                * self.helper.amplitude_mod[lane];
+        }
+
+        self.helper.increment_modulo();
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+struct LFO<const LANES: usize, S> {
+    helper: OscillatorHelper<LANES, S>,
+    waveform: LfoWaveform,
+    pulse_width: f64,
+    random: [RandomSteps; LANES],
+    mode: LfoMode,
+    fade: [LfoFade; LANES],
+    /// The fade's gains for the current block.
+    fade_gain: LaneBlock<LANES, S>,
+    /// Set once a one-shot LFO has run its cycle.
+    finished: [bool; LANES],
+
+    output: LaneData<LANES, S>,
+    quad_output: LaneData<LANES, S>,
+}
+
+impl<const LANES: usize, S: Sample> LFO<LANES, S> {
+    fn new(sample_rate: f64) -> Self {
+        LFO {
+            helper: OscillatorHelper::new(sample_rate),
+            waveform: LfoWaveform::Sine,
+            pulse_width: 0.5,
+            random: [RandomSteps::new(waveform::LFO_RANDOM_SEED); LANES],
+            mode: LfoMode::FreeRunning,
+            fade: [LfoFade::default(); LANES],
+            fade_gain: [[S::ONE; LANES]; BLOCK_SIZE],
+            finished: [false; LANES],
+            output: [S::ZERO; LANES],
+            quad_output: [S::ZERO; LANES],
+        }
+    }
+
+    fn set_frequency(&mut self, frequency: Hz) {
+        self.helper.input_frequency = [S::from_f64(frequency.0); LANES];
+    }
+
+    fn set_mode(&mut self, mode: LfoMode) {
+        self.mode = mode;
+        self.finished = [false; LANES];
+    }
+
+    fn set_delay(&mut self, delay: f64, fade_in: f64) {
+        for fade in self.fade.iter_mut() {
+            fade.set_times(delay, fade_in, self.helper.sample_rate);
+        }
+    }
+
+    /// Start a note on one voice: restart the delay and fade-in and, unless
+    /// the LFO is free running, the cycle.
+    fn trigger(&mut self, lane: usize) {
+        self.fade[lane].restart();
+
+        if self.mode != LfoMode::FreeRunning {
+            self.helper.modulo[lane] = S::ZERO;
+            self.random[lane].advance();
+            self.finished[lane] = false;
+        }
+    }
+
+    fn reset(&mut self, lane: usize) {
+        self.helper.reset(lane);
+        self.random[lane] = RandomSteps::new(waveform::LFO_RANDOM_SEED);
+        self.fade[lane].restart();
+        self.finished[lane] = false;
+    }
+
+    /// Work out the smoothed parameters and fade of the next `frames` frames.
+    fn prepare(&mut self, frames: usize) {
+        self.helper.prepare(frames);
+
+        let fade = &mut self.fade;
+        fill_lanes(&mut self.fade_gain, frames, |lane, gains| {
+            for gain in gains.iter_mut() {
+                *gain = fade[lane].next_gain();
+            }
+        });
+    }
+
+    fn render(&mut self, frame: usize) {
+        self.helper.update(frame);
+
+        // Lanes only wrap once a cycle, so the voices that did are dealt with
+        // one by one.
+        let wrapped = self.helper.wrap_modulo();
+        if wrapped != 0 {
+            self.end_cycles(wrapped);
+        }
+
+        self.helper.amplitude_mod = self.fade_gain[frame];
+
+        // The random shapes have no phase to shift, so their quadrature
+        // output is the same as the main output.
+        let quad_offset = if self.waveform.is_random() {
+            S::ZERO
+        } else {
+            S::from_f64(0.25)
+        };
+
+        let mut modulo = [S::ZERO; LANES];
+        let mut quad_modulo = [S::ZERO; LANES];
+        for lane in 0..LANES {
+            // A finished one-shot holds the end of its cycle. Both selects
+            // are worked out from `0` or `1`, like the wrap.
+            let hold = select_one::<S>(self.finished[lane]);
+            modulo[lane] = self.helper.modulo[lane] * (S::ONE - hold) + hold;
+
+            let quad = modulo[lane] + quad_offset;
+            quad_modulo[lane] = quad - select_one::<S>(quad >= S::ONE);
+        }
+
+        let pulse_width = S::from_f64(self.pulse_width);
+        let random = &self.random;
+        let (output, quad_output) = match self.waveform {
+            LfoWaveform::Sine => {
+                self.shape(&modulo, &quad_modulo, |_, modulo| waveform::sine(modulo))
+            }
+            LfoWaveform::Triangle => self.shape(&modulo, &quad_modulo, |_, modulo| {
+                waveform::triangle(modulo, S::ZERO)
+            }),
+            LfoWaveform::RampUp => {
+                self.shape(&modulo, &quad_modulo, |_, modulo| waveform::ramp_up(modulo))
+            }
+            LfoWaveform::RampDown => self.shape(&modulo, &quad_modulo, |_, modulo| {
+                waveform::ramp_down(modulo)
+            }),
+            LfoWaveform::Square => self.shape(&modulo, &quad_modulo, |_, modulo| {
+                waveform::pulse(modulo, pulse_width)
+            }),
+            LfoWaveform::SampleAndHold => self.shape(&modulo, &quad_modulo, |lane, _| {
+                S::from_f64(random[lane].held())
+            }),
+            LfoWaveform::SmoothRandom => self.shape(&modulo, &quad_modulo, |lane, modulo| {
+                S::from_f64(random[lane].smoothed(modulo.to_f64()))
+            }),
+        };
+        self.output = output;
+        self.quad_output = quad_output;
+
+        self.helper.increment_modulo();
+    }
+
+    /// Finish the one-shot cycle, or move on to the next random value, of
+    /// every lane set in `lanes`.
+    fn end_cycles(&mut self, lanes: u64) {
+        for lane in (0..LANES).filter(|lane| lanes & (1 << lane) != 0) {
+            if self.mode == LfoMode::OneShot {
+                self.finished[lane] = true;
+            } else {
+                self.random[lane].advance();
+            }
+        }
+    }
+
+    /// The main and quadrature outputs of `shape`, which is given a lane and
+    /// its phase.
+    #[inline(always)]
+    fn shape<F: Fn(usize, S) -> S>(
+        &self,
+        modulo: &LaneData<LANES, S>,
+        quad_modulo: &LaneData<LANES, S>,
+        shape: F,
+    ) -> (LaneData<LANES, S>, LaneData<LANES, S>) {
+        let mut output = [S::ZERO; LANES];
+        let mut quad_output = [S::ZERO; LANES];
+        for lane in 0..LANES {
+            let amplitude = self.helper.computed_amplitude[lane];
+            let amplitude_mod = self.helper.amplitude_mod[lane];
+            output[lane] = shape(lane, modulo[lane]) * amplitude * amplitude_mod;
+            quad_output[lane] = shape(lane, quad_modulo[lane]) * amplitude * amplitude_mod;
+        }
+        (output, quad_output)
+    }
+}
+
+struct Envelope<const LANES: usize, S> {
+    enabled: bool,
+    segments: AdsrSegments,
+    state: [AdsrState; LANES],
+    /// The levels of the current block.
+    levels: LaneBlock<LANES, S>,
+}
+
+impl<const LANES: usize, S: Sample> Envelope<LANES, S> {
+    fn new(sample_rate: f64) -> Self {
+        Envelope {
+            enabled: false,
+            segments: AdsrSegments::new(&AdsrSettings::default(), sample_rate),
+            state: [AdsrState::default(); LANES],
+            levels: [[S::ONE; LANES]; BLOCK_SIZE],
+        }
+    }
+
+    /// Work out the levels of the next `frames` frames.
+    fn prepare(&mut self, frames: usize) {
+        let segments = &self.segments;
+        let state = &mut self.state;
+        fill_lanes(&mut self.levels, frames, |lane, levels| {
+            // Note events only happen between renders, so an idle or
+            // sustaining envelope stays at the same level for the whole block.
+            if state[lane].is_steady() {
+                let level = state[lane].next(segments);
+                for output in levels.iter_mut() {
+                    *output = level;
+                }
+                return;
+            }
+
+            for output in levels.iter_mut() {
+                *output = state[lane].next(segments);
+            }
+        });
+    }
+
+    fn level(&self, lane: usize) -> f64 {
+        if self.enabled {
+            self.state[lane].level
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether a released voice is still sounding.
+    fn releasing(&self, lane: usize) -> bool {
+        self.enabled && self.state[lane].stage != Stage::Idle
+    }
+}
+
+/// A polyphonic synth with `LANES` voices, e.g. 4 or 8 to fill the SIMD
+/// registers.
+pub struct Synth<const LANES: usize, S = f64> {
+    osc1: BandLimitedOscillator<LANES, S>,
+    osc2: BandLimitedOscillator<LANES, S>,
+    lfo: LFO<LANES, S>,
+    osc2_frequency_mod_source: ModSource,
+    envelope: Envelope<LANES, S>,
+    allocator: VoiceAllocator,
+}
+
+impl<const LANES: usize, S: Sample> Synth<LANES, S> {
+    fn helper_mut(&mut self, oscillator: Oscillator) -> Option<&mut OscillatorHelper<LANES, S>> {
+        match oscillator {
+            Oscillator::Osc1 => Some(&mut self.osc1.helper),
+            Oscillator::Osc2 => Some(&mut self.osc2.helper),
+            Oscillator::Wavetable => None,
+            Oscillator::Lfo => Some(&mut self.lfo.helper),
+        }
+    }
+
+    pub fn new(sample_rate: f64) -> Self {
+        let mut synth = Synth {
+            osc1: BandLimitedOscillator::new(sample_rate),
+            osc2: BandLimitedOscillator::new(sample_rate),
+            lfo: LFO::new(sample_rate),
+            osc2_frequency_mod_source: ModSource::default(),
+            envelope: Envelope::new(sample_rate),
+            allocator: VoiceAllocator::new(LANES),
+        };
+
+        for cent_offset in synth.osc2.helper.cent_offset.iter_mut() {
+            cent_offset.set_immediate(2.5);
+        }
+
+        synth.lfo.set_frequency(Hz(0.5));
+
+        synth
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.osc1.helper.sample_rate
+    }
+
+    pub fn voice_count(&self) -> usize {
+        LANES
+    }
+
+    /// The number of voices that are held or still releasing.
+    pub fn active_voices(&self) -> usize {
+        self.allocator.active_voices()
+    }
+
+    /// The notes of the sounding voices, in voice order.
+    pub fn sounding_notes(&self) -> impl Iterator<Item = MidiNote> + '_ {
+        self.allocator.sounding_notes()
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.allocator.set_steal_policy(policy);
+    }
+
+    /// Set a parameter on every voice, clamped to its range. The frequency is
+    /// set by each voice's note instead, and the parameters of the wavetable
+    /// oscillator, hard sync and tempo sync have no effect.
+    pub fn set_parameter(&mut self, id: ParamId, value: f64) {
+        let value = id.info().clamp(value);
+
+        match id {
+            ParamId::Frequency
+            | ParamId::WavetablePosition
+            | ParamId::HardSync
+            | ParamId::LfoTempoSync => {}
+            ParamId::Octaves(oscillator) => {
+                if let Some(helper) = self.helper_mut(oscillator) {
+                    set_target(&mut helper.octave_offset, value);
+                }
+            }
+            ParamId::Semitones(oscillator) => {
+                if let Some(helper) = self.helper_mut(oscillator) {
+                    set_target(&mut helper.semitone_offset, value);
+                }
+            }
+            ParamId::Cents(oscillator) => {
+                if let Some(helper) = self.helper_mut(oscillator) {
+                    set_target(&mut helper.cent_offset, value);
+                }
+            }
+            ParamId::Level(oscillator) => {
+                if let Some(helper) = self.helper_mut(oscillator) {
+                    set_target(&mut helper.amplitude, value);
+                }
+            }
+            ParamId::Osc1Waveform => self.osc1.waveform = Waveform::from_value(value),
+            ParamId::Osc1PulseWidth => self.osc1.pulse_width = value,
+            ParamId::Osc2Waveform => self.osc2.waveform = Waveform::from_value(value),
+            ParamId::Osc2PulseWidth => self.osc2.pulse_width = value,
+            ParamId::LfoFrequency => self.lfo.set_frequency(Hz(value)),
+            ParamId::LfoWaveform => self.lfo.waveform = LfoWaveform::from_value(value),
+            ParamId::LfoPulseWidth => self.lfo.pulse_width = value,
+            ParamId::LfoMode => self.lfo.set_mode(LfoMode::from_value(value)),
+            ParamId::LfoDelay => {
+                let (_, fade_in) = self.lfo.fade[0].times();
+                self.lfo.set_delay(value, fade_in);
+            }
+            ParamId::LfoFadeIn => {
+                let (delay, _) = self.lfo.fade[0].times();
+                self.lfo.set_delay(delay, value);
+            }
+        }
+    }
+
+    /// Set how pitch offsets and levels glide to new values. Glides in
+    /// progress finish straight away.
+    pub fn set_parameter_smoothing(&mut self, smoothing: Smoothing) {
+        self.osc1.helper.set_smoothing(smoothing);
+        self.osc2.helper.set_smoothing(smoothing);
+        self.lfo.helper.set_smoothing(smoothing);
+    }
+
+    /// Select the signal driving osc2's frequency modulation.
+    pub fn set_osc2_frequency_mod_source(&mut self, source: ModSource) {
+        self.osc2_frequency_mod_source = source;
+    }
+
+    /// Keep each voice's LFO silent for `delay` seconds after its note, then
+    /// fade it in over `fade_in` seconds.
+    pub fn set_lfo_delay(&mut self, delay: f64, fade_in: f64) {
+        self.lfo.set_delay(delay, fade_in);
+    }
+
+    /// Shape every voice with an ADSR envelope, or play at a constant level
+    /// until the note is released with `None`.
+    pub fn set_amp_envelope(&mut self, settings: Option<AdsrSettings>) {
+        match settings {
+            Some(settings) => {
+                self.envelope.segments = AdsrSegments::new(&settings, self.sample_rate());
+                self.envelope.enabled = true;
+            }
+            None => {
+                self.envelope.enabled = false;
+                self.osc1.helper.amplitude_mod = [S::ONE; LANES];
+                self.osc2.helper.amplitude_mod = [S::ONE; LANES];
+            }
+        }
+    }
+
+    /// Start a note on a free voice, or steal one if they're all in use.
+    pub fn note_on(&mut self, note: MidiNote) {
+        let envelope = &self.envelope;
+        let (lane, free) = match self.allocator.note_on(note, |lane| envelope.level(lane)) {
+            Some(voice) => voice,
+            None => return,
+        };
+
+        // A free voice starts from scratch. A stolen one carries on from
+        // where it is, with the envelope attacking from its current level.
+        if free {
+            self.reset_voice(lane);
+        }
+
+        let frequency = S::from_f64(ParamId::Frequency.info().clamp(Hz::from(note).0));
+        self.osc1.helper.input_frequency[lane] = frequency;
+        self.osc2.helper.input_frequency[lane] = frequency;
+
+        self.lfo.trigger(lane);
+        self.envelope.state[lane].note_on(&self.envelope.segments);
+    }
+
+    /// Release every voice holding `note`.
+    pub fn note_off(&mut self, note: MidiNote) {
+        self.release(Some(note));
+    }
+
+    /// Release every held note.
+    pub fn all_notes_off(&mut self) {
+        self.release(None);
+    }
+
+    fn release(&mut self, note: Option<MidiNote>) {
+        let envelope = &mut self.envelope;
+        self.allocator
+            .note_off(note, |lane| envelope.state[lane].note_off());
+
+        let envelope = &self.envelope;
+        self.allocator.update(|lane| envelope.releasing(lane));
+    }
+
+    fn reset_voice(&mut self, lane: usize) {
+        self.osc1.helper.reset(lane);
+        self.osc2.helper.reset(lane);
+        self.lfo.reset(lane);
+        self.envelope.state[lane] = AdsrState::default();
+    }
+
+    /// Silence every voice and restore their initial state, keeping the
+    /// settings.
+    pub fn reset(&mut self) {
+        for lane in 0..LANES {
+            self.reset_voice(lane);
+        }
+        self.allocator.reset();
+    }
+
+    /// Render the sum of every sounding voice into `buffer`.
+    pub fn render(&mut self, buffer: &mut [S]) {
+        let half = S::from_f64(0.5);
+
+        // Free voices keep running in their lanes, but are left out of the
+        // mix.
+        let mut gates = [S::ZERO; LANES];
+        for (lane, gate) in gates.iter_mut().enumerate() {
+            if self.allocator.is_active(lane) {
+                *gate = S::ONE;
+            }
+        }
+
+        for block in buffer.chunks_mut(BLOCK_SIZE) {
+            let frames = block.len();
+
+            if self.envelope.enabled {
+                self.envelope.prepare(frames);
+            }
+            self.lfo.prepare(frames);
+            self.osc1.helper.prepare(frames);
+            self.osc2.helper.prepare(frames);
+
+            for (frame, output) in block.iter_mut().enumerate() {
+                if self.envelope.enabled {
+                    self.osc1.helper.amplitude_mod = self.envelope.levels[frame];
+                    self.osc2.helper.amplitude_mod = self.envelope.levels[frame];
+                }
+
+                self.lfo.render(frame);
+
+                self.osc1.helper.frequency_mod = self.lfo.output;
+                self.osc2.helper.frequency_mod = match self.osc2_frequency_mod_source {
+                    ModSource::Lfo => self.lfo.output,
+                    ModSource::LfoQuadrature => self.lfo.quad_output,
+                };
+
+                self.osc1.render(frame);
+                self.osc2.render(frame);
+
+                let outputs = self.osc1.output.iter().zip(self.osc2.output.iter());
+                *output = gates
+                    .iter()
+                    .zip(outputs)
+                    .fold(S::ZERO, |mix, (gate, (osc1, osc2))| {
+                        mix + *gate * (half * *osc1 + half * *osc2)
+                    });
+            }
+        }
+
+        let envelope = &self.envelope;
+        self.allocator.update(|lane| envelope.releasing(lane));
+    }
+}
@@ -0,0 +1,308 @@
+//! Per-sample oscillator waveform kernels.
+//!
+//! The discontinuous waveforms are band limited with PolyBLEP (for steps) and
+//! PolyBLAMP (for slope changes) corrections. `modulo` is the oscillator phase
+//! in `[0, 1)` and `phase_increment` the phase advance per frame, which sets
+//! the width of the correction around each discontinuity. The kernels are
+//! generic over the engine's `Sample` type.
+
+use crate::fastmath::{parabolic_sine, wrap01};
+use crate::sample::Sample;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Saw,
+    /// Pulse wave, with the duty cycle set by the oscillator's pulse width.
+    Square,
+    Triangle,
+}
+
+/// Shapes for low frequency oscillators. Unlike `Waveform`, these are not
+/// band limited, since LFOs run far below Nyquist.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LfoWaveform {
+    #[default]
+    Sine,
+    Triangle,
+    RampUp,
+    RampDown,
+    /// Pulse wave, with the duty cycle set by the LFO's pulse width.
+    Square,
+    /// A new random value on every cycle, held until the next one.
+    SampleAndHold,
+    /// A new random value on every cycle, glided to over the whole cycle.
+    SmoothRandom,
+}
+
+impl LfoWaveform {
+    /// Whether this shape is driven by `RandomSteps` rather than the phase.
+    pub fn is_random(self) -> bool {
+        matches!(self, LfoWaveform::SampleAndHold | LfoWaveform::SmoothRandom)
+    }
+}
+
+/// How an LFO responds to notes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LfoMode {
+    /// Run continuously, ignoring notes.
+    #[default]
+    FreeRunning,
+    /// Restart from the beginning of the cycle on every note.
+    Retrigger,
+    /// Restart on every note, run a single cycle and hold the final value.
+    OneShot,
+}
+
+/// Delay and fade-in of an LFO after a note starts, as a gain to be applied
+/// through the LFO's `amplitude_mod`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LfoFade {
+    /// Times in frames.
+    delay: f64,
+    fade_in: f64,
+    elapsed: f64,
+    /// Times in seconds, as they were set.
+    times: (f64, f64),
+}
+
+impl LfoFade {
+    /// Set the delay and fade-in times in seconds.
+    pub fn set_times(&mut self, delay: f64, fade_in: f64, sample_rate: f64) {
+        self.delay = (delay * sample_rate).max(0.0);
+        self.fade_in = (fade_in * sample_rate).max(0.0);
+        self.times = (delay, fade_in);
+    }
+
+    /// The delay and fade-in times in seconds.
+    pub fn times(&self) -> (f64, f64) {
+        self.times
+    }
+
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    /// The gain for the next frame: silent during the delay, then ramping up
+    /// linearly over the fade-in.
+    #[inline(always)]
+    pub fn next_gain(&mut self) -> f64 {
+        let end = self.delay + self.fade_in;
+        if self.elapsed >= end {
+            return 1.0;
+        }
+
+        let gain = if self.elapsed < self.delay {
+            0.0
+        } else {
+            (self.elapsed - self.delay) / self.fade_in
+        };
+        self.elapsed += 1.0;
+        gain
+    }
+}
+
+/// Residual of a band-limited unit step at `t == 0`, to be added to a naive
+/// step from -1 to +1.
+///
+/// The step is found from `t` and `dt` rather than from the oscillator's wrap
+/// flag: the residual also covers the frame before the step, the pulse edge
+/// and triangle corners aren't wraps at all, and phase modulation moves the
+/// rendered phase's wrap away from the accumulator's.
+#[inline(always)]
+pub fn poly_blep<S: Sample>(t: S, dt: S) -> S {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - S::ONE
+    } else if t > S::ONE - dt {
+        let t = (t - S::ONE) / dt;
+        t * t + t + t + S::ONE
+    } else {
+        S::ZERO
+    }
+}
+
+/// Residual of a band-limited corner at `t == 0` where the slope increases by
+/// 2 per frame, i.e. the integral of `poly_blep`. Scale by half the actual
+/// change in slope per frame.
+#[inline(always)]
+pub fn poly_blamp<S: Sample>(t: S, dt: S) -> S {
+    if t < dt {
+        let t = t / dt - S::ONE;
+        S::from_f64(-1.0 / 3.0) * t * t * t
+    } else if t > S::ONE - dt {
+        let t = (t - S::ONE) / dt + S::ONE;
+        S::from_f64(1.0 / 3.0) * t * t * t
+    } else {
+        S::ZERO
+    }
+}
+
+#[inline(always)]
+pub fn sine<S: Sample>(modulo: S) -> S {
+    parabolic_sine(sine_argument(modulo))
+}
+
+/// What `sine` passes to `parabolic_sine`, for batches that run it through
+/// `simd::parabolic_sine` instead.
+#[inline(always)]
+pub fn sine_argument<S: Sample>(modulo: S) -> S {
+    let pi = S::from_f64(std::f64::consts::PI);
+    let angle = modulo * S::from_f64(2.0) * pi - pi;
+    -angle
+}
+
+#[inline(always)]
+pub fn saw<S: Sample>(modulo: S, phase_increment: S) -> S {
+    let dt = phase_increment.abs();
+    S::from_f64(2.0) * modulo - S::ONE - poly_blep(modulo, dt)
+}
+
+#[inline(always)]
+pub fn square<S: Sample>(modulo: S, phase_increment: S, pulse_width: S) -> S {
+    let dt = phase_increment.abs();
+    let naive = if modulo < pulse_width {
+        S::ONE
+    } else {
+        -S::ONE
+    };
+    naive + poly_blep(modulo, dt) - poly_blep(wrap01(modulo - pulse_width), dt)
+}
+
+/// Triangle starting at zero and rising, in phase with `sine`. The corners at
+/// 1/4 and 3/4 each change the slope by 8 per cycle, i.e. `8 * dt` per frame.
+#[inline(always)]
+pub fn triangle<S: Sample>(modulo: S, phase_increment: S) -> S {
+    let dt = phase_increment.abs();
+    let quarter = S::from_f64(0.25);
+    let three_quarters = S::from_f64(0.75);
+    let four = S::from_f64(4.0);
+
+    let naive = if modulo < quarter {
+        four * modulo
+    } else if modulo < three_quarters {
+        S::from_f64(2.0) - four * modulo
+    } else {
+        four * modulo - four
+    };
+    naive - four * dt * poly_blamp(wrap01(modulo - quarter), dt)
+        + four * dt * poly_blamp(wrap01(modulo - three_quarters), dt)
+}
+
+/// How far into the current frame a wrap happened, as a fraction of the
+/// frame, given the phase right after the wrap.
+#[inline(always)]
+pub fn wrap_fraction<S: Sample>(modulo: S, phase_increment: S) -> S {
+    if phase_increment > S::ZERO {
+        modulo / phase_increment
+    } else {
+        (modulo - S::ONE) / phase_increment
+    }
+}
+
+/// PolyBLEP correction for the step caused by hard syncing a slave oscillator
+/// to a master. `naive` is the slave's waveform without any correction.
+///
+/// On the frame where the master wrapped, `sync_from` is the phase the slave
+/// was reset from. The slave's own wrap correction already covers the step
+/// from the end of its cycle to the start, so only the remaining step from
+/// `sync_from` to the end of the cycle is corrected here. On the frame before
+/// the master wraps, the slave's phase at the reset is predicted from the
+/// current phase increments.
+///
+/// The correction assumes a positive master frequency and no phase modulation
+/// on the slave.
+#[inline(always)]
+pub fn sync_correction<S: Sample, F: Fn(S) -> S>(
+    naive: F,
+    master_wrapped: bool,
+    master_modulo: S,
+    master_phase_increment: S,
+    sync_from: S,
+    modulo: S,
+    phase_increment: S,
+) -> S {
+    let dt = master_phase_increment;
+    let half = S::from_f64(0.5);
+
+    if dt <= S::ZERO {
+        S::ZERO
+    } else if master_wrapped {
+        half * (naive(S::ONE) - naive(sync_from)) * poly_blep(master_modulo, dt)
+    } else if master_modulo > S::ONE - dt {
+        let sync_at = wrap01(modulo + (S::ONE - master_modulo) / dt * phase_increment);
+        half * (naive(S::ZERO) - naive(sync_at)) * poly_blep(master_modulo, dt)
+    } else {
+        S::ZERO
+    }
+}
+
+#[inline(always)]
+pub fn ramp_up<S: Sample>(modulo: S) -> S {
+    S::from_f64(2.0) * modulo - S::ONE
+}
+
+#[inline(always)]
+pub fn ramp_down<S: Sample>(modulo: S) -> S {
+    S::ONE - S::from_f64(2.0) * modulo
+}
+
+#[inline(always)]
+pub fn pulse<S: Sample>(modulo: S, pulse_width: S) -> S {
+    if modulo < pulse_width {
+        S::ONE
+    } else {
+        -S::ONE
+    }
+}
+
+/// Seed of the random LFO shapes, shared by all engines so they render the
+/// same patch.
+pub const LFO_RANDOM_SEED: u32 = 1;
+
+/// Random values in `[-1, 1]` for the random LFO shapes, stepped once per
+/// cycle. The sequence only depends on the seed, so every engine produces the
+/// same values as long as it advances on the same wrap events.
+#[derive(Copy, Clone, Debug)]
+pub struct RandomSteps {
+    state: u32,
+    previous: f64,
+    current: f64,
+}
+
+impl RandomSteps {
+    pub fn new(seed: u32) -> RandomSteps {
+        let mut steps = RandomSteps {
+            state: seed,
+            previous: 0.0,
+            current: 0.0,
+        };
+        steps.advance();
+        steps.advance();
+        steps
+    }
+
+    /// Move on to the next random value. Call this whenever the LFO wraps.
+    #[inline(always)]
+    pub fn advance(&mut self) {
+        // Numerical Recipes LCG, using the better distributed upper bits.
+        self.state = self.state.wrapping_mul(1664525).wrapping_add(1013904223);
+        self.previous = self.current;
+        self.current = (self.state >> 8) as f64 / (1 << 23) as f64 - 1.0;
+    }
+
+    /// The value of the current cycle.
+    #[inline(always)]
+    pub fn held(&self) -> f64 {
+        self.current
+    }
+
+    /// Glide from the previous cycle's value to the current one as `modulo`
+    /// goes from 0 to 1, easing in and out so the slope is continuous too.
+    #[inline(always)]
+    pub fn smoothed(&self, modulo: f64) -> f64 {
+        let t = modulo * modulo * (3.0 - 2.0 * modulo);
+        self.previous + t * (self.current - self.previous)
+    }
+}
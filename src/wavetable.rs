@@ -0,0 +1,143 @@
+//! Mip-mapped wavetables.
+//!
+//! A wavetable holds one or more single-cycle frames. Each frame is resampled
+//! into `TABLE_SIZE` samples at several band-limited levels, one per octave:
+//! level `k` only contains the harmonics up to `TABLE_SIZE / 2 >> k`. When
+//! sampling, the phase increment picks the two adjacent levels that can be
+//! played without aliasing and crossfades between them, and the table
+//! position crossfades between adjacent frames. Tables are built in `f64`
+//! and sampled in the engine's `Sample` type.
+
+use std::f64::consts::PI;
+
+use crate::sample::Sample;
+
+pub const TABLE_SIZE: usize = 2048;
+
+/// One level per octave, down to a single harmonic.
+const LEVELS: usize = 11;
+
+/// Stride between tables, including a guard sample for interpolation.
+const TABLE_STRIDE: usize = TABLE_SIZE + 1;
+
+pub struct Wavetable {
+    frame_count: usize,
+    data: Vec<f64>,
+}
+
+impl Wavetable {
+    /// Build a wavetable from a single-cycle waveform of any length.
+    pub fn from_single_cycle(cycle: &[f64]) -> Wavetable {
+        Wavetable::from_frames(&[cycle])
+    }
+
+    /// Build a morphable wavetable. Every frame is one cycle, and frames may
+    /// have different lengths.
+    pub fn from_frames(frames: &[&[f64]]) -> Wavetable {
+        assert!(!frames.is_empty(), "a wavetable needs at least one frame");
+
+        let mut data = Vec::with_capacity(frames.len() * LEVELS * TABLE_STRIDE);
+
+        let cos_table: Vec<f64> = (0..TABLE_SIZE)
+            .map(|i| (2.0 * PI * i as f64 / TABLE_SIZE as f64).cos())
+            .collect();
+        let sin_table: Vec<f64> = (0..TABLE_SIZE)
+            .map(|i| (2.0 * PI * i as f64 / TABLE_SIZE as f64).sin())
+            .collect();
+
+        for frame in frames {
+            assert!(!frame.is_empty(), "wavetable frames can't be empty");
+
+            let harmonics = analyze(frame);
+
+            for level in 0..LEVELS {
+                let max_harmonic = (TABLE_SIZE / 2) >> level;
+                let start = data.len();
+
+                for i in 0..TABLE_SIZE {
+                    let mut sample = harmonics[0].0;
+                    for (h, (cos_amp, sin_amp)) in
+                        harmonics.iter().enumerate().take(max_harmonic + 1).skip(1)
+                    {
+                        let index = (h * i) % TABLE_SIZE;
+                        sample += cos_amp * cos_table[index] + sin_amp * sin_table[index];
+                    }
+                    data.push(sample);
+                }
+
+                data.push(data[start]);
+            }
+        }
+
+        Wavetable {
+            frame_count: frames.len(),
+            data,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Sample the table at `modulo` in `[0, 1]`. `position` in `[0, 1]` morphs
+    /// from the first to the last frame and is clamped to that range.
+    #[inline]
+    pub fn sample<S: Sample>(&self, modulo: S, phase_increment: S, position: S) -> S {
+        let table_size = S::from_f64(TABLE_SIZE as f64);
+
+        let level = (table_size * phase_increment.abs())
+            .log2()
+            .clamp(-S::ONE, S::from_f64((LEVELS - 2) as f64));
+        let level_floor = level.floor();
+        let level_fade = level - level_floor;
+        let level_a = (level_floor + S::ONE).to_usize();
+        let level_b = (level_a + 1).min(LEVELS - 1);
+
+        let frame = position.clamp(S::ZERO, S::ONE) * S::from_f64((self.frame_count - 1) as f64);
+        let frame_floor = frame.floor();
+        let frame_fade = frame - frame_floor;
+        let frame_a = frame_floor.to_usize();
+        let frame_b = (frame_a + 1).min(self.frame_count - 1);
+
+        let index = modulo * table_size;
+        let index_floor = index.floor();
+        let fraction = index - index_floor;
+        let index = index_floor.to_usize() & (TABLE_SIZE - 1);
+
+        let lookup = |frame: usize, level: usize| {
+            let table = &self.data[(frame * LEVELS + level) * TABLE_STRIDE..];
+            let (a, b) = (S::from_f64(table[index]), S::from_f64(table[index + 1]));
+            a + fraction * (b - a)
+        };
+
+        let lookup_frame = |frame: usize| {
+            let a = lookup(frame, level_a);
+            a + level_fade * (lookup(frame, level_b) - a)
+        };
+
+        let a = lookup_frame(frame_a);
+        a + frame_fade * (lookup_frame(frame_b) - a)
+    }
+}
+
+/// Cosine and sine amplitudes of each harmonic up to `TABLE_SIZE / 2`.
+fn analyze(cycle: &[f64]) -> Vec<(f64, f64)> {
+    let len = cycle.len();
+    let max_harmonic = (len / 2).min(TABLE_SIZE / 2);
+
+    let mut harmonics = vec![(0.0, 0.0); TABLE_SIZE / 2 + 1];
+
+    for (h, harmonic) in harmonics.iter_mut().enumerate().take(max_harmonic + 1) {
+        let (mut cos_sum, mut sin_sum) = (0.0, 0.0);
+        for (i, x) in cycle.iter().enumerate() {
+            let angle = 2.0 * PI * ((h * i) % len) as f64 / len as f64;
+            cos_sum += x * angle.cos();
+            sin_sum += x * angle.sin();
+        }
+
+        let scale = if h == 0 || 2 * h == len { 1.0 } else { 2.0 } / len as f64;
+        *harmonic = (cos_sum * scale, sin_sum * scale);
+    }
+
+    harmonics
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use common::SAMPLE_RATE;
+use dsp_perf::biquad::{BiquadBank, BiquadCoefficients, BiquadDesign, BiquadKind, RAMP_FRAMES};
+use dsp_perf::pitch::Hz;
+
+fn design(kind: BiquadKind, gain_db: f64) -> BiquadDesign {
+    BiquadDesign {
+        kind,
+        frequency: Hz(1000.0),
+        q: FRAC_1_SQRT_2,
+        gain_db,
+    }
+}
+
+fn db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+fn assert_response(design: BiquadDesign, frequency: f64, expected_db: f64, tolerance_db: f64) {
+    let coefficients = BiquadCoefficients::new(&design, SAMPLE_RATE);
+    let actual = db(coefficients.magnitude(Hz(frequency), SAMPLE_RATE));
+    assert!(
+        (actual - expected_db).abs() < tolerance_db,
+        "{:?} at {} Hz: expected {} dB, got {} dB",
+        design.kind,
+        frequency,
+        expected_db,
+        actual
+    );
+}
+
+#[test]
+fn pass_filters_have_the_cookbook_response() {
+    let low_pass = design(BiquadKind::LowPass, 0.0);
+    assert_response(low_pass, 20.0, 0.0, 0.01);
+    assert_response(low_pass, 1000.0, -3.01, 0.01);
+    assert_response(low_pass, 10000.0, -43.3, 0.1);
+
+    let high_pass = design(BiquadKind::HighPass, 0.0);
+    assert_response(high_pass, 100.0, -40.0, 1.0);
+    assert_response(high_pass, 1000.0, -3.01, 0.01);
+    assert_response(high_pass, 20000.0, 0.0, 0.01);
+
+    let band_pass = design(BiquadKind::BandPass, 0.0);
+    assert_response(band_pass, 1000.0, 0.0, 1e-6);
+    assert_response(band_pass, 100.0, -17.0, 0.1);
+    assert_response(band_pass, 10000.0, -18.6, 0.1);
+
+    for &frequency in [20.0, 500.0, 1000.0, 5000.0, 20000.0].iter() {
+        assert_response(design(BiquadKind::AllPass, 0.0), frequency, 0.0, 1e-9);
+    }
+}
+
+#[test]
+fn gain_filters_boost_and_cut() {
+    for &gain_db in [-12.0, 6.0, 12.0].iter() {
+        let peaking = design(BiquadKind::Peaking, gain_db);
+        assert_response(peaking, 1000.0, gain_db, 1e-6);
+        assert_response(peaking, 20.0, 0.0, 0.1);
+        assert_response(peaking, 20000.0, 0.0, 0.1);
+
+        let low_shelf = design(BiquadKind::LowShelf, gain_db);
+        assert_response(low_shelf, 20.0, gain_db, 0.1);
+        assert_response(low_shelf, 1000.0, gain_db / 2.0, 1e-6);
+        assert_response(low_shelf, 20000.0, 0.0, 0.1);
+
+        let high_shelf = design(BiquadKind::HighShelf, gain_db);
+        assert_response(high_shelf, 20.0, 0.0, 0.1);
+        assert_response(high_shelf, 1000.0, gain_db / 2.0, 1e-6);
+        assert_response(high_shelf, 20000.0, gain_db, 0.1);
+    }
+}
+
+/// Steady-state gain of the bank for a sine at `frequency`, rendered in
+/// blocks of 256 frames.
+fn measured_gain(bank: &mut BiquadBank, frequency: f64) -> f64 {
+    let frames = SAMPLE_RATE as usize;
+    let mut buffer: Vec<f64> = (0..frames)
+        .map(|i| (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin())
+        .collect();
+
+    for block in buffer.chunks_mut(256) {
+        bank.render(block);
+    }
+
+    buffer[frames / 2..]
+        .iter()
+        .fold(0.0, |peak: f64, x| peak.max(x.abs()))
+}
+
+#[test]
+fn bank_runs_sections_in_series() {
+    let sections = [
+        design(BiquadKind::Peaking, 6.0),
+        BiquadDesign {
+            frequency: Hz(200.0),
+            ..design(BiquadKind::HighPass, 0.0)
+        },
+        BiquadDesign {
+            frequency: Hz(5000.0),
+            ..design(BiquadKind::HighShelf, -6.0)
+        },
+    ];
+
+    for &frequency in [100.0, 1000.0, 8000.0].iter() {
+        let mut bank = BiquadBank::new(SAMPLE_RATE);
+        let mut expected = 1.0;
+        for section in sections.iter() {
+            bank.push(*section);
+            expected *=
+                BiquadCoefficients::new(section, SAMPLE_RATE).magnitude(Hz(frequency), SAMPLE_RATE);
+        }
+        assert_eq!(3, bank.len());
+
+        let actual = measured_gain(&mut bank, frequency);
+        assert!(
+            (actual / expected - 1.0).abs() < 0.01,
+            "at {} Hz: expected {}, got {}",
+            frequency,
+            expected,
+            actual
+        );
+    }
+}
+
+#[test]
+fn design_changes_ramp_instead_of_jumping() {
+    let frequency = 1000.0;
+    let input: Vec<f64> = (0..1024)
+        .map(|i| (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin())
+        .collect();
+
+    let render = |change: bool| {
+        let mut bank = BiquadBank::new(SAMPLE_RATE);
+        bank.push(design(BiquadKind::Peaking, -24.0));
+
+        let mut output = input.clone();
+        bank.render(&mut output[..512]);
+        if change {
+            bank.set_design(0, design(BiquadKind::Peaking, 24.0));
+        }
+        bank.render(&mut output[512..]);
+        output
+    };
+
+    let unchanged = render(false);
+    let changed = render(true);
+
+    // Right after the change the output carries on almost as before, instead
+    // of jumping by 48 dB.
+    for frame in 512..515 {
+        let difference = (changed[frame] - unchanged[frame]).abs();
+        assert!(difference < 0.005, "frame {}: {}", frame, difference);
+    }
+
+    // By the end of the ramp the level is close to the new design's.
+    let peak = |output: &[f64]| output.iter().fold(0.0, |peak: f64, x| peak.max(x.abs()));
+    let boost = 10f64.powf(24.0 / 20.0);
+    assert!(peak(&unchanged[1024 - 64..]) < 0.1);
+    assert!(peak(&changed[1024 - 64..]) > 0.5 * boost);
+}
+
+#[test]
+fn ramps_do_not_depend_on_the_block_size() {
+    let frequency = 1000.0;
+    let input: Vec<f64> = (0..4 * RAMP_FRAMES)
+        .map(|i| (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin())
+        .collect();
+
+    let render = |block_size: usize| {
+        let mut bank = BiquadBank::new(SAMPLE_RATE);
+        bank.push(design(BiquadKind::Peaking, -24.0));
+
+        // Change the design, then change it again partway through the ramp.
+        let mut output = input.clone();
+        let (before, after) = output.split_at_mut(RAMP_FRAMES + RAMP_FRAMES / 2);
+        bank.render(&mut before[..RAMP_FRAMES]);
+        bank.set_design(0, design(BiquadKind::Peaking, 24.0));
+        for block in before[RAMP_FRAMES..].chunks_mut(block_size) {
+            bank.render(block);
+        }
+        bank.set_design(0, design(BiquadKind::Peaking, 0.0));
+        for block in after.chunks_mut(block_size) {
+            bank.render(block);
+        }
+        output
+    };
+
+    assert_eq!(render(1), render(4));
+    assert_eq!(render(1), render(RAMP_FRAMES));
+}
+
+#[test]
+fn unchanged_design_does_not_ramp() {
+    let mut changed = BiquadBank::new(SAMPLE_RATE);
+    let mut unchanged = BiquadBank::new(SAMPLE_RATE);
+    changed.push(design(BiquadKind::LowPass, 0.0));
+    unchanged.push(design(BiquadKind::LowPass, 0.0));
+
+    changed.set_design(0, design(BiquadKind::LowPass, 0.0));
+
+    assert_eq!(
+        measured_gain(&mut unchanged, 3000.0),
+        measured_gain(&mut changed, 3000.0)
+    );
+}
@@ -0,0 +1,94 @@
+#![allow(dead_code)]
+
+use dsp_perf::SynthEngine;
+
+pub const SAMPLE_RATE: f64 = 44100.0;
+
+/// Render `frames` frames into a new buffer, calling `render` with buffers of
+/// `block_size` frames (and a shorter final block if needed).
+pub fn render_in_blocks(synth: &mut dyn SynthEngine, frames: usize, block_size: usize) -> Vec<f64> {
+    let mut output = vec![0.0; frames];
+    for block in output.chunks_mut(block_size) {
+        synth.render(block);
+    }
+    output
+}
+
+/// Render `frames` frames, cycling through `block_sizes` for successive calls.
+pub fn render_in_varying_blocks(
+    synth: &mut dyn SynthEngine,
+    frames: usize,
+    block_sizes: &[usize],
+) -> Vec<f64> {
+    let mut output = vec![0.0; frames];
+    let mut position = 0;
+    for block_size in block_sizes.iter().cycle() {
+        if position == frames {
+            break;
+        }
+        let end = (position + block_size).min(frames);
+        synth.render(&mut output[position..end]);
+        position = end;
+    }
+    output
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub first_frame: usize,
+    pub first_expected: f64,
+    pub first_actual: f64,
+    pub max_error: f64,
+    pub max_error_frame: usize,
+}
+
+/// Compare two renders sample by sample, returning `None` if every frame is
+/// within `tolerance`.
+pub fn compare(expected: &[f64], actual: &[f64], tolerance: f64) -> Option<Divergence> {
+    assert_eq!(expected.len(), actual.len(), "render lengths differ");
+
+    let mut divergence: Option<Divergence> = None;
+
+    for (frame, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+        let error = (expected - actual).abs();
+        if error <= tolerance {
+            continue;
+        }
+
+        match divergence {
+            Some(ref mut divergence) => {
+                if error > divergence.max_error {
+                    divergence.max_error = error;
+                    divergence.max_error_frame = frame;
+                }
+            }
+            None => {
+                divergence = Some(Divergence {
+                    first_frame: frame,
+                    first_expected: *expected,
+                    first_actual: *actual,
+                    max_error: error,
+                    max_error_frame: frame,
+                })
+            }
+        }
+    }
+
+    divergence
+}
+
+pub fn assert_renders_match(name: &str, expected: &[f64], actual: &[f64], tolerance: f64) {
+    if let Some(d) = compare(expected, actual, tolerance) {
+        panic!(
+            "{}: first diverging frame {} (expected {}, got {}), \
+             max error {:e} at frame {} (tolerance {:e})",
+            name,
+            d.first_frame,
+            d.first_expected,
+            d.first_actual,
+            d.max_error,
+            d.max_error_frame,
+            tolerance
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use dsp_perf::envelope::{
+    AdsrSegments, AdsrSettings, AdsrState, Breakpoint, BreakpointSettings, BreakpointStages,
+    BreakpointState, Curve, Stage, TriggerMode,
+};
+
+const SAMPLE_RATE: f64 = 1000.0;
+
+fn settings(curve: Curve) -> AdsrSettings {
+    AdsrSettings {
+        attack: 0.01,
+        decay: 0.02,
+        sustain: 0.5,
+        release: 0.04,
+        curve,
+        trigger_mode: TriggerMode::Retrigger,
+    }
+}
+
+/// Step until the stage changes, returning the levels on the way.
+fn run_stage(state: &mut AdsrState, segments: &AdsrSegments) -> Vec<f64> {
+    let stage = state.stage;
+    let mut levels = Vec::new();
+    while state.stage == stage {
+        levels.push(state.next(segments));
+        assert!(levels.len() < 1000, "stuck in {:?}", stage);
+    }
+    levels
+}
+
+/// Stage lengths may be off by one frame from rounding in the recurrence.
+fn assert_frames(expected: usize, levels: &[f64], curve: Curve) {
+    assert!(
+        (levels.len() as isize - expected as isize).abs() <= 1,
+        "{:?}: expected {} frames, took {}",
+        curve,
+        expected,
+        levels.len()
+    );
+}
+
+#[test]
+fn stages_take_their_set_time() {
+    for &curve in [Curve::Linear, Curve::Exponential].iter() {
+        let segments = AdsrSegments::new(&settings(curve), SAMPLE_RATE);
+        let mut state = AdsrState::default();
+
+        assert_eq!(0.0, state.next(&segments));
+
+        state.note_on(&segments);
+        let attack = run_stage(&mut state, &segments);
+        assert_frames(10, &attack, curve);
+        assert_eq!(1.0, *attack.last().unwrap());
+        assert!(attack.windows(2).all(|w| w[1] > w[0]));
+
+        let decay = run_stage(&mut state, &segments);
+        assert_frames(20, &decay, curve);
+        assert_eq!(0.5, *decay.last().unwrap());
+        assert!(decay.windows(2).all(|w| w[1] < w[0]));
+
+        assert_eq!(Stage::Sustain, state.stage);
+        for _ in 0..100 {
+            assert_eq!(0.5, state.next(&segments));
+        }
+
+        // Releases are timed from full level, so half the level takes half
+        // the time when linear.
+        state.note_off();
+        let release = run_stage(&mut state, &segments);
+        assert_eq!(0.0, *release.last().unwrap());
+        assert!(release.windows(2).all(|w| w[1] < w[0]));
+        if curve == Curve::Linear {
+            assert_frames(20, &release, curve);
+        }
+
+        assert_eq!(Stage::Idle, state.stage);
+        assert_eq!(0.0, state.next(&segments));
+    }
+}
+
+#[test]
+fn exponential_segments_are_curved() {
+    let segments = AdsrSegments::new(&settings(Curve::Exponential), SAMPLE_RATE);
+    let mut state = AdsrState::default();
+    state.note_on(&segments);
+
+    // The attack rises fastest at the start, the decay falls fastest at the
+    // start.
+    let attack = run_stage(&mut state, &segments);
+    let n = attack.len();
+    assert!(attack[1] - attack[0] > attack[n - 1] - attack[n - 2]);
+
+    let decay = run_stage(&mut state, &segments);
+    let n = decay.len();
+    assert!(decay[0] - decay[1] > decay[n - 2] - decay[n - 1]);
+}
+
+#[test]
+fn retrigger_restarts_the_attack_from_the_current_level() {
+    let segments = AdsrSegments::new(&settings(Curve::Linear), SAMPLE_RATE);
+    let mut state = AdsrState::default();
+    state.note_on(&segments);
+    run_stage(&mut state, &segments);
+    run_stage(&mut state, &segments);
+
+    state.note_on(&segments);
+    assert_eq!(Stage::Attack, state.stage);
+    assert!((state.next(&segments) - 0.6).abs() < 1e-12);
+}
+
+#[test]
+fn legato_notes_keep_the_envelope_going() {
+    let mut legato = settings(Curve::Linear);
+    legato.trigger_mode = TriggerMode::Legato;
+    let segments = AdsrSegments::new(&legato, SAMPLE_RATE);
+
+    let mut state = AdsrState::default();
+    state.note_on(&segments);
+    run_stage(&mut state, &segments);
+
+    state.note_on(&segments);
+    assert_eq!(Stage::Decay, state.stage);
+
+    // Once released, the next note starts a new attack.
+    state.note_off();
+    state.note_on(&segments);
+    assert_eq!(Stage::Attack, state.stage);
+}
+
+#[test]
+fn flat_decay_goes_straight_to_sustain() {
+    for &curve in [Curve::Linear, Curve::Exponential].iter() {
+        let mut full_sustain = settings(curve);
+        full_sustain.sustain = 1.0;
+        let segments = AdsrSegments::new(&full_sustain, SAMPLE_RATE);
+
+        let mut state = AdsrState::default();
+        state.note_on(&segments);
+        run_stage(&mut state, &segments);
+
+        assert_eq!(1.0, state.next(&segments));
+        assert_eq!(Stage::Sustain, state.stage);
+    }
+}
+
+fn breakpoint(level: f64, time: f64, curve: Curve) -> Breakpoint {
+    Breakpoint { level, time, curve }
+}
+
+fn render(state: &mut BreakpointState, stages: &BreakpointStages, frames: usize) -> Vec<f64> {
+    (0..frames).map(|_| state.next(stages)).collect()
+}
+
+#[test]
+fn breakpoints_are_reached_on_time() {
+    let settings = BreakpointSettings {
+        stages: vec![
+            breakpoint(1.0, 0.004, Curve::Linear),
+            breakpoint(1.0, 0.003, Curve::Linear),
+            breakpoint(-0.5, 0.005, Curve::Exponential),
+        ],
+        ..BreakpointSettings::default()
+    };
+    let stages = BreakpointStages::new(&settings, SAMPLE_RATE);
+
+    let mut state = BreakpointState::default();
+    assert_eq!(vec![0.0; 3], render(&mut state, &stages, 3));
+
+    state.note_on(&stages);
+    let levels = render(&mut state, &stages, 15);
+
+    assert_eq!(
+        vec![0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0],
+        levels[..7].to_vec()
+    );
+    assert!(levels[7..11].windows(2).all(|w| w[1] < w[0]));
+    assert!(levels[7] - levels[8] > levels[9] - levels[10]);
+    assert_eq!(vec![-0.5; 4], levels[11..].to_vec());
+}
+
+#[test]
+fn sustain_point_holds_until_release() {
+    let settings = BreakpointSettings {
+        stages: vec![
+            breakpoint(1.0, 0.002, Curve::Linear),
+            breakpoint(0.5, 0.002, Curve::Linear),
+            breakpoint(0.0, 0.002, Curve::Linear),
+        ],
+        sustain: Some(1),
+        ..BreakpointSettings::default()
+    };
+    let stages = BreakpointStages::new(&settings, SAMPLE_RATE);
+
+    let mut state = BreakpointState::default();
+    state.note_on(&stages);
+    assert_eq!(
+        vec![0.5, 1.0, 0.75, 0.5, 0.5, 0.5],
+        render(&mut state, &stages, 6)
+    );
+    assert!(state.is_steady());
+
+    state.note_off(&stages);
+    assert_eq!(vec![0.25, 0.0, 0.0], render(&mut state, &stages, 3));
+}
+
+#[test]
+fn loop_repeats_while_held() {
+    let settings = BreakpointSettings {
+        stages: vec![
+            breakpoint(1.0, 0.001, Curve::Linear),
+            breakpoint(0.0, 0.002, Curve::Linear),
+            breakpoint(1.0, 0.002, Curve::Linear),
+            breakpoint(-1.0, 0.001, Curve::Linear),
+        ],
+        loop_stages: Some(1..3),
+        ..BreakpointSettings::default()
+    };
+    let stages = BreakpointStages::new(&settings, SAMPLE_RATE);
+
+    let mut state = BreakpointState::default();
+    state.note_on(&stages);
+    assert_eq!(
+        vec![1.0, 0.5, 0.0, 0.5, 1.0, 0.5, 0.0, 0.5, 1.0],
+        render(&mut state, &stages, 9)
+    );
+
+    // Once released, it plays the rest of the loop once more and carries on
+    // after it.
+    state.note_off(&stages);
+    assert_eq!(
+        vec![0.5, 0.0, 0.5, 1.0, -1.0, -1.0],
+        render(&mut state, &stages, 6)
+    );
+}
+
+#[test]
+fn invalid_sustain_and_loop_points_are_ignored() {
+    let settings = BreakpointSettings {
+        stages: vec![breakpoint(1.0, 0.001, Curve::Linear)],
+        sustain: Some(3),
+        loop_stages: Some(0..2),
+    };
+    let stages = BreakpointStages::new(&settings, SAMPLE_RATE);
+
+    let mut state = BreakpointState::default();
+    state.note_on(&stages);
+    assert_eq!(vec![1.0, 1.0], render(&mut state, &stages, 2));
+    assert!(state.is_steady());
+}
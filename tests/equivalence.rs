@@ -0,0 +1,868 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{
+    assert_renders_match, compare, render_in_blocks, render_in_varying_blocks, SAMPLE_RATE,
+};
+use dsp_perf::engine::Oscillator;
+use dsp_perf::envelope::{AdsrSettings, Breakpoint, BreakpointSettings, Curve, TriggerMode};
+use dsp_perf::filter::{FilterMode, FilterModel, FilterSettings, Oversampling};
+use dsp_perf::modulation::{ModDestination, ModSource};
+use dsp_perf::params::ParamId;
+use dsp_perf::pitch::{Cents, Hz, Octaves, Semitones};
+use dsp_perf::smoothing::Smoothing;
+use dsp_perf::waveform::{LfoMode, LfoWaveform, Waveform};
+use dsp_perf::wavetable::Wavetable;
+use dsp_perf::{array_of_structs, fixed_batch_size, one_frame_per_call, SynthEngine, ENGINES};
+
+/// All engines run the same operations in the same order per frame, so in
+/// practice they agree bit for bit. The tolerance leaves room for an engine to
+/// reorder floating point operations (e.g. when vectorising) while still being
+/// far below the error caused by any phase or pitch mismatch, which shows up
+/// within a few frames at the 1e-3 level or above.
+const TOLERANCE: f64 = 1e-9;
+
+/// Two seconds, i.e. one full period of the 0.5 Hz LFO.
+const FRAMES: usize = 88200;
+
+const BLOCK_SIZES: &[usize] = &[1, 7, 16, 63, 64, 65, 100, 128, 441, 512, 1000, 4096];
+
+fn reference() -> Vec<f64> {
+    let mut synth = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    render_in_blocks(&mut synth, FRAMES, FRAMES)
+}
+
+#[test]
+fn engines_match_reference_for_fixed_block_sizes() {
+    let expected = reference();
+
+    for engine in ENGINES {
+        for &block_size in BLOCK_SIZES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            let actual = render_in_blocks(&mut *synth, FRAMES, block_size);
+
+            assert_renders_match(
+                &format!("{}, {} frame blocks", engine.name, block_size),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_match_reference_for_varying_block_sizes() {
+    let expected = reference();
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, varying blocks", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+fn boxed<E: SynthEngine + 'static>() -> Box<dyn SynthEngine> {
+    Box::new(E::new(SAMPLE_RATE))
+}
+
+fn batch_size_variants() -> Vec<(&'static str, Box<dyn SynthEngine>)> {
+    vec![
+        ("SoA, 16 frames", boxed::<fixed_batch_size::Synth<16>>()),
+        ("SoA, 32 frames", boxed::<fixed_batch_size::Synth<32>>()),
+        ("SoA, 128 frames", boxed::<fixed_batch_size::Synth<128>>()),
+        ("SoA, 256 frames", boxed::<fixed_batch_size::Synth<256>>()),
+        ("AoS, 16 frames", boxed::<array_of_structs::Synth<16>>()),
+        ("AoS, 32 frames", boxed::<array_of_structs::Synth<32>>()),
+        ("AoS, 128 frames", boxed::<array_of_structs::Synth<128>>()),
+        ("AoS, 256 frames", boxed::<array_of_structs::Synth<256>>()),
+    ]
+}
+
+#[test]
+fn batch_sizes_match_reference() {
+    let expected = reference();
+
+    for (name, mut synth) in batch_size_variants() {
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+        assert_renders_match(name, &expected, &actual, TOLERANCE);
+    }
+}
+
+#[test]
+fn reset_restarts_the_render() {
+    let expected = reference();
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        render_in_blocks(&mut *synth, 1000, 100);
+        synth.reset();
+
+        assert_eq!(synth.sample_rate(), SAMPLE_RATE);
+
+        let actual = render_in_blocks(&mut *synth, FRAMES, 441);
+        assert_renders_match(
+            &format!("{}, after reset", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_with_wavetable_loaded() {
+    let saw: Vec<f64> = (0..1000).map(|i| 2.0 * i as f64 / 1000.0 - 1.0).collect();
+    let square: Vec<f64> = (0..1000)
+        .map(|i| if i < 500 { 1.0 } else { -1.0 })
+        .collect();
+    let wavetable = Arc::new(Wavetable::from_frames(&[&saw, &square]));
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    reference.set_wavetable(Some(wavetable.clone()));
+    let expected = render_in_blocks(&mut reference, FRAMES, FRAMES);
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_wavetable(Some(wavetable.clone()));
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, with wavetable", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_with_moving_wavetable_position() {
+    let saw: Vec<f64> = (0..1000).map(|i| 2.0 * i as f64 / 1000.0 - 1.0).collect();
+    let sine: Vec<f64> = (0..1000)
+        .map(|i| (2.0 * std::f64::consts::PI * i as f64 / 1000.0).sin())
+        .collect();
+    let wavetable = Arc::new(Wavetable::from_frames(&[&saw, &sine, &saw]));
+
+    // Sweep the position across the table and back, a step per 441 frames.
+    let render = |synth: &mut dyn SynthEngine, block_sizes: &[usize]| {
+        synth.set_wavetable(Some(wavetable.clone()));
+        let mut output = Vec::new();
+        for step in 0..100 {
+            synth.set_wavetable_position(1.0 - (step as f64 / 50.0 - 1.0).abs());
+            output.extend(render_in_varying_blocks(synth, 441, block_sizes));
+        }
+        output
+    };
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    let expected = render(&mut reference, &[441]);
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let actual = render(&mut *synth, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, with moving wavetable position", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_with_hard_sync() {
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    reference.set_hard_sync(true);
+    let expected = render_in_blocks(&mut reference, FRAMES, FRAMES);
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_hard_sync(true);
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, with hard sync", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_with_quadrature_lfo_on_osc2() {
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    let in_phase = render_in_blocks(&mut reference, FRAMES, FRAMES);
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    reference.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+    let expected = render_in_blocks(&mut reference, FRAMES, FRAMES);
+
+    assert!(
+        compare(&in_phase, &expected, TOLERANCE).is_some(),
+        "the quadrature output must change the patch"
+    );
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, with quadrature LFO on osc2", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_for_every_lfo_waveform() {
+    // A fast LFO, so the random shapes step many times, driving osc2 through
+    // the quadrature output so both LFO outputs are compared.
+    let configure = |synth: &mut dyn SynthEngine, waveform| {
+        synth.set_lfo_frequency(Hz(13.0));
+        synth.set_lfo_waveform(waveform);
+        synth.set_lfo_pulse_width(0.3);
+        synth.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+    };
+
+    let frames = FRAMES / 4;
+
+    for &waveform in [
+        LfoWaveform::Sine,
+        LfoWaveform::Triangle,
+        LfoWaveform::RampUp,
+        LfoWaveform::RampDown,
+        LfoWaveform::Square,
+        LfoWaveform::SampleAndHold,
+        LfoWaveform::SmoothRandom,
+    ]
+    .iter()
+    {
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        configure(&mut reference, waveform);
+        let expected = render_in_blocks(&mut reference, frames, frames);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            configure(&mut *synth, waveform);
+
+            // Rendering twice checks that reset restarts the random sequence.
+            render_in_blocks(&mut *synth, frames / 3, 100);
+            synth.reset();
+            let actual = render_in_varying_blocks(&mut *synth, frames, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with {:?} LFO", engine.name, waveform),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_match_for_every_waveform() {
+    // A high note, so the corrections cover a good share of every cycle, and
+    // pulse widths on either side of the default.
+    let configure = |synth: &mut dyn SynthEngine, waveform, pulse_width| {
+        synth.set_parameter(ParamId::Frequency, 1800.0);
+        synth.set_osc_waveform(Oscillator::Osc1, waveform);
+        synth.set_osc_waveform(Oscillator::Osc2, waveform);
+        synth.set_osc_pulse_width(Oscillator::Osc1, pulse_width);
+        synth.set_osc_pulse_width(Oscillator::Osc2, 1.0 - pulse_width);
+    };
+
+    let frames = FRAMES / 4;
+
+    for &waveform in [
+        Waveform::Sine,
+        Waveform::Saw,
+        Waveform::Square,
+        Waveform::Triangle,
+    ]
+    .iter()
+    {
+        for &pulse_width in [0.5, 0.2].iter() {
+            let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+            configure(&mut reference, waveform, pulse_width);
+            let expected = render_in_blocks(&mut reference, frames, frames);
+
+            for engine in ENGINES {
+                let mut synth = (engine.create)(SAMPLE_RATE);
+                configure(&mut *synth, waveform, pulse_width);
+                let actual = render_in_varying_blocks(&mut *synth, frames, BLOCK_SIZES);
+
+                assert_renders_match(
+                    &format!(
+                        "{}, with {:?} at pulse width {}",
+                        engine.name, waveform, pulse_width
+                    ),
+                    &expected,
+                    &actual,
+                    TOLERANCE,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn engines_match_for_every_lfo_mode() {
+    let configure = |synth: &mut dyn SynthEngine, mode| {
+        synth.set_lfo_frequency(Hz(7.0));
+        synth.set_lfo_waveform(LfoWaveform::RampUp);
+        synth.set_lfo_mode(mode);
+        synth.set_lfo_delay(0.05, 0.1);
+        synth.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+    };
+
+    // Notes start at these frames, which are not on batch boundaries.
+    let notes = [0, 5000, 13001, FRAMES / 4];
+
+    let render = |synth: &mut dyn SynthEngine, block_sizes: &[usize]| {
+        let mut output = Vec::new();
+        for note in notes.windows(2) {
+            let frames = note[1] - note[0];
+            synth.note_on();
+            output.extend(render_in_varying_blocks(synth, frames, block_sizes));
+        }
+        output
+    };
+
+    for &mode in [LfoMode::FreeRunning, LfoMode::Retrigger, LfoMode::OneShot].iter() {
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        configure(&mut reference, mode);
+        let expected = render(&mut reference, &[FRAMES]);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            configure(&mut *synth, mode);
+            let actual = render(&mut *synth, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with {:?} LFO", engine.name, mode),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+/// Note on and off events, at frames that are not on batch boundaries. Notes
+/// overlap, end during the attack, and retrigger during the release.
+const NOTE_EVENTS: &[(usize, bool)] = &[
+    (0, true),
+    (3000, true),
+    (9001, false),
+    (9500, true),
+    (9700, false),
+    (15000, true),
+    (16000, false),
+    (FRAMES / 4, false),
+];
+
+fn render_notes(synth: &mut dyn SynthEngine, block_sizes: &[usize]) -> Vec<f64> {
+    let mut output = Vec::new();
+    for event in NOTE_EVENTS.windows(2) {
+        let ((start, note_on), (end, _)) = (event[0], event[1]);
+        if note_on {
+            synth.note_on();
+        } else {
+            synth.note_off();
+        }
+        output.extend(render_in_varying_blocks(synth, end - start, block_sizes));
+    }
+    output
+}
+
+#[test]
+fn engines_match_with_amp_envelope() {
+    let settings = [
+        AdsrSettings::default(),
+        AdsrSettings {
+            attack: 0.03,
+            decay: 0.05,
+            sustain: 0.3,
+            release: 0.02,
+            curve: Curve::Exponential,
+            trigger_mode: TriggerMode::Legato,
+        },
+    ];
+
+    for settings in settings.iter() {
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        reference.set_amp_envelope(Some(*settings));
+        let expected = render_notes(&mut reference, &[FRAMES]);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            synth.set_amp_envelope(Some(*settings));
+            let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with {:?}", engine.name, settings),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn disabling_the_amp_envelope_restores_full_level() {
+    for engine in ENGINES {
+        let mut expected_synth = (engine.create)(SAMPLE_RATE);
+        let expected = render_in_blocks(&mut *expected_synth, 4096, 512);
+
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_amp_envelope(Some(AdsrSettings::default()));
+        synth.note_on();
+        render_in_blocks(&mut *synth, 1000, 100);
+        synth.set_amp_envelope(None);
+        synth.reset();
+        let actual = render_in_blocks(&mut *synth, 4096, 512);
+
+        assert_renders_match(engine.name, &expected, &actual, TOLERANCE);
+    }
+}
+
+#[test]
+fn engines_match_with_mod_envelope() {
+    let settings = BreakpointSettings {
+        stages: vec![
+            Breakpoint {
+                level: 1.0,
+                time: 0.01,
+                curve: Curve::Linear,
+            },
+            Breakpoint {
+                level: 0.2,
+                time: 0.03,
+                curve: Curve::Exponential,
+            },
+            Breakpoint {
+                level: 0.6,
+                time: 0.02,
+                curve: Curve::Exponential,
+            },
+            Breakpoint {
+                level: 0.0,
+                time: 0.05,
+                curve: Curve::Linear,
+            },
+        ],
+        sustain: Some(3),
+        loop_stages: Some(1..3),
+    };
+
+    for &destination in [
+        ModDestination::Frequency,
+        ModDestination::Phase,
+        ModDestination::Amplitude,
+    ]
+    .iter()
+    {
+        for &amp_envelope in [false, true].iter() {
+            let configure = |synth: &mut dyn SynthEngine| {
+                synth.set_mod_envelope(Some(&settings), destination);
+                if amp_envelope {
+                    synth.set_amp_envelope(Some(AdsrSettings::default()));
+                }
+            };
+
+            let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+            configure(&mut reference);
+            let expected = render_notes(&mut reference, &[FRAMES]);
+
+            for engine in ENGINES {
+                let mut synth = (engine.create)(SAMPLE_RATE);
+                configure(&mut *synth);
+                let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+                assert_renders_match(
+                    &format!(
+                        "{}, with mod envelope on {:?}, amp envelope {}",
+                        engine.name, destination, amp_envelope
+                    ),
+                    &expected,
+                    &actual,
+                    TOLERANCE,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn engines_match_with_mod_envelope_on_wavetable_position() {
+    let saw: Vec<f64> = (0..1000).map(|i| 2.0 * i as f64 / 1000.0 - 1.0).collect();
+    let square: Vec<f64> = (0..1000)
+        .map(|i| if i < 500 { 1.0 } else { -1.0 })
+        .collect();
+    let sine: Vec<f64> = (0..1000)
+        .map(|i| (2.0 * std::f64::consts::PI * i as f64 / 1000.0).sin())
+        .collect();
+    let wavetable = Arc::new(Wavetable::from_frames(&[&saw, &square, &sine]));
+
+    // Sweep the whole table every 40 frames while the note is held, i.e. at
+    // audio rate, then settle halfway.
+    let settings = BreakpointSettings {
+        stages: vec![
+            Breakpoint {
+                level: 1.0,
+                time: 20.0 / SAMPLE_RATE,
+                curve: Curve::Linear,
+            },
+            Breakpoint {
+                level: 0.0,
+                time: 20.0 / SAMPLE_RATE,
+                curve: Curve::Exponential,
+            },
+            Breakpoint {
+                level: 0.5,
+                time: 0.01,
+                curve: Curve::Linear,
+            },
+        ],
+        sustain: None,
+        loop_stages: Some(0..2),
+    };
+
+    let configure = |synth: &mut dyn SynthEngine, modulated: bool| {
+        synth.set_wavetable(Some(wavetable.clone()));
+        synth.set_parameter(ParamId::WavetablePosition, 0.25);
+        if modulated {
+            synth.set_mod_envelope(Some(&settings), ModDestination::WavetablePosition);
+        }
+    };
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    configure(&mut reference, true);
+    let expected = render_notes(&mut reference, &[FRAMES]);
+
+    let mut unmodulated = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    configure(&mut unmodulated, false);
+    let unmodulated = render_notes(&mut unmodulated, &[FRAMES]);
+    assert!(compare(&unmodulated, &expected, 0.1).is_some());
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        configure(&mut *synth, true);
+        let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+        assert_renders_match(
+            &format!("{}, with mod envelope on wavetable position", engine.name),
+            &expected,
+            &actual,
+            TOLERANCE,
+        );
+
+        // Turning the envelope off leaves the position where it's set.
+        synth.set_mod_envelope(None, ModDestination::WavetablePosition);
+        synth.reset();
+        let actual = render_notes(&mut *synth, BLOCK_SIZES);
+        assert_renders_match(
+            &format!("{}, after the mod envelope", engine.name),
+            &unmodulated,
+            &actual,
+            TOLERANCE,
+        );
+    }
+}
+
+#[test]
+fn engines_match_with_modulated_filter() {
+    for &mode in [
+        FilterMode::LowPass,
+        FilterMode::HighPass,
+        FilterMode::BandPass,
+        FilterMode::Notch,
+    ]
+    .iter()
+    {
+        let settings = FilterSettings {
+            model: FilterModel::StateVariable,
+            mode,
+            cutoff: Hz(800.0),
+            resonance: 0.6,
+            cutoff_lfo_amount: Octaves(2.0),
+            cutoff_envelope_amount: Octaves(3.0),
+            cutoff_mod_envelope_amount: Octaves(0.0),
+            resonance_lfo_amount: 0.3,
+        };
+
+        // A fast LFO sweeps the cutoff and resonance at audio rate, on top of
+        // the amp envelope.
+        let configure = |synth: &mut dyn SynthEngine| {
+            synth.set_lfo_frequency(Hz(9.0));
+            synth.set_amp_envelope(Some(AdsrSettings::default()));
+            synth.set_filter(Some(settings));
+        };
+
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        configure(&mut reference);
+        let expected = render_notes(&mut reference, &[FRAMES]);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            configure(&mut *synth);
+            let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with {:?} filter", engine.name, mode),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_match_with_modulated_ladder_filter() {
+    for &oversampling in [Oversampling::Off, Oversampling::X2, Oversampling::X4].iter() {
+        let settings = FilterSettings {
+            model: FilterModel::Ladder(oversampling),
+            cutoff: Hz(800.0),
+            resonance: 0.8,
+            cutoff_lfo_amount: Octaves(2.0),
+            cutoff_envelope_amount: Octaves(3.0),
+            resonance_lfo_amount: 0.3,
+            ..FilterSettings::default()
+        };
+
+        let configure = |synth: &mut dyn SynthEngine| {
+            synth.set_lfo_frequency(Hz(9.0));
+            synth.set_amp_envelope(Some(AdsrSettings::default()));
+            synth.set_filter(Some(settings));
+        };
+
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        configure(&mut reference);
+        let expected = render_notes(&mut reference, &[FRAMES]);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            configure(&mut *synth);
+            let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with ladder filter at {:?}", engine.name, oversampling),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_match_with_mod_envelope_on_filter_cutoff() {
+    let envelope = BreakpointSettings {
+        stages: vec![
+            Breakpoint {
+                level: 1.0,
+                time: 0.005,
+                curve: Curve::Exponential,
+            },
+            Breakpoint {
+                level: -0.5,
+                time: 0.02,
+                curve: Curve::Linear,
+            },
+            Breakpoint {
+                level: 0.0,
+                time: 0.05,
+                curve: Curve::Exponential,
+            },
+        ],
+        sustain: None,
+        loop_stages: Some(0..2),
+    };
+
+    for &model in [
+        FilterModel::StateVariable,
+        FilterModel::Ladder(Oversampling::X2),
+    ]
+    .iter()
+    {
+        let settings = FilterSettings {
+            model,
+            cutoff: Hz(800.0),
+            resonance: 0.6,
+            cutoff_mod_envelope_amount: Octaves(4.0),
+            ..FilterSettings::default()
+        };
+
+        // The envelope drives the filter on its own, with and without a
+        // destination among the oscillators and the other modulation.
+        for &(destination, modulated) in [
+            (ModDestination::WavetablePosition, false),
+            (ModDestination::Frequency, true),
+        ]
+        .iter()
+        {
+            let filter = FilterSettings {
+                cutoff_lfo_amount: Octaves(if modulated { 1.0 } else { 0.0 }),
+                cutoff_envelope_amount: Octaves(if modulated { 2.0 } else { 0.0 }),
+                ..settings
+            };
+            let configure = |synth: &mut dyn SynthEngine| {
+                synth.set_filter(Some(filter));
+                synth.set_mod_envelope(Some(&envelope), destination);
+                if modulated {
+                    synth.set_lfo_frequency(Hz(9.0));
+                    synth.set_amp_envelope(Some(AdsrSettings::default()));
+                }
+            };
+
+            let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+            configure(&mut reference);
+            let expected = render_notes(&mut reference, &[FRAMES]);
+
+            // Without the amount, the filter doesn't follow the envelope.
+            let mut unmodulated = one_frame_per_call::Synth::new(SAMPLE_RATE);
+            configure(&mut unmodulated);
+            unmodulated.set_filter(Some(FilterSettings {
+                cutoff_mod_envelope_amount: Octaves(0.0),
+                ..filter
+            }));
+            let unmodulated = render_notes(&mut unmodulated, &[FRAMES]);
+            assert!(compare(&unmodulated, &expected, 0.01).is_some());
+
+            for engine in ENGINES {
+                let mut synth = (engine.create)(SAMPLE_RATE);
+                configure(&mut *synth);
+                let actual = render_notes(&mut *synth, BLOCK_SIZES);
+
+                assert_renders_match(
+                    &format!(
+                        "{}, with {:?} filter under the mod envelope on {:?}",
+                        engine.name, model, destination
+                    ),
+                    &expected,
+                    &actual,
+                    TOLERANCE,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn engines_match_with_smoothed_parameter_changes() {
+    // Changes part way through a batch, then again before the previous glide
+    // has finished.
+    let automate = |synth: &mut dyn SynthEngine, block_sizes: &[usize]| {
+        let mut output = render_in_varying_blocks(synth, 1000, block_sizes);
+
+        synth.set_pitch_offset(Oscillator::Osc1, Octaves(1.0), Semitones(0.0), Cents(0.0));
+        synth.set_pitch_offset(Oscillator::Osc2, Octaves(0.0), Semitones(-5.0), Cents(7.0));
+        synth.set_amplitude(Oscillator::Osc2, 0.3);
+        synth.set_pitch_offset(Oscillator::Lfo, Octaves(3.0), Semitones(0.0), Cents(0.0));
+        synth.set_amplitude(Oscillator::Lfo, 0.1);
+        output.extend(render_in_varying_blocks(synth, 500, block_sizes));
+
+        synth.set_amplitude(Oscillator::Osc1, 0.0);
+        synth.set_amplitude(Oscillator::Osc2, 1.0);
+        output.extend(render_in_varying_blocks(synth, 4000, block_sizes));
+        output
+    };
+
+    for &smoothing in [
+        Smoothing::Off,
+        Smoothing::Linear(0.02),
+        Smoothing::OnePole(0.01),
+    ]
+    .iter()
+    {
+        let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        reference.set_parameter_smoothing(smoothing);
+        let expected = automate(&mut reference, &[FRAMES]);
+
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            synth.set_parameter_smoothing(smoothing);
+            let actual = automate(&mut *synth, BLOCK_SIZES);
+
+            assert_renders_match(
+                &format!("{}, with {:?}", engine.name, smoothing),
+                &expected,
+                &actual,
+                TOLERANCE,
+            );
+        }
+    }
+}
+
+#[test]
+fn smoothing_removes_jumps_from_parameter_changes() {
+    let max_step = |smoothing| {
+        let mut synth = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        synth.set_parameter_smoothing(smoothing);
+        synth.set_pitch_offset(Oscillator::Osc2, Octaves(-8.0), Semitones(0.0), Cents(0.0));
+
+        // Osc1 at a low pitch, so one frame to the next hardly changes.
+        synth.set_pitch_offset(Oscillator::Osc1, Octaves(-8.0), Semitones(0.0), Cents(0.0));
+        let mut output = render_in_blocks(&mut synth, 2000, 100);
+        synth.set_amplitude(Oscillator::Osc1, 0.0);
+        output.extend(render_in_blocks(&mut synth, 2000, 100));
+
+        output[1000..]
+            .windows(2)
+            .fold(0.0, |step: f64, pair| step.max((pair[1] - pair[0]).abs()))
+    };
+
+    assert!(max_step(Smoothing::Off) > 0.1);
+    assert!(max_step(Smoothing::Linear(0.02)) < 0.01);
+    assert!(max_step(Smoothing::OnePole(0.01)) < 0.01);
+}
+
+#[test]
+fn engines_match_with_a_patch_set_through_parameters() {
+    let patch = [
+        (ParamId::Frequency, 220.0),
+        (ParamId::Osc1Waveform, 1.0),
+        (ParamId::Osc2Waveform, 2.0),
+        (ParamId::Osc2PulseWidth, 0.3),
+        (ParamId::Semitones(Oscillator::Osc2), 7.0),
+        (ParamId::Level(Oscillator::Osc1), 0.6),
+        (ParamId::LfoFrequency, 6.0),
+        (ParamId::LfoWaveform, 1.0),
+        (ParamId::Level(Oscillator::Lfo), 0.05),
+        (ParamId::LfoMode, 1.0),
+    ];
+    let load = |synth: &mut dyn SynthEngine| {
+        for &(id, value) in patch.iter() {
+            synth.set_parameter(id, value);
+        }
+        synth.note_on();
+    };
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    load(&mut reference);
+    let expected = render_in_blocks(&mut reference, FRAMES, FRAMES);
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        load(&mut *synth);
+        let actual = render_in_varying_blocks(&mut *synth, FRAMES, BLOCK_SIZES);
+
+        assert_renders_match(engine.name, &expected, &actual, TOLERANCE);
+    }
+}
@@ -0,0 +1,323 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use dsp_perf::fastmath;
+use dsp_perf::sample::Sample;
+
+/// The precision the errors of a `Sample` type are measured in.
+trait Precision: Sample {
+    const MANTISSA_BITS: i32;
+    const NAME: &'static str;
+}
+
+impl Precision for f64 {
+    const MANTISSA_BITS: i32 = 52;
+    const NAME: &'static str = "f64";
+}
+
+impl Precision for f32 {
+    const MANTISSA_BITS: i32 = 23;
+    const NAME: &'static str = "f32";
+}
+
+/// The spacing of `S` values around `value`, with subnormals spaced like the
+/// smallest normal numbers.
+fn ulp<S: Precision>(value: f64) -> f64 {
+    let exponent = if value == 0.0 {
+        S::MIN_EXPONENT
+    } else {
+        (value.abs().log2().floor() as i32).max(S::MIN_EXPONENT)
+    };
+    ((exponent - S::MANTISSA_BITS) as f64).exp2()
+}
+
+/// The largest error found, and the input it was found at.
+#[derive(Copy, Clone, Default)]
+struct MaxError {
+    error: f64,
+    input: f64,
+}
+
+impl MaxError {
+    fn update(&mut self, error: f64, input: f64) {
+        if error > self.error {
+            self.error = error;
+            self.input = input;
+        }
+    }
+}
+
+/// The worst absolute, relative and ULP errors of an approximation over a set
+/// of inputs. Relative errors skip the inputs where the reference is zero.
+#[derive(Copy, Clone, Default)]
+struct Accuracy {
+    abs: MaxError,
+    rel: MaxError,
+    ulp: MaxError,
+}
+
+impl fmt::Display for Accuracy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "max abs {:.3e} at {}, max rel {:.3e} at {}, max {:.2} ULP at {}",
+            self.abs.error,
+            self.abs.input,
+            self.rel.error,
+            self.rel.input,
+            self.ulp.error,
+            self.ulp.input
+        )
+    }
+}
+
+/// The largest errors an approximation may have over its domain. Errors that
+/// say nothing about a function, like the relative error of a sine near its
+/// zeros, are left unbounded.
+#[derive(Copy, Clone)]
+struct Bounds {
+    abs: f64,
+    rel: f64,
+    ulp: f64,
+}
+
+const UNBOUNDED: Bounds = Bounds {
+    abs: f64::INFINITY,
+    rel: f64::INFINITY,
+    ulp: f64::INFINITY,
+};
+
+/// Measure `f` against `reference`, which is evaluated in `f64` at the same
+/// `S` inputs, and fail if any error is above its bound. The errors are
+/// printed either way, so `cargo test --test fastmath -- --nocapture` reports
+/// them all.
+fn assert_accuracy<S: Precision, I: IntoIterator<Item = f64>>(
+    name: &str,
+    f: fn(S) -> S,
+    reference: fn(f64) -> f64,
+    inputs: I,
+    bounds: Bounds,
+) {
+    let mut accuracy = Accuracy::default();
+
+    for input in inputs {
+        let x = S::from_f64(input);
+        let input = x.to_f64();
+        let expected = reference(input);
+        let error = (f(x).to_f64() - expected).abs();
+
+        accuracy.abs.update(error, input);
+        if expected != 0.0 {
+            accuracy.rel.update(error / expected.abs(), input);
+        }
+        accuracy.ulp.update(error / ulp::<S>(expected), input);
+    }
+
+    println!("{} ({}): {}", name, S::NAME, accuracy);
+
+    assert!(
+        accuracy.abs.error <= bounds.abs
+            && accuracy.rel.error <= bounds.rel
+            && accuracy.ulp.error <= bounds.ulp,
+        "{} ({}) regressed: {} (bounds: abs {:e}, rel {:e}, {} ULP)",
+        name,
+        S::NAME,
+        accuracy,
+        bounds.abs,
+        bounds.rel,
+        bounds.ulp
+    );
+}
+
+/// Evenly spaced inputs from `min` to `max`, plus as many pseudo-random ones
+/// in between so the grid doesn't hide anything.
+fn sampled(min: f64, max: f64) -> impl Iterator<Item = f64> {
+    const SAMPLES: usize = 100_000;
+
+    let grid = (0..SAMPLES).map(move |i| min + (max - min) * i as f64 / (SAMPLES - 1) as f64);
+
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let random = (0..SAMPLES).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        min + (max - min) * (state >> 11) as f64 / (1u64 << 53) as f64
+    });
+
+    grid.chain(random)
+}
+
+#[test]
+fn parabolic_sine_accuracy() {
+    // The sine's own zeros make its relative and ULP errors meaningless, so
+    // only the absolute error is bounded over the whole domain.
+    let bounds = Bounds {
+        abs: 1.1e-3,
+        ..UNBOUNDED
+    };
+    assert_accuracy::<f64, _>(
+        "parabolic_sine",
+        fastmath::parabolic_sine,
+        f64::sin,
+        sampled(-PI, PI),
+        bounds,
+    );
+    assert_accuracy::<f32, _>(
+        "parabolic_sine",
+        fastmath::parabolic_sine,
+        f64::sin,
+        sampled(-PI, PI),
+        bounds,
+    );
+
+    // Near zero the parabola is about 1.3% too shallow. Near +-pi it is too,
+    // but `4 - 4` cancels there, so that end is left out.
+    let bounds = Bounds {
+        abs: 1.1e-3,
+        rel: 1.4e-2,
+        ulp: f64::INFINITY,
+    };
+    assert_accuracy::<f64, _>(
+        "parabolic_sine, away from +-pi",
+        fastmath::parabolic_sine,
+        f64::sin,
+        sampled(-PI + 0.1, PI - 0.1),
+        bounds,
+    );
+    assert_accuracy::<f32, _>(
+        "parabolic_sine, away from +-pi",
+        fastmath::parabolic_sine,
+        f64::sin,
+        sampled(-PI + 0.1, PI - 0.1),
+        bounds,
+    );
+}
+
+#[test]
+fn exp2_accuracy() {
+    let f64_bounds = Bounds {
+        rel: 2.5e-16,
+        ulp: 1.1,
+        ..UNBOUNDED
+    };
+    let f32_bounds = Bounds {
+        rel: 1.5e-7,
+        ulp: 1.8,
+        ..UNBOUNDED
+    };
+
+    // The fraction, which the polynomial covers.
+    assert_accuracy::<f64, _>(
+        "exp2, [-1, 1]",
+        fastmath::exp2,
+        f64::exp2,
+        sampled(-1.0, 1.0),
+        f64_bounds,
+    );
+    assert_accuracy::<f32, _>(
+        "exp2, [-1, 1]",
+        fastmath::exp2,
+        f64::exp2,
+        sampled(-1.0, 1.0),
+        f32_bounds,
+    );
+
+    // Every input with a normal result.
+    assert_accuracy::<f64, _>(
+        "exp2, normal results",
+        fastmath::exp2,
+        f64::exp2,
+        sampled(-1022.0, 1023.5 - 1e-9),
+        f64_bounds,
+    );
+    assert_accuracy::<f32, _>(
+        "exp2, normal results",
+        fastmath::exp2,
+        f64::exp2,
+        sampled(-126.0, 127.5 - 1e-5),
+        f32_bounds,
+    );
+}
+
+#[test]
+fn exp2_saturates_out_of_range() {
+    for &x in [1023.5, 1024.0, 2000.0, 3e9, 1e10, 1e300, f64::INFINITY].iter() {
+        assert_eq!(fastmath::exp2(x), f64::INFINITY, "exp2({})", x);
+    }
+    for &x in [127.5, 128.0, 1000.0, 1e10, f32::INFINITY].iter() {
+        assert_eq!(fastmath::exp2(x), f32::INFINITY, "exp2({})", x);
+    }
+
+    // Subnormal results flush to zero, apart from those within half an octave
+    // of the normal ones.
+    assert!(fastmath::exp2(-1022.5) > 0.0);
+    assert!(fastmath::exp2(-126.5f32) > 0.0);
+    for &x in [
+        -1023.0,
+        -1060.0,
+        -1100.0,
+        -3e9,
+        -1e10,
+        -1e300,
+        f64::NEG_INFINITY,
+    ]
+    .iter()
+    {
+        let actual = fastmath::exp2(x);
+        assert_eq!(actual, 0.0, "exp2({})", x);
+        assert!(x.exp2() - actual < f64::MIN_POSITIVE, "exp2({})", x);
+    }
+    for &x in [-127.0, -140.0, -1e10, f32::NEG_INFINITY].iter() {
+        let actual = fastmath::exp2(x);
+        assert_eq!(actual, 0.0, "exp2({})", x);
+        assert!(x.exp2() - actual < f32::MIN_POSITIVE, "exp2({})", x);
+    }
+
+    assert!(fastmath::exp2(f64::NAN).is_nan());
+    assert!(fastmath::exp2(f32::NAN).is_nan());
+}
+
+#[test]
+fn wrap01_accuracy() {
+    let reference: fn(f64) -> f64 = |x| x.rem_euclid(1.0);
+
+    // Exact in `f64`, and correctly rounded in `f32`, where a small negative
+    // input plus one rounds.
+    assert_accuracy::<f64, _>(
+        "wrap01",
+        fastmath::wrap01,
+        reference,
+        sampled(-1000.0, 1000.0),
+        Bounds {
+            abs: 0.0,
+            ..UNBOUNDED
+        },
+    );
+    assert_accuracy::<f32, _>(
+        "wrap01",
+        fastmath::wrap01,
+        reference,
+        sampled(-1000.0, 1000.0),
+        Bounds {
+            ulp: 0.5,
+            ..UNBOUNDED
+        },
+    );
+}
+
+#[test]
+fn wrap01_stays_in_range() {
+    for &x in [-1e300, -1e-300, -0.0, 0.0, 1.0, 1.0 + f64::EPSILON, 1e300].iter() {
+        let wrapped = fastmath::wrap01(x);
+        assert!(
+            (0.0..=1.0).contains(&wrapped),
+            "wrap01({}) = {}",
+            x,
+            wrapped
+        );
+    }
+
+    for &x in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN].iter() {
+        assert!(fastmath::wrap01(x).is_nan(), "wrap01({})", x);
+    }
+}
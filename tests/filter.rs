@@ -0,0 +1,286 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use common::{render_in_blocks, SAMPLE_RATE};
+use dsp_perf::filter::{
+    FilterMode, FilterModel, FilterSettings, LadderCoefficients, LadderState, Oversampling,
+    SvfCoefficients, SvfState,
+};
+use dsp_perf::pitch::Hz;
+use dsp_perf::ENGINES;
+
+/// Steady-state gain of the filter for a sine at `frequency`.
+fn gain(mode: FilterMode, cutoff: f64, resonance: f64, frequency: f64) -> f64 {
+    let coefficients = SvfCoefficients::new(cutoff, resonance, SAMPLE_RATE);
+    let mut state = SvfState::default();
+    let frames = SAMPLE_RATE as usize;
+
+    (0..frames)
+        .map(|i| {
+            let input = (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin();
+            state.process(input, &coefficients, mode)
+        })
+        .skip(frames / 2)
+        .fold(0.0, |peak, out| out.abs().max(peak))
+}
+
+fn assert_gain(mode: FilterMode, resonance: f64, frequency: f64, expected: f64, tolerance: f64) {
+    let actual = gain(mode, 1000.0, resonance, frequency);
+    assert!(
+        (actual - expected).abs() < tolerance,
+        "{:?} at {} Hz, resonance {}: expected gain {}, got {}",
+        mode,
+        frequency,
+        resonance,
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn low_pass_passes_lows_and_cuts_highs() {
+    assert_gain(FilterMode::LowPass, 0.0, 50.0, 1.0, 0.01);
+    assert_gain(FilterMode::LowPass, 0.0, 10000.0, 0.0, 0.01);
+}
+
+#[test]
+fn high_pass_passes_highs_and_cuts_lows() {
+    assert_gain(FilterMode::HighPass, 0.0, 50.0, 0.0, 0.01);
+    assert_gain(FilterMode::HighPass, 0.0, 15000.0, 1.0, 0.01);
+}
+
+#[test]
+fn band_pass_peaks_at_cutoff() {
+    let at_cutoff = gain(FilterMode::BandPass, 1000.0, 0.0, 1000.0);
+    assert!(at_cutoff > gain(FilterMode::BandPass, 1000.0, 0.0, 250.0));
+    assert!(at_cutoff > gain(FilterMode::BandPass, 1000.0, 0.0, 4000.0));
+}
+
+#[test]
+fn notch_rejects_cutoff() {
+    assert_gain(FilterMode::Notch, 0.5, 1000.0, 0.0, 0.01);
+    assert_gain(FilterMode::Notch, 0.5, 50.0, 1.0, 0.01);
+    assert_gain(FilterMode::Notch, 0.5, 15000.0, 1.0, 0.01);
+}
+
+#[test]
+fn resonance_sets_gain_at_cutoff() {
+    // The gain at the cutoff is the filter's Q, which goes from 0.5 without
+    // resonance to infinity at full resonance.
+    assert_gain(FilterMode::LowPass, 0.0, 1000.0, 0.5, 0.01);
+    assert_gain(FilterMode::LowPass, 0.5, 1000.0, 1.0, 0.02);
+    assert_gain(FilterMode::LowPass, 0.9, 1000.0, 5.0, 0.1);
+}
+
+#[test]
+fn cutoff_is_clamped_below_nyquist() {
+    let coefficients = SvfCoefficients::new(1e6, 0.0, SAMPLE_RATE);
+    let mut state = SvfState::default();
+
+    for i in 0..1000 {
+        let out = state.process((i % 2) as f64, &coefficients, FilterMode::LowPass);
+        assert!(out.is_finite());
+    }
+}
+
+#[test]
+fn filter_darkens_every_engine() {
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let dry = render_in_blocks(&mut *synth, 44100, 512);
+
+        synth.reset();
+        synth.set_filter(Some(FilterSettings {
+            mode: FilterMode::LowPass,
+            cutoff: Hz(100.0),
+            ..FilterSettings::default()
+        }));
+        let filtered = render_in_blocks(&mut *synth, 44100, 512);
+
+        synth.reset();
+        synth.set_filter(None);
+        let restored = render_in_blocks(&mut *synth, 44100, 512);
+
+        // Skip the filter's transient.
+        let peak = |buffer: &[f64]| {
+            buffer[22050..]
+                .iter()
+                .fold(0.0, |peak: f64, x| peak.max(x.abs()))
+        };
+        assert!(peak(&filtered) < 0.1 * peak(&dry), "{}", engine.name);
+        assert_eq!(dry, restored, "{}", engine.name);
+    }
+}
+
+/// Run the ladder from a single impulse and return its output.
+fn ladder_ring(cutoff: f64, resonance: f64, oversampling: Oversampling) -> Vec<f64> {
+    let coefficients = LadderCoefficients::new(cutoff, resonance, SAMPLE_RATE, oversampling);
+    let mut state = LadderState::default();
+
+    (0..SAMPLE_RATE as usize)
+        .map(|i| state.process(if i == 0 { 0.1 } else { 0.0 }, &coefficients))
+        .collect()
+}
+
+/// Frequency of a steady oscillation, from the time between the first and
+/// last rising zero crossings.
+fn oscillation_frequency(output: &[f64]) -> f64 {
+    let crossings: Vec<usize> = output
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+        .map(|(i, _)| i)
+        .collect();
+    let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+
+    (crossings.len() - 1) as f64 * SAMPLE_RATE / (last - first) as f64
+}
+
+#[test]
+fn ladder_self_oscillates_at_cutoff() {
+    for &oversampling in [Oversampling::Off, Oversampling::X2, Oversampling::X4].iter() {
+        for &cutoff in [220.0, 1000.0, 4000.0].iter() {
+            let output = ladder_ring(cutoff, 1.0, oversampling);
+
+            // The saturation keeps the oscillation going at a bounded level.
+            let tail = &output[output.len() / 2..];
+            let peak = tail.iter().fold(0.0, |peak: f64, x| peak.max(x.abs()));
+            assert!(
+                peak > 0.1 && peak < 1.0,
+                "{:?} at {} Hz: peak {}",
+                oversampling,
+                cutoff,
+                peak
+            );
+
+            let frequency = oscillation_frequency(tail);
+            assert!(
+                (frequency / cutoff - 1.0).abs() < 0.02,
+                "{:?}: oscillating at {} Hz for a {} Hz cutoff",
+                oversampling,
+                frequency,
+                cutoff
+            );
+        }
+    }
+}
+
+#[test]
+fn ladder_decays_below_self_oscillation() {
+    for &resonance in [0.0, 0.5].iter() {
+        let output = ladder_ring(1000.0, resonance, Oversampling::Off);
+        let tail = &output[output.len() / 2..];
+        assert!(
+            tail.iter().all(|x| x.abs() < 1e-9),
+            "resonance {}",
+            resonance
+        );
+    }
+}
+
+#[test]
+fn ladder_is_a_four_pole_low_pass() {
+    // Well below the cutoff the ladder passes a small signal, and a decade
+    // above it falls by 24 dB per octave, i.e. 80 dB per decade. A 3-pole
+    // filter would only be down by 60 dB.
+    let gain = |frequency: f64| {
+        let coefficients = LadderCoefficients::new(1000.0, 0.0, SAMPLE_RATE, Oversampling::Off);
+        let mut state = LadderState::default();
+        let frames = SAMPLE_RATE as usize;
+
+        (0..frames)
+            .map(|i| {
+                let input = 0.01 * (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin();
+                state.process(input, &coefficients)
+            })
+            .skip(frames / 2)
+            .fold(0.0, |peak: f64, out| out.abs().max(peak))
+            / 0.01
+    };
+
+    assert!((gain(50.0) - 1.0).abs() < 0.01);
+    assert!(gain(10000.0) < 2e-4);
+}
+
+#[test]
+fn ladder_filter_darkens_every_engine() {
+    for &oversampling in [Oversampling::Off, Oversampling::X4].iter() {
+        for engine in ENGINES {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            let dry = render_in_blocks(&mut *synth, 44100, 512);
+
+            synth.reset();
+            synth.set_filter(Some(FilterSettings {
+                model: FilterModel::Ladder(oversampling),
+                cutoff: Hz(100.0),
+                ..FilterSettings::default()
+            }));
+            let filtered = render_in_blocks(&mut *synth, 44100, 512);
+
+            let peak = |buffer: &[f64]| {
+                buffer[22050..]
+                    .iter()
+                    .fold(0.0, |peak: f64, x| peak.max(x.abs()))
+            };
+            assert!(
+                peak(&filtered) < 0.01 * peak(&dry),
+                "{}, {:?}",
+                engine.name,
+                oversampling
+            );
+        }
+    }
+}
+
+/// Share of a driven ladder's output power that isn't at the harmonics of its
+/// input, in dB. The input has a whole number of cycles in the measured
+/// frames, so the harmonics below Nyquist land exactly on their bins, while
+/// those folded back from above Nyquist land between them.
+fn ladder_aliasing(oversampling: Oversampling) -> f64 {
+    const FRAMES: usize = 4096;
+    // Coprime with `FRAMES`, so no folded harmonic lands on a true one.
+    const CYCLES: usize = 397;
+
+    let coefficients = LadderCoefficients::new(18000.0, 0.0, SAMPLE_RATE, oversampling);
+    let mut state = LadderState::default();
+    let output: Vec<f64> = (0..4 * FRAMES)
+        .map(|i| {
+            let input = 4.0 * (2.0 * PI * (CYCLES * i) as f64 / FRAMES as f64).sin();
+            state.process(input, &coefficients)
+        })
+        .skip(3 * FRAMES)
+        .collect();
+
+    let total: f64 = output.iter().map(|x| x * x).sum();
+    let harmonics: f64 = (1..)
+        .map(|harmonic| harmonic * CYCLES)
+        .take_while(|bin| *bin < FRAMES / 2)
+        .map(|bin| {
+            let (re, im) = output
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (i, x)| {
+                    let angle = 2.0 * PI * (bin * i % FRAMES) as f64 / FRAMES as f64;
+                    (re + x * angle.cos(), im - x * angle.sin())
+                });
+            2.0 * (re * re + im * im) / FRAMES as f64
+        })
+        .sum();
+
+    10.0 * ((total - harmonics) / total).log10()
+}
+
+#[test]
+fn oversampling_keeps_ladder_saturation_from_aliasing() {
+    let off = ladder_aliasing(Oversampling::Off);
+    let x2 = ladder_aliasing(Oversampling::X2);
+    let x4 = ladder_aliasing(Oversampling::X4);
+
+    // Without oversampling, the harmonics of the saturation fold straight
+    // back. The half-band filters keep them out down to their rejection,
+    // less the harmonics that still fold within the internal rate at 2x.
+    assert!(off > -40.0, "aliasing without oversampling: {} dB", off);
+    assert!(x2 < -60.0, "aliasing at 2x: {} dB", x2);
+    assert!(x4 < -100.0, "aliasing at 4x: {} dB", x4);
+}
@@ -0,0 +1,117 @@
+//! Golden-file regression tests for the rendered signal.
+//!
+//! Each engine renders the default patch and the result is compared against a
+//! reference file in `tests/golden/`. When a change to the signal is
+//! intentional, regenerate the references with
+//!
+//! ```text
+//! BLESS=1 cargo test --test golden
+//! ```
+//!
+//! and check the updated files in. The comparison tolerance can be overridden
+//! with `GOLDEN_TOLERANCE=<value>`.
+
+mod common;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use common::{assert_renders_match, render_in_blocks, SAMPLE_RATE};
+use dsp_perf::ENGINES;
+
+/// Loose enough to absorb differences in `exp2` between platforms, tight
+/// enough to catch any audible change.
+const DEFAULT_TOLERANCE: f64 = 1e-7;
+
+/// Two seconds, one full period of the 0.5 Hz LFO.
+const FRAMES: usize = 88200;
+
+/// Only every `STRIDE`th frame is stored to keep the reference files small.
+/// The stride is odd so that the stored frames fall on every position within
+/// a batch.
+const STRIDE: usize = 49;
+
+const BLOCK_SIZE: usize = 441;
+
+fn golden_path(id: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.txt", id))
+}
+
+fn blessing() -> bool {
+    env::var_os("BLESS").is_some_and(|v| v != "0")
+}
+
+fn tolerance() -> f64 {
+    match env::var("GOLDEN_TOLERANCE") {
+        Ok(v) => v
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid GOLDEN_TOLERANCE: {:?}", v)),
+        Err(_) => DEFAULT_TOLERANCE,
+    }
+}
+
+fn serialize(samples: &[f64]) -> String {
+    let mut out = String::new();
+    writeln!(out, "# sample rate: {}", SAMPLE_RATE).unwrap();
+    writeln!(out, "# frames: {}", FRAMES).unwrap();
+    writeln!(out, "# stride: {}", STRIDE).unwrap();
+    for sample in samples {
+        writeln!(out, "{:?}", sample).unwrap();
+    }
+    out
+}
+
+fn deserialize(contents: &str) -> Vec<f64> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            line.trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid sample in golden file: {:?}", line))
+        })
+        .collect()
+}
+
+#[test]
+fn engines_match_golden_files() {
+    let tolerance = tolerance();
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let rendered: Vec<f64> = render_in_blocks(&mut *synth, FRAMES, BLOCK_SIZE)
+            .into_iter()
+            .step_by(STRIDE)
+            .collect();
+
+        let path = golden_path(engine.id);
+
+        if blessing() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, serialize(&rendered)).unwrap();
+            continue;
+        }
+
+        let contents = fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "could not read {} ({}), run with BLESS=1 to create it",
+                path.display(),
+                e
+            )
+        });
+        let expected = deserialize(&contents);
+
+        assert_eq!(
+            expected.len(),
+            rendered.len(),
+            "{}: golden file has a different length, run with BLESS=1 to regenerate it",
+            engine.name
+        );
+        assert_renders_match(engine.name, &expected, &rendered, tolerance);
+    }
+}
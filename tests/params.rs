@@ -0,0 +1,191 @@
+mod common;
+
+use std::collections::HashSet;
+
+use common::{render_in_blocks, SAMPLE_RATE};
+use dsp_perf::engine::Oscillator;
+use dsp_perf::params::{ParamId, Unit, PARAMS};
+use dsp_perf::pitch::Hz;
+use dsp_perf::tempo::Division;
+use dsp_perf::ENGINES;
+
+const OSCILLATORS: [Oscillator; 4] = [
+    Oscillator::Osc1,
+    Oscillator::Osc2,
+    Oscillator::Wavetable,
+    Oscillator::Lfo,
+];
+
+#[test]
+fn every_parameter_is_listed_once() {
+    let mut ids: HashSet<ParamId> = [
+        ParamId::Frequency,
+        ParamId::Osc1Waveform,
+        ParamId::Osc1PulseWidth,
+        ParamId::Osc2Waveform,
+        ParamId::Osc2PulseWidth,
+        ParamId::HardSync,
+        ParamId::WavetablePosition,
+        ParamId::LfoFrequency,
+        ParamId::LfoTempoSync,
+        ParamId::LfoWaveform,
+        ParamId::LfoPulseWidth,
+        ParamId::LfoMode,
+        ParamId::LfoDelay,
+        ParamId::LfoFadeIn,
+    ]
+    .iter()
+    .copied()
+    .collect();
+    for &oscillator in OSCILLATORS.iter() {
+        ids.insert(ParamId::Octaves(oscillator));
+        ids.insert(ParamId::Semitones(oscillator));
+        ids.insert(ParamId::Cents(oscillator));
+        ids.insert(ParamId::Level(oscillator));
+    }
+
+    assert_eq!(ids.len(), PARAMS.len());
+    for &id in ids.iter() {
+        assert_eq!(id, id.info().id);
+    }
+
+    let keys: HashSet<_> = PARAMS.iter().map(|info| info.key).collect();
+    assert_eq!(PARAMS.len(), keys.len());
+
+    for info in PARAMS {
+        assert!(info.min < info.max, "{}", info.key);
+        assert_eq!(info.default, info.clamp(info.default), "{}", info.key);
+    }
+}
+
+#[test]
+fn engines_start_with_the_default_patch() {
+    for engine in ENGINES {
+        let synth = (engine.create)(SAMPLE_RATE);
+        for info in PARAMS {
+            assert_eq!(
+                info.default,
+                synth.parameter(info.id),
+                "{}: {}",
+                engine.name,
+                info.key
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_store_clamped_values() {
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        for info in PARAMS {
+            for &value in [
+                info.min,
+                info.max,
+                0.7 * info.min + 0.3 * info.max,
+                info.max + 1.0,
+                info.min - 1.0,
+            ]
+            .iter()
+            {
+                synth.set_parameter(info.id, value);
+                assert_eq!(
+                    info.clamp(value),
+                    synth.parameter(info.id),
+                    "{}: {} set to {}",
+                    engine.name,
+                    info.key,
+                    value
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn clamp_rounds_choices_and_ignores_nan() {
+    let waveform = ParamId::Osc1Waveform.info();
+    assert_eq!(1.0, waveform.clamp(1.4));
+    assert_eq!(3.0, waveform.clamp(10.0));
+
+    let frequency = ParamId::Frequency.info();
+    assert_eq!(8.0, frequency.clamp(-5.0));
+    assert_eq!(440.0, frequency.clamp(f64::NAN));
+}
+
+#[test]
+fn values_display_with_their_unit() {
+    let display = |id: ParamId, value| id.info().display(value);
+
+    assert_eq!("440.0 Hz", display(ParamId::Frequency, 440.0));
+    assert_eq!("2.50 kHz", display(ParamId::Frequency, 2500.0));
+    assert_eq!("0.50 Hz", display(ParamId::LfoFrequency, 0.5));
+    assert_eq!(
+        "-1.00 oct",
+        display(ParamId::Octaves(Oscillator::Osc1), -1.0)
+    );
+    assert_eq!(
+        "+7.00 st",
+        display(ParamId::Semitones(Oscillator::Osc2), 7.0)
+    );
+    assert_eq!("+2.5 ct", display(ParamId::Cents(Oscillator::Osc2), 2.5));
+    assert_eq!("-6.0 dB", display(ParamId::Level(Oscillator::Osc1), 0.5));
+    assert_eq!("-inf dB", display(ParamId::Level(Oscillator::Osc1), 0.0));
+    assert_eq!("+1.00 oct", display(ParamId::Level(Oscillator::Lfo), 1.0));
+    assert_eq!("25 %", display(ParamId::Osc1PulseWidth, 0.25));
+    assert_eq!("Saw", display(ParamId::Osc1Waveform, 1.0));
+    assert_eq!("Sample & hold", display(ParamId::LfoWaveform, 5.0));
+    assert_eq!("One shot", display(ParamId::LfoMode, 2.0));
+    assert_eq!("On", display(ParamId::HardSync, 1.0));
+    assert_eq!("1/8 dotted", display(ParamId::LfoTempoSync, 11.0));
+    assert_eq!("250 ms", display(ParamId::LfoDelay, 0.25));
+    assert_eq!("1.50 s", display(ParamId::LfoFadeIn, 1.5));
+
+    assert!(matches!(
+        ParamId::LfoMode.info().unit,
+        Unit::Choice(options) if options.len() == 3
+    ));
+}
+
+#[test]
+fn parameters_match_the_typed_setters() {
+    for engine in ENGINES {
+        let mut typed = (engine.create)(SAMPLE_RATE);
+        typed.set_lfo_frequency(Hz(3.0));
+        typed.set_amplitude(Oscillator::Osc2, 0.25);
+        typed.set_hard_sync(true);
+        typed.set_lfo_delay(0.01, 0.02);
+
+        let mut by_id = (engine.create)(SAMPLE_RATE);
+        by_id.set_parameter(ParamId::LfoFrequency, 3.0);
+        by_id.set_parameter(ParamId::Level(Oscillator::Osc2), 0.25);
+        by_id.set_parameter(ParamId::HardSync, 1.0);
+        by_id.set_parameter(ParamId::LfoDelay, 0.01);
+        by_id.set_parameter(ParamId::LfoFadeIn, 0.02);
+
+        assert_eq!(
+            render_in_blocks(&mut *typed, 4096, 64),
+            render_in_blocks(&mut *by_id, 4096, 64),
+            "{}",
+            engine.name
+        );
+    }
+}
+
+#[test]
+fn tempo_sync_reports_the_nearest_listed_division() {
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_lfo_tempo_sync(Some(Division::new(1, 8).dotted()));
+        assert_eq!(11.0, synth.parameter(ParamId::LfoTempoSync));
+
+        synth.set_lfo_tempo_sync(Some(Division::new(3, 16)));
+        assert_eq!(11.0, synth.parameter(ParamId::LfoTempoSync));
+
+        synth.set_lfo_tempo_sync(Some(Division::bars(8)));
+        assert_eq!(1.0, synth.parameter(ParamId::LfoTempoSync));
+
+        synth.set_parameter(ParamId::LfoTempoSync, 0.0);
+        assert_eq!(0.0, synth.parameter(ParamId::LfoTempoSync));
+    }
+}
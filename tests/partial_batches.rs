@@ -0,0 +1,52 @@
+mod common;
+
+use common::{assert_renders_match, render_in_blocks, render_in_varying_blocks, SAMPLE_RATE};
+use dsp_perf::ENGINES;
+
+/// Buffer sizes that none of the batch sizes divide, so every render ends on
+/// a partial batch.
+const UNEVEN_SIZES: &[usize] = &[100, 441, 37, 1, 250];
+
+#[test]
+fn partial_final_batch_is_rendered() {
+    for engine in ENGINES {
+        for &frames in [100, 441].iter() {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+
+            // Frames the engine leaves untouched keep the NaN.
+            let mut buffer = vec![f64::NAN; frames];
+            synth.render(&mut buffer);
+
+            for (frame, sample) in buffer.iter().enumerate() {
+                assert!(
+                    sample.is_finite(),
+                    "{}, {} frames: frame {} not rendered",
+                    engine.name,
+                    frames,
+                    frame
+                );
+            }
+            assert!(
+                buffer[frames - 10..].iter().any(|sample| *sample != 0.0),
+                "{}, {} frames: silent tail",
+                engine.name,
+                frames
+            );
+        }
+    }
+}
+
+#[test]
+fn output_is_continuous_across_uneven_buffers() {
+    let frames = UNEVEN_SIZES.iter().sum::<usize>() * 3;
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let expected = render_in_blocks(&mut *synth, frames, frames);
+
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        let actual = render_in_varying_blocks(&mut *synth, frames, UNEVEN_SIZES);
+
+        assert_renders_match(engine.name, &expected, &actual, 1e-9);
+    }
+}
@@ -0,0 +1,72 @@
+use dsp_perf::pitch::{Cents, Hz, MidiNote, Octaves, Semitones};
+
+const TOLERANCE: f64 = 1e-9;
+
+fn assert_close(expected: f64, actual: f64) {
+    assert!(
+        (expected - actual).abs() <= TOLERANCE * expected.abs().max(1.0),
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn midi_notes_to_frequency() {
+    let known = [
+        (0.0, 8.175798915643707),
+        (21.0, 27.5),
+        (57.0, 220.0),
+        (60.0, 261.6255653005986),
+        (64.0, 329.6275569128699),
+        (67.0, 391.9954359817493),
+        (69.0, 440.0),
+        (81.0, 880.0),
+        (108.0, 4186.009044809578),
+        (127.0, 12543.853951415975),
+    ];
+
+    for &(note, frequency) in known.iter() {
+        assert_close(frequency, Hz::from(MidiNote(note)).0);
+        assert_close(note, MidiNote::from(Hz(frequency)).0);
+    }
+}
+
+#[test]
+fn fractional_midi_notes() {
+    assert_close(440.0 * 2.0f64.powf(0.5 / 12.0), Hz::from(MidiNote(69.5)).0);
+    assert_close(
+        69.25,
+        MidiNote::from(Hz(440.0).transpose(Cents(25.0).into())).0,
+    );
+}
+
+#[test]
+fn interval_conversions() {
+    assert_eq!(Octaves(1.0), Octaves::from(Semitones(12.0)));
+    assert_eq!(Octaves(1.0), Octaves::from(Cents(1200.0)));
+    assert_eq!(Semitones(1.0), Semitones::from(Cents(100.0)));
+    assert_eq!(Semitones(-24.0), Semitones::from(Octaves(-2.0)));
+    assert_eq!(Cents(250.0), Cents::from(Semitones(2.5)));
+    assert_eq!(Cents(600.0), Cents::from(Octaves(0.5)));
+}
+
+#[test]
+fn interval_ratios() {
+    assert_close(2.0, Octaves(1.0).ratio());
+    assert_close(0.25, Octaves(-2.0).ratio());
+    assert_close(1.5f64.log2(), Octaves::between(Hz(440.0), Hz(660.0)).0);
+
+    // The 2.5 cent detune of the default patch is a tiny interval, not a
+    // fraction of an octave.
+    let detuned = Hz(440.0).transpose(Cents(2.5).into());
+    assert_close(440.6358439023197, detuned.0);
+}
+
+#[test]
+fn interval_arithmetic() {
+    let offset = Octaves(1.0) + Semitones(7.0).into() + Cents(-5.0).into();
+    assert_close(1.0 + 7.0 / 12.0 - 5.0 / 1200.0, offset.0);
+    assert_eq!(Semitones(5.0), Semitones(12.0) - Semitones(7.0));
+    assert_eq!(Cents(-3.0), -Cents(3.0));
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use common::SAMPLE_RATE;
+use dsp_perf::envelope::AdsrSettings;
+use dsp_perf::params::ParamId;
+use dsp_perf::pitch::{Hz, MidiNote};
+use dsp_perf::polyphony::{PolySynth, StealPolicy};
+use dsp_perf::{one_frame_per_call, EngineDescriptor, SynthEngine, ENGINES};
+
+/// Counts the allocations made by each test's own thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+fn create_one_frame(sample_rate: f64) -> Box<dyn SynthEngine> {
+    Box::new(one_frame_per_call::Synth::new(sample_rate))
+}
+
+fn poly_synth(engine: &EngineDescriptor, voice_count: usize, envelope: AdsrSettings) -> PolySynth {
+    let mut poly = PolySynth::new(engine.create, SAMPLE_RATE, voice_count);
+    poly.for_each_voice(|synth| synth.set_amp_envelope(Some(envelope)));
+    poly
+}
+
+fn render(poly: &mut PolySynth, frames: usize) -> Vec<f64> {
+    let mut buffer = vec![0.0; frames];
+    poly.render(&mut buffer);
+    buffer
+}
+
+fn sounding_notes(poly: &PolySynth) -> Vec<f64> {
+    let mut notes: Vec<f64> = poly.sounding_notes().map(|note| note.0).collect();
+    notes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    notes
+}
+
+#[test]
+fn voices_sum_like_separate_synths() {
+    let notes = [60.0, 64.0, 67.0];
+
+    for engine in ENGINES {
+        let mut poly = poly_synth(engine, 4, AdsrSettings::default());
+        poly.set_parameter(ParamId::Osc1Waveform, 1.0);
+        for &note in notes.iter() {
+            poly.note_on(MidiNote(note));
+        }
+        let actual = render(&mut poly, 3000);
+
+        let mut expected = vec![0.0; 3000];
+        for &note in notes.iter() {
+            let mut synth = (engine.create)(SAMPLE_RATE);
+            synth.set_amp_envelope(Some(AdsrSettings::default()));
+            synth.set_parameter(ParamId::Osc1Waveform, 1.0);
+            synth.set_parameter(ParamId::Frequency, Hz::from(MidiNote(note)).0);
+            synth.note_on();
+
+            let mut voice = vec![0.0; 3000];
+            synth.render(&mut voice);
+            for (expected, sample) in expected.iter_mut().zip(voice.iter()) {
+                *expected += sample;
+            }
+        }
+
+        for (frame, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+            assert!(
+                (expected - actual).abs() < 1e-12,
+                "{}, frame {}: expected {}, got {}",
+                engine.name,
+                frame,
+                expected,
+                actual
+            );
+        }
+    }
+}
+
+#[test]
+fn every_midi_note_plays_at_its_pitch() {
+    for &note in [0.0, 12.0, 15.0, 60.0, 127.0].iter() {
+        let mut poly = PolySynth::new(create_one_frame, SAMPLE_RATE, 1);
+        poly.note_on(MidiNote(note));
+
+        poly.for_each_voice(|synth| {
+            assert_eq!(
+                Hz::from(MidiNote(note)).0,
+                synth.parameter(ParamId::Frequency),
+                "note {}",
+                note
+            )
+        });
+    }
+}
+
+#[test]
+fn voices_are_freed_once_released() {
+    let envelope = AdsrSettings {
+        release: 0.01,
+        ..AdsrSettings::default()
+    };
+
+    for engine in ENGINES {
+        let mut poly = poly_synth(engine, 4, envelope);
+        poly.note_on(MidiNote(60.0));
+        poly.note_on(MidiNote(64.0));
+        render(&mut poly, 1000);
+
+        poly.note_off(MidiNote(60.0));
+        assert_eq!(2, poly.active_voices(), "{}", engine.name);
+
+        // Still releasing after half the release time.
+        render(&mut poly, 220);
+        assert_eq!(2, poly.active_voices(), "{}", engine.name);
+
+        render(&mut poly, 1000);
+        assert_eq!(vec![64.0], sounding_notes(&poly), "{}", engine.name);
+
+        poly.all_notes_off();
+        render(&mut poly, 1000);
+        assert_eq!(0, poly.active_voices(), "{}", engine.name);
+        assert!(render(&mut poly, 64).iter().all(|&sample| sample == 0.0));
+    }
+}
+
+#[test]
+fn voices_without_an_amp_envelope_stop_on_release() {
+    let mut poly = PolySynth::new(create_one_frame, SAMPLE_RATE, 2);
+    poly.note_on(MidiNote(60.0));
+    assert_eq!(1, poly.active_voices());
+
+    poly.note_off(MidiNote(60.0));
+    assert_eq!(0, poly.active_voices());
+}
+
+#[test]
+fn oldest_policy_steals_the_first_note() {
+    let mut poly = PolySynth::new(create_one_frame, SAMPLE_RATE, 2);
+    poly.note_on(MidiNote(60.0));
+    poly.note_on(MidiNote(62.0));
+    poly.note_on(MidiNote(64.0));
+    assert_eq!(vec![62.0, 64.0], sounding_notes(&poly));
+
+    poly.note_on(MidiNote(65.0));
+    assert_eq!(vec![64.0, 65.0], sounding_notes(&poly));
+}
+
+#[test]
+fn quietest_policy_steals_the_quietest_voice() {
+    let envelope = AdsrSettings {
+        attack: 0.1,
+        ..AdsrSettings::default()
+    };
+
+    for &(policy, expected) in [
+        (StealPolicy::Oldest, [62.0, 64.0]),
+        (StealPolicy::Quietest, [60.0, 64.0]),
+    ]
+    .iter()
+    {
+        let mut poly = poly_synth(&ENGINES[0], 2, envelope);
+        poly.set_steal_policy(policy);
+
+        // The first note is further into its attack, so louder.
+        poly.note_on(MidiNote(60.0));
+        render(&mut poly, 2000);
+        poly.note_on(MidiNote(62.0));
+        render(&mut poly, 100);
+        poly.note_on(MidiNote(64.0));
+
+        assert_eq!(expected.to_vec(), sounding_notes(&poly), "{:?}", policy);
+    }
+}
+
+#[test]
+fn same_note_policy_retriggers_the_voice() {
+    for (policy, expected) in [
+        (StealPolicy::Oldest, vec![60.0, 60.0, 62.0]),
+        (StealPolicy::SameNote, vec![60.0, 62.0]),
+    ]
+    .iter()
+    {
+        let mut poly = PolySynth::new(create_one_frame, SAMPLE_RATE, 4);
+        poly.set_steal_policy(*policy);
+        poly.note_on(MidiNote(60.0));
+        poly.note_on(MidiNote(62.0));
+        poly.note_on(MidiNote(60.0));
+
+        assert_eq!(*expected, sounding_notes(&poly), "{:?}", policy);
+    }
+
+    // With every voice in use, other notes steal the oldest one.
+    let mut poly = PolySynth::new(create_one_frame, SAMPLE_RATE, 2);
+    poly.set_steal_policy(StealPolicy::SameNote);
+    poly.note_on(MidiNote(60.0));
+    poly.note_on(MidiNote(62.0));
+    poly.note_on(MidiNote(64.0));
+    assert_eq!(vec![62.0, 64.0], sounding_notes(&poly));
+}
+
+#[test]
+fn rendering_and_note_events_do_not_allocate() {
+    for engine in ENGINES {
+        let mut poly = poly_synth(engine, 8, AdsrSettings::default());
+        poly.set_steal_policy(StealPolicy::Quietest);
+        let mut buffer = vec![0.0; 4096];
+
+        let before = allocations();
+        for note in 0..12 {
+            poly.note_on(MidiNote(48.0 + note as f64));
+            poly.render(&mut buffer[..100 + note * 300]);
+            poly.note_off(MidiNote(46.0 + note as f64));
+        }
+        poly.all_notes_off();
+        poly.render(&mut buffer);
+
+        assert_eq!(before, allocations(), "{}", engine.name);
+    }
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use common::{assert_renders_match, SAMPLE_RATE};
+use dsp_perf::filter::FilterSettings;
+use dsp_perf::params::ParamId;
+use dsp_perf::pitch::{Hz, MidiNote};
+use dsp_perf::polyphony::PolySynth;
+use dsp_perf::sample::Sample;
+use dsp_perf::{SynthEngine, ENGINES};
+
+/// Ten seconds.
+const FRAMES: usize = 441000;
+
+const BLOCK_SIZE: usize = 512;
+
+fn render<S: Sample>(synth: &mut dyn SynthEngine<S>, frames: usize) -> Vec<S> {
+    let mut output = vec![S::ZERO; frames];
+    for block in output.chunks_mut(BLOCK_SIZE) {
+        synth.render(block);
+    }
+    output
+}
+
+fn widen(samples: &[f32]) -> Vec<f64> {
+    samples.iter().map(|&sample| sample as f64).collect()
+}
+
+/// How far, in cycles, an `f32` oscillator's phase may drift from the `f64`
+/// one's per frame. Every step of the accumulator rounds to the `f32` spacing
+/// of the phase, and the increment itself carries the rounding of the
+/// frequency calculation, which grows with the increment.
+fn max_drift_per_frame(frequency: f64) -> f64 {
+    let phase_increment = frequency / SAMPLE_RATE;
+    f32::EPSILON as f64 * (0.5 + 2.0 * phase_increment)
+}
+
+/// Allowance for the error of the `f32` shape and mixing, which doesn't
+/// accumulate.
+const SAMPLE_ERROR: f64 = 1e-4;
+
+/// Stop the LFO, whose frequency modulation would amplify the drift of its
+/// own phase, so only the oscillators drift.
+fn without_lfo<S: Sample>(synth: &mut dyn SynthEngine<S>, frequency: f64) {
+    synth.set_lfo_frequency(Hz(0.0));
+    synth.set_parameter(ParamId::Frequency, frequency);
+}
+
+#[test]
+fn f32_phase_drift_is_bounded_over_long_renders() {
+    for engine in ENGINES {
+        for &frequency in [27.5, 440.0, 4186.0, 15000.0].iter() {
+            let mut reference = (engine.create)(SAMPLE_RATE);
+            without_lfo(&mut *reference, frequency);
+            let expected = render(&mut *reference, FRAMES);
+
+            let mut synth = (engine.create_f32)(SAMPLE_RATE);
+            without_lfo(&mut *synth, frequency);
+            let actual = widen(&render(&mut *synth, FRAMES));
+
+            // The drift grows linearly, so check it second by second. A phase
+            // error of `d` cycles changes a unit sine by up to `2 pi d`.
+            let second = SAMPLE_RATE as usize;
+            for end in (second..=FRAMES).step_by(second) {
+                let drift = end as f64 * max_drift_per_frame(frequency);
+                assert_renders_match(
+                    &format!("{}, {} Hz, first {} frames", engine.name, frequency, end),
+                    &expected[..end],
+                    &actual[..end],
+                    2.0 * PI * drift + SAMPLE_ERROR,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn f32_follows_f64_with_the_lfo() {
+    // The LFO's frequency modulation turns its phase drift into a pitch
+    // drift, so the renders only stay close for a short while.
+    let frames = 2205;
+
+    for engine in ENGINES {
+        let mut reference = (engine.create)(SAMPLE_RATE);
+        let expected = render(&mut *reference, frames);
+
+        let mut synth = (engine.create_f32)(SAMPLE_RATE);
+        let actual = widen(&render(&mut *synth, frames));
+
+        assert_renders_match(engine.name, &expected, &actual, SAMPLE_ERROR);
+    }
+}
+
+#[test]
+fn f32_engines_match_each_other() {
+    let configurations: [fn(&mut dyn SynthEngine<f32>); 3] = [
+        |_| {},
+        |synth| synth.set_hard_sync(true),
+        |synth| {
+            synth.set_lfo_frequency(Hz(13.0));
+            synth.set_filter(Some(FilterSettings::default()));
+        },
+    ];
+
+    for configure in configurations.iter() {
+        let mut reference = (ENGINES[0].create_f32)(SAMPLE_RATE);
+        configure(&mut *reference);
+        let expected = widen(&render(&mut *reference, FRAMES));
+
+        for engine in &ENGINES[1..] {
+            let mut synth = (engine.create_f32)(SAMPLE_RATE);
+            configure(&mut *synth);
+            let actual = widen(&render(&mut *synth, FRAMES));
+
+            // Like the `f64` engines, they run the same operations in the
+            // same order.
+            assert_renders_match(engine.name, &expected, &actual, 1e-6);
+        }
+    }
+}
+
+#[test]
+fn poly_synth_renders_f32() {
+    let frames = 44100;
+
+    let mut reference = PolySynth::new(ENGINES[0].create, SAMPLE_RATE, 4);
+    let mut poly = PolySynth::new(ENGINES[0].create_f32, SAMPLE_RATE, 4);
+
+    reference.for_each_voice(|synth| synth.set_lfo_frequency(Hz(0.0)));
+    poly.for_each_voice(|synth| synth.set_lfo_frequency(Hz(0.0)));
+    for &note in [60.0, 64.0, 67.0].iter() {
+        reference.note_on(MidiNote(note));
+        poly.note_on(MidiNote(note));
+    }
+
+    let mut expected = vec![0.0; frames];
+    reference.render(&mut expected);
+    let mut actual = vec![0.0; frames];
+    poly.render(&mut actual);
+
+    let drift = frames as f64 * max_drift_per_frame(Hz::from(MidiNote(67.0)).0);
+    assert_renders_match(
+        "poly synth",
+        &expected,
+        &widen(&actual),
+        3.0 * (2.0 * PI * drift + SAMPLE_ERROR),
+    );
+}
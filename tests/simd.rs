@@ -0,0 +1,221 @@
+use dsp_perf::sample::Sample;
+use dsp_perf::simd::{self, SimdLevel};
+
+type Kernel<S> = fn(SimdLevel, &mut [S]);
+
+/// A spread of ordinary inputs, with lengths that leave every possible
+/// remainder after the packed chunks.
+fn inputs<S: Sample>(min: f64, max: f64, count: usize) -> Vec<S> {
+    (0..count)
+        .map(|i| S::from_f64(min + (max - min) * i as f64 / (count - 1) as f64))
+        .collect()
+}
+
+/// Check that every level gives exactly the scalar results. NaNs only have to
+/// stay NaN.
+fn assert_matches_scalar<S: Sample>(kernel: Kernel<S>, values: &[S]) {
+    for len in 0..=values.len().min(17) {
+        check_levels(kernel, &values[..len]);
+    }
+    check_levels(kernel, values);
+}
+
+fn check_levels<S: Sample>(kernel: Kernel<S>, values: &[S]) {
+    let mut expected = values.to_vec();
+    kernel(SimdLevel::Scalar, &mut expected);
+
+    for level in SimdLevel::available() {
+        let mut actual = values.to_vec();
+        kernel(level, &mut actual);
+
+        for ((input, expected), actual) in values.iter().zip(&expected).zip(&actual) {
+            // Widening is exact, so it keeps the bits apart.
+            let (expected, actual) = (expected.to_f64(), actual.to_f64());
+            assert!(
+                expected.to_bits() == actual.to_bits() || (expected.is_nan() && actual.is_nan()),
+                "{:?}: f({:?}) = {}, expected {}",
+                level,
+                input,
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn levels_are_detected_in_order() {
+    let available: Vec<_> = SimdLevel::available().collect();
+    assert_eq!(Some(&SimdLevel::Scalar), available.first());
+    assert_eq!(Some(&SimdLevel::detect()), available.last());
+
+    #[cfg(target_arch = "x86_64")]
+    assert!(available.contains(&SimdLevel::Sse2));
+}
+
+#[test]
+fn exp2_matches_scalar() {
+    assert_matches_scalar(simd::exp2_with, &inputs(-30.0, 30.0, 1001));
+    assert_matches_scalar(simd::exp2_with, &inputs(-0.51, 0.51, 103));
+
+    let edges = [
+        0.0,
+        -0.0,
+        0.5,
+        -0.5,
+        1.5,
+        -1.5,
+        1e-300,
+        -1e-300,
+        1e9,
+        -1e9,
+        2e9,
+        -2e9,
+        1e10,
+        -1e10,
+        1021.9,
+        1023.4,
+        1023.5,
+        1024.0,
+        -1022.0,
+        -1022.6,
+        -1100.0,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+        3.25,
+    ];
+    assert_matches_scalar(simd::exp2_with, &edges);
+}
+
+#[test]
+fn wrap01_matches_scalar() {
+    assert_matches_scalar(simd::wrap01_with, &inputs(-3.0, 3.0, 1001));
+
+    let edges = [
+        0.0,
+        -0.0,
+        1.0,
+        -1.0,
+        2.0,
+        -2.0,
+        1.0 + f64::EPSILON,
+        -f64::EPSILON,
+        -1e-300,
+        1e9,
+        -1e9,
+        3e9,
+        -3e9,
+        1e300,
+        -1e300,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+    assert_matches_scalar(simd::wrap01_with, &edges);
+}
+
+#[test]
+fn parabolic_sine_matches_scalar() {
+    use std::f64::consts::PI;
+
+    assert_matches_scalar(simd::parabolic_sine_with, &inputs(-PI, PI, 1001));
+
+    let edges = [0.0, -0.0, PI, -PI, PI / 2.0, -PI / 2.0, 1e-300, f64::NAN];
+    assert_matches_scalar(simd::parabolic_sine_with, &edges);
+}
+
+#[test]
+fn f32_exp2_matches_scalar() {
+    assert_matches_scalar(simd::exp2_f32_with, &inputs(-30.0, 30.0, 1001));
+    assert_matches_scalar(simd::exp2_f32_with, &inputs(-0.51, 0.51, 103));
+
+    let edges = [
+        0.0,
+        -0.0,
+        0.5,
+        -0.5,
+        1.5,
+        -1.5,
+        1e-30,
+        -1e-30,
+        1e9,
+        -1e9,
+        1e10,
+        -1e10,
+        125.9,
+        127.4,
+        127.5,
+        128.0,
+        -126.0,
+        -126.6,
+        -140.0,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+        3.25,
+    ];
+    assert_matches_scalar(simd::exp2_f32_with, &edges);
+}
+
+#[test]
+fn f32_wrap01_matches_scalar() {
+    assert_matches_scalar(simd::wrap01_f32_with, &inputs(-3.0, 3.0, 1001));
+
+    let edges = [
+        0.0,
+        -0.0,
+        1.0,
+        -1.0,
+        2.0,
+        -2.0,
+        1.0 + f32::EPSILON,
+        -f32::EPSILON,
+        -1e-30,
+        1e9,
+        -1e9,
+        3e9,
+        -3e9,
+        1e30,
+        -1e30,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+    ];
+    assert_matches_scalar(simd::wrap01_f32_with, &edges);
+}
+
+#[test]
+fn f32_parabolic_sine_matches_scalar() {
+    use std::f32::consts::PI;
+
+    assert_matches_scalar(
+        simd::parabolic_sine_f32_with,
+        &inputs(-PI as f64, PI as f64, 1001),
+    );
+
+    let edges = [0.0, -0.0, PI, -PI, PI / 2.0, -PI / 2.0, 1e-30, f32::NAN];
+    assert_matches_scalar(simd::parabolic_sine_f32_with, &edges);
+}
+
+#[test]
+fn default_kernels_use_the_best_level() {
+    let values = inputs(-2.0, 2.0, 37);
+
+    let mut expected = values.clone();
+    simd::wrap01_with(SimdLevel::detect(), &mut expected);
+    let mut actual = values;
+    simd::wrap01(&mut actual);
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn default_f32_kernels_use_the_best_level() {
+    let values = inputs(-2.0, 2.0, 37);
+
+    let mut expected = values.clone();
+    simd::wrap01_f32_with(SimdLevel::detect(), &mut expected);
+    let mut actual: Vec<f32> = values;
+    simd::wrap01(&mut actual);
+    assert_eq!(expected, actual);
+}
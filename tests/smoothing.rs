@@ -0,0 +1,135 @@
+use dsp_perf::smoothing::{SmoothedParam, Smoothing};
+
+const SAMPLE_RATE: f64 = 1000.0;
+
+fn ticks(param: &mut SmoothedParam, frames: usize) -> Vec<f64> {
+    (0..frames).map(|_| param.tick()).collect()
+}
+
+#[test]
+fn off_jumps_to_the_target() {
+    let mut param = SmoothedParam::new(1.0, Smoothing::Off, SAMPLE_RATE);
+    param.set_target(3.0);
+    assert_eq!(3.0, param.value());
+    assert_eq!(vec![3.0; 4], ticks(&mut param, 4));
+}
+
+#[test]
+fn zero_time_glides_jump_to_the_target() {
+    for &smoothing in [
+        Smoothing::Linear(0.0),
+        Smoothing::OnePole(0.0),
+        Smoothing::OnePole(0.5 / SAMPLE_RATE),
+    ]
+    .iter()
+    {
+        let mut param = SmoothedParam::new(1.0, smoothing, SAMPLE_RATE);
+        param.set_target(3.0);
+        assert_eq!(3.0, param.value(), "{:?}", smoothing);
+        assert_eq!(vec![3.0; 4], ticks(&mut param, 4), "{:?}", smoothing);
+    }
+}
+
+#[test]
+fn linear_ramps_to_the_target_in_the_set_time() {
+    let mut param = SmoothedParam::new(0.0, Smoothing::Linear(0.01), SAMPLE_RATE);
+    param.set_target(1.0);
+
+    let values = ticks(&mut param, 12);
+    for (i, value) in values.iter().take(10).enumerate() {
+        assert!(
+            (value - (i + 1) as f64 / 10.0).abs() < 1e-12,
+            "{:?}",
+            values
+        );
+    }
+    assert_eq!(1.0, values[9]);
+    assert_eq!(&[1.0, 1.0], &values[10..]);
+    assert!(param.is_settled());
+}
+
+#[test]
+fn linear_retargets_from_the_current_value() {
+    let mut param = SmoothedParam::new(0.0, Smoothing::Linear(0.01), SAMPLE_RATE);
+    param.set_target(1.0);
+    ticks(&mut param, 5);
+
+    param.set_target(0.0);
+    let values = ticks(&mut param, 10);
+    assert!((values[0] - 0.45).abs() < 1e-12, "{:?}", values);
+    assert_eq!(0.0, values[9]);
+}
+
+#[test]
+fn one_pole_approaches_the_target_exponentially() {
+    let mut param = SmoothedParam::new(0.0, Smoothing::OnePole(0.01), SAMPLE_RATE);
+    param.set_target(1.0);
+
+    // One time constant covers 1 - 1/e of the distance.
+    let values = ticks(&mut param, 10);
+    assert!((values[9] - (1.0 - (-1.0f64).exp())).abs() < 1e-9);
+    assert!(values.windows(2).all(|pair| pair[1] > pair[0]));
+
+    // And it snaps to the target once it's close enough.
+    ticks(&mut param, 1000);
+    assert!(param.is_settled());
+    assert_eq!(1.0, param.value());
+}
+
+#[test]
+fn fill_matches_ticking() {
+    for &smoothing in [Smoothing::Linear(0.013), Smoothing::OnePole(0.005)].iter() {
+        let mut ticked = SmoothedParam::new(2.0, smoothing, SAMPLE_RATE);
+        let mut filled = ticked;
+        ticked.set_target(-1.0);
+        filled.set_target(-1.0);
+
+        let expected = ticks(&mut ticked, 100);
+        let mut actual = vec![0.0; 100];
+        for block in actual.chunks_mut(7) {
+            filled.fill(block);
+        }
+
+        assert_eq!(expected, actual, "{:?}", smoothing);
+    }
+}
+
+#[test]
+fn finish_and_set_smoothing_end_the_glide() {
+    let mut param = SmoothedParam::new(0.0, Smoothing::Linear(0.1), SAMPLE_RATE);
+    param.set_target(1.0);
+    ticks(&mut param, 3);
+    param.finish();
+    assert_eq!(1.0, param.tick());
+
+    param.set_target(2.0);
+    ticks(&mut param, 3);
+    param.set_smoothing(Smoothing::OnePole(0.1), SAMPLE_RATE);
+    assert_eq!(2.0, param.tick());
+    assert_eq!(2.0, param.target());
+}
+
+#[test]
+fn non_finite_targets_do_not_break_the_glide() {
+    for &smoothing in [Smoothing::Linear(0.01), Smoothing::OnePole(0.01)].iter() {
+        let mut param = SmoothedParam::new(0.0, smoothing, SAMPLE_RATE);
+        param.set_target(f64::NAN);
+        assert!(ticks(&mut param, 100).last().unwrap().is_nan());
+
+        param.set_target(f64::INFINITY);
+        ticks(&mut param, 100);
+
+        // A later finite target is still reached.
+        param.set_target(1.0);
+        param.finish();
+        assert_eq!(1.0, param.tick());
+    }
+
+    // A linear glide away from NaN lands on its target on time.
+    let mut param = SmoothedParam::new(0.0, Smoothing::Linear(0.01), SAMPLE_RATE);
+    param.set_target(f64::NAN);
+    ticks(&mut param, 20);
+    param.set_target(1.0);
+    assert_eq!(1.0, ticks(&mut param, 10)[9]);
+    assert!(param.is_settled());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{assert_renders_match, compare, SAMPLE_RATE};
+use dsp_perf::tempo::{Division, Transport};
+use dsp_perf::{one_frame_per_call, SynthEngine, ENGINES};
+
+const EPSILON: f64 = 1e-12;
+
+const BLOCK_SIZE: usize = 441;
+
+#[test]
+fn division_lengths() {
+    let lengths = [
+        (Division::new(1, 4), 1.0),
+        (Division::new(1, 16), 0.25),
+        (Division::new(3, 4), 3.0),
+        (Division::new(1, 8).dotted(), 0.75),
+        (Division::new(1, 4).dotted(), 1.5),
+        (Division::new(1, 8).triplet(), 1.0 / 3.0),
+        (Division::new(1, 2).triplet(), 4.0 / 3.0),
+        (Division::bars(1), 4.0),
+        (Division::bars(4), 16.0),
+    ];
+
+    for &(division, beats) in lengths.iter() {
+        assert!(
+            (division.beats() - beats).abs() < EPSILON,
+            "{:?}: expected {} beats, got {}",
+            division,
+            beats,
+            division.beats()
+        );
+    }
+}
+
+#[test]
+fn division_rates() {
+    assert!((Division::new(1, 4).frequency(120.0).0 - 2.0).abs() < EPSILON);
+    assert!((Division::new(1, 8).triplet().frequency(120.0).0 - 6.0).abs() < EPSILON);
+    assert!((Division::bars(2).frequency(120.0).0 - 0.25).abs() < EPSILON);
+}
+
+#[test]
+fn phase_follows_song_position() {
+    let half = Division::new(1, 2);
+    assert_eq!(0.0, half.phase(0.0));
+    assert_eq!(0.25, half.phase(0.5));
+    assert_eq!(0.5, half.phase(5.0));
+    assert_eq!(0.75, half.phase(-0.5));
+    assert_eq!(0.0, half.phase(-1e-20));
+}
+
+/// Render `blocks` blocks, telling the synth where each block starts. The
+/// song jumps back to `loop_start` after `loop_end` beats.
+fn render_with_transport(
+    synth: &mut dyn SynthEngine,
+    bpm: f64,
+    start: f64,
+    (loop_start, loop_end): (f64, f64),
+    blocks: usize,
+) -> Vec<f64> {
+    let beats_per_block = BLOCK_SIZE as f64 / SAMPLE_RATE * bpm / 60.0;
+    let mut position = start;
+
+    let mut output = vec![0.0; blocks * BLOCK_SIZE];
+    for block in output.chunks_mut(BLOCK_SIZE) {
+        synth.render_with_transport(block, &Transport { bpm, position });
+
+        position += beats_per_block;
+        if position >= loop_end {
+            position -= loop_end - loop_start;
+        }
+    }
+    output
+}
+
+#[test]
+fn engines_match_with_tempo_synced_lfo() {
+    let division = Some(Division::new(1, 8).dotted());
+    let bpm = 133.0;
+    let looped = (2.0, 3.7);
+
+    let mut reference = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    reference.set_lfo_tempo_sync(division);
+    let expected = render_with_transport(&mut reference, bpm, 0.0, looped, 100);
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_lfo_tempo_sync(division);
+        let actual = render_with_transport(&mut *synth, bpm, 0.0, looped, 100);
+
+        assert_renders_match(
+            &format!("{}, with tempo-synced LFO", engine.name),
+            &expected,
+            &actual,
+            1e-9,
+        );
+    }
+}
+
+#[test]
+fn synced_lfo_phase_is_locked_to_song_position() {
+    let render_from = |position| {
+        let mut synth = one_frame_per_call::Synth::new(SAMPLE_RATE);
+        synth.set_lfo_tempo_sync(Some(Division::new(1, 2)));
+        render_with_transport(&mut synth, 120.0, position, (0.0, f64::MAX), 50)
+    };
+
+    let from_start = render_from(0.0);
+
+    // Whole cycles later the LFO lines up with the start of the song, up to
+    // rounding in the song position. Half a cycle later it doesn't.
+    assert!(compare(&from_start, &render_from(8.0), 1e-9).is_none());
+    assert!(compare(&from_start, &render_from(1.0), 1e-9).is_some());
+}
+
+#[test]
+fn unsynced_lfo_ignores_the_transport() {
+    let mut synced = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    synced.set_lfo_tempo_sync(Some(Division::new(1, 4)));
+    synced.set_lfo_tempo_sync(None);
+    let actual = render_with_transport(&mut synced, 97.0, 1.3, (0.0, f64::MAX), 50);
+
+    let mut free = one_frame_per_call::Synth::new(SAMPLE_RATE);
+    let expected = common::render_in_blocks(&mut free, 50 * BLOCK_SIZE, BLOCK_SIZE);
+
+    assert_renders_match("unsynced LFO", &expected, &actual, 0.0);
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use common::{assert_renders_match, SAMPLE_RATE};
+use dsp_perf::engine::Oscillator;
+use dsp_perf::envelope::{AdsrSettings, Curve};
+use dsp_perf::modulation::ModSource;
+use dsp_perf::params::ParamId;
+use dsp_perf::pitch::MidiNote;
+use dsp_perf::polyphony::{PolySynth, StealPolicy};
+use dsp_perf::sample::Sample;
+use dsp_perf::smoothing::Smoothing;
+use dsp_perf::voice_per_lane;
+use dsp_perf::{one_frame_per_call, SynthEngine};
+
+const TOLERANCE: f64 = 1e-9;
+
+fn create_one_frame<S: Sample>(sample_rate: f64) -> Box<dyn SynthEngine<S>> {
+    Box::new(one_frame_per_call::Synth::<S>::new(sample_rate))
+}
+
+/// The same note events and patch changes, on either polyphonic engine.
+trait Poly {
+    fn note_on(&mut self, note: f64);
+    fn note_off(&mut self, note: f64);
+    fn set_parameter(&mut self, id: ParamId, value: f64);
+    fn render(&mut self, frames: usize) -> Vec<f64>;
+}
+
+impl<S: Sample> Poly for PolySynth<S> {
+    fn note_on(&mut self, note: f64) {
+        PolySynth::note_on(self, MidiNote(note));
+    }
+
+    fn note_off(&mut self, note: f64) {
+        PolySynth::note_off(self, MidiNote(note));
+    }
+
+    fn set_parameter(&mut self, id: ParamId, value: f64) {
+        PolySynth::set_parameter(self, id, value);
+    }
+
+    fn render(&mut self, frames: usize) -> Vec<f64> {
+        let mut buffer = vec![S::ZERO; frames];
+        PolySynth::render(self, &mut buffer);
+        buffer.iter().map(|sample| sample.to_f64()).collect()
+    }
+}
+
+impl<const LANES: usize, S: Sample> Poly for voice_per_lane::Synth<LANES, S> {
+    fn note_on(&mut self, note: f64) {
+        voice_per_lane::Synth::note_on(self, MidiNote(note));
+    }
+
+    fn note_off(&mut self, note: f64) {
+        voice_per_lane::Synth::note_off(self, MidiNote(note));
+    }
+
+    fn set_parameter(&mut self, id: ParamId, value: f64) {
+        voice_per_lane::Synth::set_parameter(self, id, value);
+    }
+
+    fn render(&mut self, frames: usize) -> Vec<f64> {
+        let mut buffer = vec![S::ZERO; frames];
+        voice_per_lane::Synth::render(self, &mut buffer);
+        buffer.iter().map(|sample| sample.to_f64()).collect()
+    }
+}
+
+/// Overlapping notes, more of them than there are voices, with patch changes
+/// in between and renders of varying length.
+fn play(synth: &mut dyn Poly) -> Vec<f64> {
+    let mut output = Vec::new();
+
+    synth.set_parameter(ParamId::Osc1Waveform, 1.0);
+    synth.set_parameter(ParamId::Osc2Waveform, 2.0);
+    synth.set_parameter(ParamId::Level(Oscillator::Lfo), 0.02);
+    synth.set_parameter(ParamId::LfoFrequency, 5.0);
+
+    for (i, &note) in [60.0, 64.0, 67.0, 71.0, 72.0, 48.0, 55.0, 76.0, 79.0, 62.0]
+        .iter()
+        .enumerate()
+    {
+        synth.note_on(note);
+        output.extend(synth.render(100 + 97 * i));
+
+        if i % 3 == 1 {
+            synth.note_off(note);
+        }
+        if i == 4 {
+            synth.set_parameter(ParamId::Semitones(Oscillator::Osc2), 7.0);
+            synth.set_parameter(ParamId::LfoWaveform, 5.0);
+            synth.set_parameter(ParamId::LfoMode, 1.0);
+        }
+    }
+
+    for &note in [60.0, 64.0, 67.0, 71.0, 72.0, 48.0, 55.0, 76.0, 79.0, 62.0].iter() {
+        synth.note_off(note);
+        output.extend(synth.render(300));
+    }
+
+    output
+}
+
+fn assert_matches_poly_synth<const LANES: usize>(
+    name: &str,
+    configure_poly: impl Fn(&mut PolySynth),
+    configure_lanes: impl Fn(&mut voice_per_lane::Synth<LANES>),
+) {
+    let mut poly = PolySynth::new(create_one_frame::<f64>, SAMPLE_RATE, LANES);
+    configure_poly(&mut poly);
+    let expected = play(&mut poly);
+
+    let mut lanes = voice_per_lane::Synth::<LANES>::new(SAMPLE_RATE);
+    configure_lanes(&mut lanes);
+    let actual = play(&mut lanes);
+
+    assert_renders_match(
+        &format!("{} with {} lanes", name, LANES),
+        &expected,
+        &actual,
+        TOLERANCE,
+    );
+}
+
+fn envelope() -> AdsrSettings {
+    AdsrSettings {
+        attack: 0.005,
+        decay: 0.01,
+        release: 0.004,
+        curve: Curve::Exponential,
+        ..AdsrSettings::default()
+    }
+}
+
+#[test]
+fn lanes_match_poly_synth_with_amp_envelope() {
+    assert_matches_poly_synth::<4>(
+        "amp envelope",
+        |poly| poly.for_each_voice(|synth| synth.set_amp_envelope(Some(envelope()))),
+        |lanes| lanes.set_amp_envelope(Some(envelope())),
+    );
+    assert_matches_poly_synth::<8>(
+        "amp envelope",
+        |poly| poly.for_each_voice(|synth| synth.set_amp_envelope(Some(envelope()))),
+        |lanes| lanes.set_amp_envelope(Some(envelope())),
+    );
+}
+
+#[test]
+fn lanes_match_poly_synth_without_amp_envelope() {
+    assert_matches_poly_synth::<4>("no envelope", |_| {}, |_| {});
+}
+
+#[test]
+fn lanes_match_poly_synth_with_every_setting() {
+    let configure_poly = |poly: &mut PolySynth| {
+        poly.set_steal_policy(StealPolicy::Quietest);
+        poly.for_each_voice(|synth| {
+            synth.set_amp_envelope(Some(envelope()));
+            synth.set_parameter_smoothing(Smoothing::OnePole(0.005));
+            synth.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+            synth.set_lfo_delay(0.002, 0.003);
+        });
+    };
+    let configure_lanes = |lanes: &mut voice_per_lane::Synth<4>| {
+        lanes.set_steal_policy(StealPolicy::Quietest);
+        lanes.set_amp_envelope(Some(envelope()));
+        lanes.set_parameter_smoothing(Smoothing::OnePole(0.005));
+        lanes.set_osc2_frequency_mod_source(ModSource::LfoQuadrature);
+        lanes.set_lfo_delay(0.002, 0.003);
+    };
+
+    assert_matches_poly_synth::<4>("every setting", configure_poly, configure_lanes);
+}
+
+#[test]
+fn lanes_play_low_notes_at_their_pitch() {
+    let play_low_notes = |synth: &mut dyn Poly| {
+        for &note in [0.0, 5.0, 12.0, 15.0].iter() {
+            synth.note_on(note);
+        }
+        synth.render(44100)
+    };
+
+    let mut poly = PolySynth::new(create_one_frame::<f64>, SAMPLE_RATE, 4);
+    let expected = play_low_notes(&mut poly);
+    let mut lanes = voice_per_lane::Synth::<4>::new(SAMPLE_RATE);
+    let actual = play_low_notes(&mut lanes);
+
+    assert_renders_match("low notes", &expected, &actual, TOLERANCE);
+}
+
+#[test]
+fn lanes_track_sounding_notes() {
+    let mut lanes = voice_per_lane::Synth::<4>::new(SAMPLE_RATE);
+    lanes.set_amp_envelope(Some(envelope()));
+    assert_eq!(4, lanes.voice_count());
+
+    for &note in [60.0, 62.0, 64.0, 65.0, 67.0].iter() {
+        lanes.note_on(MidiNote(note));
+    }
+    let mut notes: Vec<f64> = lanes.sounding_notes().map(|note| note.0).collect();
+    notes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(vec![62.0, 64.0, 65.0, 67.0], notes);
+
+    lanes.all_notes_off();
+    assert_eq!(4, lanes.active_voices());
+    lanes.render(&mut [0.0; 1000]);
+    assert_eq!(0, lanes.active_voices());
+}
+
+#[test]
+fn f32_lanes_match_f32_poly_synth() {
+    let mut poly = PolySynth::new(create_one_frame::<f32>, SAMPLE_RATE, 4);
+    poly.for_each_voice(|synth| synth.set_amp_envelope(Some(envelope())));
+    let expected = play(&mut poly);
+
+    let mut lanes = voice_per_lane::Synth::<4, f32>::new(SAMPLE_RATE);
+    lanes.set_amp_envelope(Some(envelope()));
+    let actual = play(&mut lanes);
+
+    assert_renders_match("f32", &expected, &actual, TOLERANCE);
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use common::{render_in_blocks, SAMPLE_RATE};
+use dsp_perf::engine::Oscillator;
+use dsp_perf::params::ParamId;
+use dsp_perf::pitch::Hz;
+use dsp_perf::smoothing::Smoothing;
+use dsp_perf::waveform::{
+    poly_blamp, poly_blep, saw, sine, square, sync_correction, triangle, wrap_fraction, LfoFade,
+    RandomSteps,
+};
+use dsp_perf::ENGINES;
+
+const EPSILON: f64 = 1e-9;
+
+/// Phase increment of 56/1024 cycles per frame, so every harmonic lands
+/// exactly on a bin of a 1024 point DFT, aliased or not.
+const DFT_SIZE: usize = 1024;
+const CYCLES_PER_DFT: usize = 56;
+
+#[test]
+fn corrections_vanish_away_from_discontinuities() {
+    let dt = 0.01;
+    for &t in [0.011, 0.25, 0.5, 0.75, 0.989].iter() {
+        assert_eq!(0.0, poly_blep(t, dt));
+        assert_eq!(0.0, poly_blamp(t, dt));
+    }
+}
+
+#[test]
+fn corrected_saw_and_square_are_continuous_across_steps() {
+    let dt = 0.05;
+
+    let before = saw(1.0 - EPSILON, dt);
+    let after = saw(EPSILON, dt);
+    assert!((before - after).abs() < 1e-6, "{} vs {}", before, after);
+
+    for &pulse_width in [0.5, 0.25, 0.9].iter() {
+        let before = square(pulse_width - EPSILON, dt, pulse_width);
+        let after = square(pulse_width + EPSILON, dt, pulse_width);
+        assert!((before - after).abs() < 1e-6, "{} vs {}", before, after);
+
+        let before = square(1.0 - EPSILON, dt, pulse_width);
+        let after = square(EPSILON, dt, pulse_width);
+        assert!((before - after).abs() < 1e-6, "{} vs {}", before, after);
+    }
+}
+
+#[test]
+fn triangle_is_in_phase_with_sine() {
+    for &(modulo, expected) in [(0.0f64, 0.0), (0.25, 1.0), (0.5, 0.0), (0.75, -1.0)].iter() {
+        assert!((triangle(modulo, 0.0) - expected).abs() < EPSILON);
+        assert!((sine(modulo) - expected).abs() < 1e-3);
+    }
+}
+
+/// Power in all DFT bins that don't hold a harmonic below Nyquist, relative
+/// to the total power.
+fn relative_alias_power<F: Fn(f64, f64) -> f64>(shape: F, band_limited: bool) -> f64 {
+    let phase_increment = CYCLES_PER_DFT as f64 / DFT_SIZE as f64;
+    let signal: Vec<f64> = (0..DFT_SIZE)
+        .map(|i| {
+            let modulo = (i as f64 * phase_increment).fract();
+            shape(modulo, if band_limited { phase_increment } else { 0.0 })
+        })
+        .collect();
+
+    let mut alias_power = 0.0;
+    let mut total_power = 0.0;
+
+    for bin in 1..DFT_SIZE / 2 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, x) in signal.iter().enumerate() {
+            let angle = 2.0 * std::f64::consts::PI * (bin * i) as f64 / DFT_SIZE as f64;
+            re += x * angle.cos();
+            im -= x * angle.sin();
+        }
+        let power = re * re + im * im;
+
+        total_power += power;
+        if bin % CYCLES_PER_DFT != 0 {
+            alias_power += power;
+        }
+    }
+
+    alias_power / total_power
+}
+
+fn assert_reduces_aliasing<F: Fn(f64, f64) -> f64>(name: &str, shape: F) {
+    let naive = relative_alias_power(&shape, false);
+    let band_limited = relative_alias_power(&shape, true);
+
+    assert!(
+        band_limited < naive * 0.1,
+        "{}: alias power {:e} (naive {:e})",
+        name,
+        band_limited,
+        naive
+    );
+}
+
+#[test]
+fn corrections_reduce_aliasing() {
+    assert_reduces_aliasing("saw", saw);
+    assert_reduces_aliasing("square", |modulo, dt| square(modulo, dt, 0.5));
+    assert_reduces_aliasing("pulse", |modulo, dt| square(modulo, dt, 0.3));
+    assert_reduces_aliasing("triangle", triangle);
+}
+
+#[test]
+fn sync_correction_only_applies_around_master_wraps() {
+    let naive = |modulo: f64| saw(modulo, 0.0);
+    let dt = 0.01;
+
+    // Far from the master's wrap there is nothing to correct.
+    assert_eq!(0.0, sync_correction(naive, false, 0.5, dt, 0.0, 0.3, 0.02));
+
+    // Right before and right after the wrap the slave's step is smoothed.
+    assert!(sync_correction(naive, false, 0.995, dt, 0.0, 0.3, 0.02) != 0.0);
+    assert!(sync_correction(naive, true, 0.005, dt, 0.6, 0.01, 0.02) != 0.0);
+}
+
+/// Times of the rising zero crossings, in frames, interpolated between the
+/// frames either side.
+fn rising_zero_crossings(output: &[f64]) -> Vec<f64> {
+    output
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+        .map(|(i, pair)| i as f64 + pair[0] / (pair[0] - pair[1]))
+        .collect()
+}
+
+#[test]
+fn hard_sync_locks_osc2_to_osc1_between_frames() {
+    // A period of osc1 that isn't a whole number of frames, and osc2 an
+    // octave and a tritone above, so it runs 2.83 cycles per period of osc1.
+    let frequency = 310.0;
+    let ratio = 2f64.powf(18.0 / 12.0);
+    let period = SAMPLE_RATE / frequency;
+
+    for engine in ENGINES {
+        let mut synth = (engine.create)(SAMPLE_RATE);
+        synth.set_parameter_smoothing(Smoothing::Off);
+        synth.set_lfo_frequency(Hz(0.0));
+        synth.set_amplitude(Oscillator::Osc1, 0.0);
+        synth.set_parameter(ParamId::Osc2Waveform, 1.0);
+        synth.set_parameter(ParamId::Octaves(Oscillator::Osc2), 1.0);
+        synth.set_parameter(ParamId::Semitones(Oscillator::Osc2), 6.0);
+        synth.set_parameter(ParamId::Cents(Oscillator::Osc2), 0.0);
+        synth.set_parameter(ParamId::Frequency, frequency);
+        synth.set_hard_sync(true);
+
+        // The saw crosses zero rising halfway through each of its cycles, so
+        // three times for every period of osc1. Skip the first period.
+        let output = render_in_blocks(&mut *synth, SAMPLE_RATE as usize, 512);
+        let crossings = rising_zero_crossings(&output);
+        let crossings = &crossings[3..];
+        assert!(crossings.len() > 900, "{}", engine.name);
+
+        // Each reset lands between frames, exactly one period of osc1 after
+        // the last one, however the period falls on the frames.
+        for (i, window) in crossings.windows(4).enumerate() {
+            let error = (window[3] - window[0] - period).abs();
+            assert!(
+                error < 1e-6,
+                "{}: crossing {} is {} frames off",
+                engine.name,
+                i + 3,
+                error
+            );
+        }
+
+        // In between, osc2 runs at its own frequency.
+        for group in crossings.chunks_exact(3) {
+            let error = (group[1] - group[0] - period / ratio).abs();
+            assert!(error < 1e-6, "{}: {} frames off", engine.name, error);
+        }
+    }
+}
+
+#[test]
+fn wrap_fraction_locates_the_wrap_within_the_frame() {
+    assert!((wrap_fraction(0.025f64, 0.1) - 0.25).abs() < EPSILON);
+    assert!((wrap_fraction(0.975f64, -0.1) - 0.25).abs() < EPSILON);
+}
+
+#[test]
+fn random_steps_are_deterministic_and_bounded() {
+    let mut a = RandomSteps::new(7);
+    let mut b = RandomSteps::new(7);
+    let mut distinct = 0;
+    let mut last = a.held();
+
+    for _ in 0..1000 {
+        a.advance();
+        b.advance();
+        assert_eq!(a.held(), b.held());
+        assert!((-1.0..1.0).contains(&a.held()), "{} out of range", a.held());
+        if a.held() != last {
+            distinct += 1;
+        }
+        last = a.held();
+    }
+
+    assert!(distinct > 990);
+}
+
+#[test]
+fn smoothed_random_is_continuous_across_steps() {
+    let mut steps = RandomSteps::new(3);
+
+    for _ in 0..100 {
+        let end_of_cycle = steps.smoothed(1.0);
+        assert!((steps.held() - end_of_cycle).abs() < EPSILON);
+
+        steps.advance();
+        assert!((end_of_cycle - steps.smoothed(0.0)).abs() < EPSILON);
+    }
+}
+
+#[test]
+fn lfo_fade_delays_then_ramps_up() {
+    let mut fade = LfoFade::default();
+    assert_eq!(1.0, fade.next_gain());
+
+    fade.set_times(0.5, 0.25, 8.0);
+    fade.restart();
+    let gains: Vec<f64> = (0..8).map(|_| fade.next_gain()).collect();
+    assert_eq!(vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.5, 1.0, 1.0], gains);
+
+    fade.restart();
+    assert_eq!(0.0, fade.next_gain());
+}
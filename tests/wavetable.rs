@@ -0,0 +1,95 @@
+use std::f64::consts::PI;
+
+use dsp_perf::wavetable::Wavetable;
+
+fn harmonic(h: usize, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|i| (2.0 * PI * (h * i) as f64 / len as f64).sin())
+        .collect()
+}
+
+fn peak(wavetable: &Wavetable, phase_increment: f64, position: f64) -> f64 {
+    (0..4096)
+        .map(|i| {
+            let modulo = i as f64 / 4096.0;
+            wavetable.sample(modulo, phase_increment, position).abs()
+        })
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn reproduces_band_limited_source_at_low_pitch() {
+    let source: Vec<f64> = (0..600)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f64 / 600.0;
+            angle.sin() + 0.5 * (3.0 * angle).cos() - 0.25 * (7.0 * angle).sin()
+        })
+        .collect();
+    let wavetable = Wavetable::from_single_cycle(&source);
+
+    for (i, expected) in source.iter().enumerate() {
+        let actual = wavetable.sample(i as f64 / 600.0, 1e-4, 0.0);
+        assert!(
+            (expected - actual).abs() < 1e-4,
+            "sample {}: expected {}, got {}",
+            i,
+            expected,
+            actual
+        );
+    }
+}
+
+#[test]
+fn drops_harmonics_above_nyquist() {
+    let wavetable = Wavetable::from_single_cycle(&harmonic(100, 2048));
+
+    // Below 1/200 cycles per frame, the 100th harmonic is below Nyquist.
+    assert!(peak(&wavetable, 1.0 / 1000.0, 0.0) > 0.99);
+
+    // Above 1/200 it would alias and must be gone entirely.
+    assert!(peak(&wavetable, 1.0 / 150.0, 0.0) < 1e-9);
+    assert!(peak(&wavetable, -1.0 / 150.0, 0.0) < 1e-9);
+}
+
+#[test]
+fn level_crossfade_is_continuous() {
+    let saw: Vec<f64> = (0..2048).map(|i| 2.0 * i as f64 / 2048.0 - 1.0).collect();
+    let wavetable = Wavetable::from_single_cycle(&saw);
+
+    let mut last: f64 = wavetable.sample(0.3, 1e-4, 0.0);
+    let mut phase_increment = 1e-4;
+    while phase_increment < 0.5 {
+        phase_increment *= 1.01;
+        let next = wavetable.sample(0.3, phase_increment, 0.0);
+        assert!(
+            (next - last).abs() < 0.05,
+            "jump from {} to {} at increment {}",
+            last,
+            next,
+            phase_increment
+        );
+        last = next;
+    }
+}
+
+#[test]
+fn position_morphs_between_frames() {
+    let first = vec![0.5; 256];
+    let second = harmonic(1, 256);
+    let third = vec![-0.5; 256];
+    let wavetable = Wavetable::from_frames(&[&first, &second, &third]);
+
+    assert_eq!(3, wavetable.frame_count());
+
+    let quarter_cycle: f64 = 0.25;
+    let at = |position| wavetable.sample(quarter_cycle, 1e-3, position);
+
+    assert!((at(0.0) - 0.5).abs() < 1e-9);
+    assert!((at(0.5) - 1.0).abs() < 1e-6);
+    assert!((at(1.0) + 0.5).abs() < 1e-9);
+    assert!((at(0.25) - 0.75).abs() < 1e-6);
+
+    // Positions outside the table are clamped to the first and last frames.
+    assert!((at(-1.0) - 0.5).abs() < 1e-9);
+    assert!((at(2.0) + 0.5).abs() < 1e-9);
+}